@@ -0,0 +1,166 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, GrayImage};
+
+// Offsets are searched exhaustively only on a pyramid level where the
+// remaining search radius is at most this, finer levels just refine.
+const COARSE_RADIUS: u32 = 4;
+// Below this size a pyramid level has too little detail to match on
+const MIN_LEVEL_SIZE: u32 = 16;
+
+/// Both images cropped to the area they share after compensating for the
+/// detected translation
+#[derive(Debug)]
+pub struct AlignedImages {
+    pub image1: DynamicImage,
+    pub image2: DynamicImage,
+    /// Translation of image2 relative to image1: content at (x, y) in image1
+    /// is found at (x + dx, y + dy) in image2
+    pub offset: (i32, i32),
+}
+
+// Overlapping region of image1 for an offset: (x, y, width, height)
+fn overlap(
+    size1: (u32, u32),
+    size2: (u32, u32),
+    offset: (i32, i32),
+) -> Option<(u32, u32, u32, u32)> {
+    let x1 = 0.max(-offset.0);
+    let y1 = 0.max(-offset.1);
+    let x2 = (size1.0 as i32).min(size2.0 as i32 - offset.0);
+    let y2 = (size1.1 as i32).min(size2.1 as i32 - offset.1);
+    if x2 <= x1 || y2 <= y1 {
+        return None;
+    }
+    Some((x1 as u32, y1 as u32, (x2 - x1) as u32, (y2 - y1) as u32))
+}
+
+// Mean absolute luma difference over the overlap, None when the images
+// would overlap by less than half of the smaller one
+fn cost(a: &GrayImage, b: &GrayImage, offset: (i32, i32)) -> Option<f64> {
+    let (x, y, width, height) = overlap(a.dimensions(), b.dimensions(), offset)?;
+    let min_area = (a.width().min(b.width()) as u64 * a.height().min(b.height()) as u64) / 2;
+    if (width as u64 * height as u64) < min_area.max(1) {
+        return None;
+    }
+
+    let mut total = 0u64;
+    for yy in y..y + height {
+        for xx in x..x + width {
+            let pa = a.get_pixel(xx, yy)[0];
+            let pb = b.get_pixel((xx as i32 + offset.0) as u32, (yy as i32 + offset.1) as u32)[0];
+            total += pa.abs_diff(pb) as u64;
+        }
+    }
+    Some(total as f64 / (width as u64 * height as u64) as f64)
+}
+
+fn search(a: &GrayImage, b: &GrayImage, center: (i32, i32), radius: i32) -> (i32, i32) {
+    let mut best = center;
+    let mut best_cost = f64::MAX;
+    for dy in center.1 - radius..=center.1 + radius {
+        for dx in center.0 - radius..=center.0 + radius {
+            if let Some(c) = cost(a, b, (dx, dy)) {
+                // ties go to the smaller shift
+                let closer = dx.abs() + dy.abs() < best.0.abs() + best.1.abs();
+                if c < best_cost || (c == best_cost && closer) {
+                    best_cost = c;
+                    best = (dx, dy);
+                }
+            }
+        }
+    }
+    best
+}
+
+/// Estimate the translation between the images by a coarse-to-fine search
+/// of all offsets up to `max_offset` pixels in each direction
+pub fn estimate_offset(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    max_offset: u32,
+) -> (i32, i32) {
+    let mut levels = vec![(image1.to_luma8(), image2.to_luma8())];
+    while max_offset >> (levels.len() - 1) > COARSE_RADIUS {
+        let (a, b) = levels.last().unwrap();
+        if a.width().min(a.height()).min(b.width()).min(b.height()) / 2 < MIN_LEVEL_SIZE {
+            break;
+        }
+        let half = |img: &GrayImage| {
+            imageops::resize(img, img.width() / 2, img.height() / 2, FilterType::Triangle)
+        };
+        levels.push((half(a), half(b)));
+    }
+
+    let coarsest = levels.len() - 1;
+    let (a, b) = &levels[coarsest];
+    let mut offset = search(a, b, (0, 0), (max_offset >> coarsest) as i32);
+    for (a, b) in levels.iter().rev().skip(1) {
+        offset = search(a, b, (offset.0 * 2, offset.1 * 2), 1);
+    }
+
+    let max = max_offset as i32;
+    (offset.0.clamp(-max, max), offset.1.clamp(-max, max))
+}
+
+/// Find the translation between the images and crop both to their common
+/// area so they can be compared pixel for pixel
+pub fn align_images(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    max_offset: u32,
+) -> Result<AlignedImages, String> {
+    let offset = estimate_offset(image1, image2, max_offset);
+    let (x, y, width, height) = overlap(image1.dimensions(), image2.dimensions(), offset)
+        .ok_or_else(|| "Images do not overlap".to_string())?;
+
+    Ok(AlignedImages {
+        image1: image1.crop_imm(x, y, width, height),
+        image2: image2.crop_imm(
+            (x as i32 + offset.0) as u32,
+            (y as i32 + offset.1) as u32,
+            width,
+            height,
+        ),
+        offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shifted(image: &DynamicImage, dx: u32, dy: u32) -> DynamicImage {
+        image.crop_imm(dx, dy, image.width() - 40, image.height() - 40)
+    }
+
+    #[test]
+    fn test_estimate_offset() {
+        let image =
+            image::open("tests/images/image1.png")
+                .unwrap()
+                .resize(400, 200, FilterType::Triangle);
+        let base = shifted(&image, 20, 20);
+
+        for (dx, dy) in [(0, 0), (3, 0), (0, 7), (13, 5), (20, 20), (2, 15)] {
+            let moved = shifted(&image, dx, dy);
+            let expected = (dx as i32 - 20, dy as i32 - 20);
+            assert_eq!(estimate_offset(&moved, &base, 20), expected);
+        }
+    }
+
+    #[test]
+    fn test_align_images() {
+        let image =
+            image::open("tests/images/image1.png")
+                .unwrap()
+                .resize(400, 200, FilterType::Triangle);
+        let image1 = shifted(&image, 10, 10);
+        let image2 = shifted(&image, 14, 8);
+
+        let aligned = align_images(&image1, &image2, 8).unwrap();
+
+        assert_eq!(aligned.offset, (-4, 2));
+        assert_eq!(aligned.image1.dimensions(), aligned.image2.dimensions());
+        assert_eq!(aligned.image1.to_rgba8(), aligned.image2.to_rgba8());
+    }
+}
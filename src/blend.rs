@@ -0,0 +1,200 @@
+use image::Rgb;
+
+use crate::{BlendMode, ColorSpace};
+
+/// Settings for `BlendMode::OnionSkin`. Each image is turned into a tinted
+/// greyscale layer scaled by its opacity and the layers are added together,
+/// so with complementary tints (e.g. red and cyan at full opacity) content
+/// shared by both images comes out neutral grey and differences in colour.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OnionSkin {
+    pub before_opacity: f32,
+    pub after_opacity: f32,
+    pub before_tint: Rgb<u8>,
+    pub after_tint: Rgb<u8>,
+}
+
+impl Default for OnionSkin {
+    fn default() -> Self {
+        OnionSkin {
+            before_opacity: 1.0,
+            after_opacity: 1.0,
+            before_tint: Rgb([255, 0, 0]),
+            after_tint: Rgb([0, 255, 255]),
+        }
+    }
+}
+
+impl OnionSkin {
+    pub fn blend(&self, before: Rgb<u8>, after: Rgb<u8>) -> Rgb<u8> {
+        let before_luma = luma(before) * self.before_opacity;
+        let after_luma = luma(after) * self.after_opacity;
+
+        let channel = |c: usize| {
+            let value = before_luma * self.before_tint[c] as f32 / 255.0
+                + after_luma * self.after_tint[c] as f32 / 255.0;
+            value.round().clamp(0.0, 255.0) as u8
+        };
+        Rgb([channel(0), channel(1), channel(2)])
+    }
+
+    /// Like `blend`, adding up the light of the layers: takes and returns
+    /// linear RGB components from 0 to 1
+    pub fn blend_linear(&self, before: [f64; 3], after: [f64; 3]) -> [f64; 3] {
+        let before_luma = linear_luma(before) * self.before_opacity as f64;
+        let after_luma = linear_luma(after) * self.after_opacity as f64;
+        let before_tint = ColorSpace::LinearRgb.components(self.before_tint);
+        let after_tint = ColorSpace::LinearRgb.components(self.after_tint);
+
+        [0, 1, 2].map(|c| before_luma * before_tint[c] + after_luma * after_tint[c])
+    }
+}
+
+// Rec. 601 luma
+fn luma(pixel: Rgb<u8>) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+// Rec. 709 relative luminance of linear light
+fn linear_luma(rgb: [f64; 3]) -> f64 {
+    0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+}
+
+// Soft light helper from the W3C compositing spec
+fn soft_light_d(base: f32) -> f32 {
+    if base <= 0.25 {
+        ((16.0 * base - 12.0) * base + 4.0) * base
+    } else {
+        base.sqrt()
+    }
+}
+
+// Blend one channel of the base (image1) with the blend layer (image2), both
+// normalized to 0.0 - 1.0, following the W3C compositing formulas.
+fn blend_unit(base: f32, blend: f32, blend_mode: BlendMode) -> f32 {
+    match blend_mode {
+        BlendMode::Multiply => base * blend,
+        BlendMode::Screen => base + blend - base * blend,
+        BlendMode::Overlay => blend_unit(blend, base, BlendMode::HardLight),
+        BlendMode::HardLight => {
+            if blend <= 0.5 {
+                blend_unit(base, 2.0 * blend, BlendMode::Multiply)
+            } else {
+                blend_unit(base, 2.0 * blend - 1.0, BlendMode::Screen)
+            }
+        }
+        BlendMode::SoftLight => {
+            if blend <= 0.5 {
+                base - (1.0 - 2.0 * blend) * base * (1.0 - base)
+            } else {
+                base + (2.0 * blend - 1.0) * (soft_light_d(base) - base)
+            }
+        }
+        BlendMode::Difference => (base - blend).abs(),
+        BlendMode::Exclusion => base + blend - 2.0 * base * blend,
+        // Not separable, handled in blend_pixel
+        BlendMode::BIAS | BlendMode::HUE | BlendMode::Average | BlendMode::OnionSkin => base,
+    }
+}
+
+/// Blend a single 8-bit channel with one of the separable blend modes
+pub fn blend_channel(base: u8, blend: u8, blend_mode: BlendMode) -> u8 {
+    let result = blend_unit(base as f32 / 255.0, blend as f32 / 255.0, blend_mode);
+    (result * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_channel_reference_values() {
+        // (base, blend, mode, expected) computed from the W3C formulas
+        let cases = [
+            (128, 128, BlendMode::Multiply, 64),
+            (255, 77, BlendMode::Multiply, 77),
+            (128, 128, BlendMode::Screen, 192),
+            (0, 77, BlendMode::Screen, 77),
+            (64, 128, BlendMode::Overlay, 64),
+            (192, 128, BlendMode::Overlay, 192),
+            (200, 50, BlendMode::Overlay, 167),
+            (128, 64, BlendMode::HardLight, 64),
+            (50, 200, BlendMode::HardLight, 167),
+            (128, 255, BlendMode::SoftLight, 181),
+            (128, 0, BlendMode::SoftLight, 64),
+            (40, 200, BlendMode::SoftLight, 74),
+            (200, 50, BlendMode::Difference, 150),
+            (50, 200, BlendMode::Difference, 150),
+            (100, 100, BlendMode::Exclusion, 122),
+            (255, 100, BlendMode::Exclusion, 155),
+        ];
+
+        for (base, blend, mode, expected) in cases {
+            assert_eq!(
+                blend_channel(base, blend, mode),
+                expected,
+                "{:?}({}, {})",
+                mode,
+                base,
+                blend
+            );
+        }
+    }
+
+    #[test]
+    fn test_onion_skin_neutral_where_equal() {
+        let onion_skin = OnionSkin::default();
+
+        assert_eq!(
+            onion_skin.blend(Rgb([90, 90, 90]), Rgb([90, 90, 90])),
+            Rgb([90, 90, 90])
+        );
+        // only in before -> red, only in after -> cyan
+        assert_eq!(
+            onion_skin.blend(Rgb([200, 200, 200]), Rgb([0, 0, 0])),
+            Rgb([200, 0, 0])
+        );
+        assert_eq!(
+            onion_skin.blend(Rgb([0, 0, 0]), Rgb([200, 200, 200])),
+            Rgb([0, 200, 200])
+        );
+    }
+
+    #[test]
+    fn test_onion_skin_linear_neutral_where_equal() {
+        let onion_skin = OnionSkin::default();
+        let grey = [0.2, 0.2, 0.2];
+
+        let blended = onion_skin.blend_linear(grey, grey);
+        let only_before = onion_skin.blend_linear([1.0, 1.0, 1.0], [0.0, 0.0, 0.0]);
+
+        assert!(blended.iter().all(|c| (c - 0.2).abs() < 1e-9));
+        assert_eq!(only_before, [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_onion_skin_opacity() {
+        let onion_skin = OnionSkin {
+            before_opacity: 0.5,
+            after_opacity: 0.0,
+            before_tint: Rgb([255, 255, 255]),
+            after_tint: Rgb([255, 255, 255]),
+        };
+
+        assert_eq!(
+            onion_skin.blend(Rgb([200, 200, 200]), Rgb([100, 100, 100])),
+            Rgb([100, 100, 100])
+        );
+    }
+
+    #[test]
+    fn test_blend_channel_identities() {
+        for v in [0, 17, 128, 255] {
+            assert_eq!(blend_channel(v, 255, BlendMode::Multiply), v);
+            assert_eq!(blend_channel(v, 0, BlendMode::Screen), v);
+            assert_eq!(blend_channel(v, v, BlendMode::Difference), 0);
+            assert_eq!(blend_channel(v, 0, BlendMode::Exclusion), v);
+            assert_eq!(blend_channel(v, 128, BlendMode::SoftLight), v);
+        }
+    }
+}
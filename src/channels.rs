@@ -0,0 +1,153 @@
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Pixel, Rgba};
+
+// Rec. 601 luma, as used for SSIM and the perceptual hashes
+const LUMA_WEIGHTS: [f32; 4] = [0.299, 0.587, 0.114, 0.0];
+
+/// Which part of each pixel the images are compared on
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Channels {
+    /// Red, green and blue separately
+    Rgb,
+    /// Brightness only, so colour theme changes do not count
+    Luma,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    /// Red, green, blue and alpha, the difference of each channel counts
+    /// with its weight. The weights are scaled to add up to 1.
+    Weighted([f32; 4]),
+}
+
+impl Channels {
+    /// Weights of red, green, blue and alpha the selected value is a sum
+    /// of, None for `Rgb` and `Weighted`
+    fn weights(&self) -> Option<[f32; 4]> {
+        match *self {
+            Channels::Rgb | Channels::Weighted(_) => None,
+            Channels::Luma => Some(LUMA_WEIGHTS),
+            Channels::Red => Some([1.0, 0.0, 0.0, 0.0]),
+            Channels::Green => Some([0.0, 1.0, 0.0, 0.0]),
+            Channels::Blue => Some([0.0, 0.0, 1.0, 0.0]),
+            Channels::Alpha => Some([0.0, 0.0, 0.0, 1.0]),
+        }
+    }
+
+    /// Weight of the difference of each value `channel_values` returns for
+    /// a pixel, in the same order
+    pub fn value_weights(&self) -> Vec<f64> {
+        match *self {
+            Channels::Rgb => vec![1.0; 3],
+            Channels::Weighted(weights) => {
+                let total: f32 = weights.iter().sum();
+                if total > 0.0 {
+                    weights.iter().map(|&w| (w / total) as f64).collect()
+                } else {
+                    vec![0.25; 4]
+                }
+            }
+            _ => vec![1.0],
+        }
+    }
+}
+
+fn weighted_value(pixel: Rgba<u8>, weights: &[f32; 4]) -> u8 {
+    let value: f32 = (0..4).map(|c| pixel[c] as f32 * weights[c]).sum();
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+/// The channel values that are compared, three per pixel for `Rgb`, four
+/// for `Weighted` and one per pixel otherwise. Their differences count with
+/// `Channels::value_weights`.
+pub fn channel_values(image: &DynamicImage, channels: Channels) -> Vec<u8> {
+    match channels.weights() {
+        None if matches!(channels, Channels::Weighted(_)) => {
+            image.pixels().flat_map(|(_, _, pixel)| pixel.0).collect()
+        }
+        None => image
+            .pixels()
+            .flat_map(|(_, _, pixel)| pixel.to_rgb().0)
+            .collect(),
+        Some(weights) => image
+            .pixels()
+            .map(|(_, _, pixel)| weighted_value(pixel, &weights))
+            .collect(),
+    }
+}
+
+/// Reduce the image to the selected channels: `Rgb` keeps the image as it
+/// is, `Weighted` clears the channels weighted 0 (alpha becomes opaque) and
+/// anything else gives a greyscale image of the selected value, so every
+/// diff mode can work on it
+pub fn select_channels(image: &DynamicImage, channels: Channels) -> DynamicImage {
+    match channels {
+        Channels::Rgb => return image.clone(),
+        Channels::Weighted(weights) => {
+            let mut rgba = image.to_rgba8();
+            for pixel in rgba.pixels_mut() {
+                for c in (0..4).filter(|&c| weights[c] <= 0.0) {
+                    pixel[c] = if c == 3 { 255 } else { 0 };
+                }
+            }
+            return DynamicImage::ImageRgba8(rgba);
+        }
+        _ => (),
+    }
+    match channels.weights() {
+        None => image.clone(),
+        Some(weights) => {
+            let rgba = image.to_rgba8();
+            DynamicImage::ImageLuma8(GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+                Luma([weighted_value(*rgba.get_pixel(x, y), &weights)])
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageBuffer;
+
+    fn pixel_image(pixel: Rgba<u8>) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_pixel(2, 1, pixel))
+    }
+
+    #[test]
+    fn test_channel_values() {
+        let image = pixel_image(Rgba([10, 20, 30, 40]));
+
+        assert_eq!(
+            channel_values(&image, Channels::Rgb),
+            vec![10, 20, 30, 10, 20, 30]
+        );
+        assert_eq!(channel_values(&image, Channels::Red), vec![10, 10]);
+        assert_eq!(channel_values(&image, Channels::Green), vec![20, 20]);
+        assert_eq!(channel_values(&image, Channels::Blue), vec![30, 30]);
+        assert_eq!(channel_values(&image, Channels::Alpha), vec![40, 40]);
+        assert_eq!(channel_values(&image, Channels::Luma), vec![18, 18]);
+        assert_eq!(
+            channel_values(&image, Channels::Weighted([1.0, 0.0, 0.0, 1.0])),
+            vec![10, 20, 30, 40, 10, 20, 30, 40]
+        );
+        assert_eq!(
+            Channels::Weighted([1.0, 0.0, 0.0, 1.0]).value_weights(),
+            vec![0.5, 0.0, 0.0, 0.5]
+        );
+    }
+
+    #[test]
+    fn test_select_channels() {
+        let image = pixel_image(Rgba([10, 20, 30, 40]));
+
+        let alpha = select_channels(&image, Channels::Alpha);
+
+        assert_eq!(alpha.color(), image::ColorType::L8);
+        assert_eq!(alpha.get_pixel(1, 0), Rgba([40, 40, 40, 255]));
+        assert_eq!(select_channels(&image, Channels::Rgb), image);
+        assert_eq!(
+            select_channels(&image, Channels::Weighted([1.0, 0.0, 2.0, 0.0])).get_pixel(0, 0),
+            Rgba([10, 0, 30, 255])
+        );
+    }
+}
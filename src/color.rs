@@ -0,0 +1,407 @@
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel, Rgb, RgbImage, Rgba, RgbaImage};
+
+// Linear RGB to CIE XYZ (D65) for the primaries of each profile
+const SRGB_TO_XYZ: [[f64; 3]; 3] = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.1191920, 0.9503041],
+];
+const DISPLAY_P3_TO_XYZ: [[f64; 3]; 3] = [
+    [0.4865709, 0.2656677, 0.1982173],
+    [0.2289746, 0.6917385, 0.0792869],
+    [0.0000000, 0.0451134, 1.0439444],
+];
+const ADOBE_RGB_TO_XYZ: [[f64; 3]; 3] = [
+    [0.5767309, 0.1855540, 0.1881852],
+    [0.2973769, 0.6273491, 0.0752741],
+    [0.0270343, 0.0706872, 0.9911085],
+];
+const XYZ_TO_SRGB: [[f64; 3]; 3] = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+// Reference white for Lab, D65 like all the profiles above
+const WHITE: [f64; 3] = [0.95047, 1.0, 1.08883];
+// Adobe RGB (1998) encodes with a plain power curve
+const ADOBE_RGB_GAMMA: f64 = 563.0 / 256.0;
+
+/// Components of every pixel in a `ColorSpace`, see `ColorSpace::components`
+pub type ComponentImage = ImageBuffer<Rgb<f64>, Vec<f64>>;
+
+/// RGB colour spaces recognised from an embedded ICC profile
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorProfile {
+    Srgb,
+    DisplayP3,
+    AdobeRgb,
+}
+
+/// Space the pixel values are converted to before they are compared or blended
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// Gamma encoded sRGB bytes, as stored in the file
+    Srgb,
+    /// Linear light sRGB, where averages and blends are physically meaningful
+    LinearRgb,
+    /// CIE L*a*b*, roughly perceptually uniform
+    Lab,
+    /// Hue, saturation and value
+    Hsv,
+}
+
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn multiply(matrix: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn to_byte(c: f64) -> u8 {
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+impl ColorProfile {
+    fn to_xyz(self, rgb: Rgb<u8>) -> [f64; 3] {
+        let channels = rgb.0.map(|c| c as f64 / 255.0);
+        match self {
+            ColorProfile::Srgb => multiply(&SRGB_TO_XYZ, channels.map(srgb_to_linear)),
+            ColorProfile::DisplayP3 => multiply(&DISPLAY_P3_TO_XYZ, channels.map(srgb_to_linear)),
+            ColorProfile::AdobeRgb => {
+                multiply(&ADOBE_RGB_TO_XYZ, channels.map(|c| c.powf(ADOBE_RGB_GAMMA)))
+            }
+        }
+    }
+}
+
+// Text of the profile description tag, either a v2 'desc' or a v4 'mluc'
+fn icc_description(icc: &[u8]) -> Option<String> {
+    let read_u32 = |offset: usize| -> Option<usize> {
+        let bytes = icc.get(offset..offset + 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().ok()?) as usize)
+    };
+
+    let tag_count = read_u32(128)?;
+    for i in 0..tag_count {
+        let entry = 132 + i * 12;
+        if icc.get(entry..entry + 4)? != b"desc" {
+            continue;
+        }
+        let offset = read_u32(entry + 4)?;
+        return match icc.get(offset..offset + 4)? {
+            b"desc" => {
+                let length = read_u32(offset + 8)?;
+                let text = icc.get(offset + 12..offset + 12 + length)?;
+                Some(
+                    String::from_utf8_lossy(text)
+                        .trim_end_matches('\0')
+                        .to_string(),
+                )
+            }
+            b"mluc" => {
+                let length = read_u32(offset + 20)?;
+                let start = offset + read_u32(offset + 24)?;
+                let units: Vec<u16> = icc
+                    .get(start..start + length)?
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect();
+                Some(String::from_utf16_lossy(&units))
+            }
+            _ => None,
+        };
+    }
+    None
+}
+
+/// Recognise the colour space of an ICC profile from its description, None
+/// if it is not one of the known RGB spaces
+pub fn detect_color_profile(icc: &[u8]) -> Option<ColorProfile> {
+    if icc.get(16..20)? != b"RGB " {
+        return None;
+    }
+    let description = icc_description(icc)?.to_lowercase();
+    if description.contains("srgb") {
+        Some(ColorProfile::Srgb)
+    } else if description.contains("p3") {
+        Some(ColorProfile::DisplayP3)
+    } else if description.contains("adobe rgb") || description.contains("adobergb") {
+        Some(ColorProfile::AdobeRgb)
+    } else {
+        None
+    }
+}
+
+/// Convert an image in `profile` to sRGB, colours outside of the sRGB gamut
+/// are clipped
+pub fn convert_to_srgb(image: &DynamicImage, profile: ColorProfile) -> DynamicImage {
+    if profile == ColorProfile::Srgb {
+        return image.clone();
+    }
+    let rgba = image.to_rgba8();
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let pixel = rgba.get_pixel(x, y);
+        let linear = multiply(&XYZ_TO_SRGB, profile.to_xyz(pixel.to_rgb()));
+        let rgb = linear.map(|c| to_byte(linear_to_srgb(c.clamp(0.0, 1.0))));
+        Rgba([rgb[0], rgb[1], rgb[2], pixel[3]])
+    }))
+}
+
+fn lab_f(t: f64) -> f64 {
+    const DELTA: f64 = 6.0 / 29.0;
+    if t > DELTA.powi(3) {
+        t.cbrt()
+    } else {
+        t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+    }
+}
+
+fn lab_f_inverse(t: f64) -> f64 {
+    const DELTA: f64 = 6.0 / 29.0;
+    if t > DELTA {
+        t.powi(3)
+    } else {
+        3.0 * DELTA * DELTA * (t - 4.0 / 29.0)
+    }
+}
+
+impl ColorSpace {
+    /// Components of an sRGB pixel in this space: 0 to 1 for the RGB spaces,
+    /// L from 0 to 100 and a, b around 0 for Lab, hue in degrees and
+    /// saturation and value from 0 to 1 for HSV
+    pub fn components(self, rgb: Rgb<u8>) -> [f64; 3] {
+        let srgb = rgb.0.map(|c| c as f64 / 255.0);
+        match self {
+            ColorSpace::Srgb => srgb,
+            ColorSpace::LinearRgb => srgb.map(srgb_to_linear),
+            ColorSpace::Lab => {
+                let xyz = multiply(&SRGB_TO_XYZ, srgb.map(srgb_to_linear));
+                let [fx, fy, fz] = [0, 1, 2].map(|i| lab_f(xyz[i] / WHITE[i]));
+                [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+            }
+            ColorSpace::Hsv => {
+                let [r, g, b] = srgb;
+                let max = r.max(g).max(b);
+                let delta = max - r.min(g).min(b);
+                let hue = if delta == 0.0 {
+                    0.0
+                } else if max == r {
+                    60.0 * ((g - b) / delta).rem_euclid(6.0)
+                } else if max == g {
+                    60.0 * ((b - r) / delta + 2.0)
+                } else {
+                    60.0 * ((r - g) / delta + 4.0)
+                };
+                let saturation = if max == 0.0 { 0.0 } else { delta / max };
+                [hue, saturation, max]
+            }
+        }
+    }
+
+    /// The sRGB pixel for components in this space
+    pub fn to_rgb(self, components: [f64; 3]) -> Rgb<u8> {
+        let srgb = match self {
+            ColorSpace::Srgb => components,
+            ColorSpace::LinearRgb => components.map(|c| linear_to_srgb(c.clamp(0.0, 1.0))),
+            ColorSpace::Lab => {
+                let [l, a, b] = components;
+                let fy = (l + 16.0) / 116.0;
+                let f = [fy + a / 500.0, fy, fy - b / 200.0];
+                let xyz = [0, 1, 2].map(|i| WHITE[i] * lab_f_inverse(f[i]));
+                multiply(&XYZ_TO_SRGB, xyz).map(|c| linear_to_srgb(c.clamp(0.0, 1.0)))
+            }
+            ColorSpace::Hsv => {
+                let [hue, saturation, value] = components;
+                let chroma = value * saturation;
+                let h = hue.rem_euclid(360.0) / 60.0;
+                let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+                let (r, g, b) = match h as u32 {
+                    0 => (chroma, x, 0.0),
+                    1 => (x, chroma, 0.0),
+                    2 => (0.0, chroma, x),
+                    3 => (0.0, x, chroma),
+                    4 => (x, 0.0, chroma),
+                    _ => (chroma, 0.0, x),
+                };
+                let m = value - chroma;
+                [r + m, g + m, b + m]
+            }
+        };
+        Rgb(srgb.map(to_byte))
+    }
+
+    /// Difference of two sRGB pixels in this space, from 0 to 1. Every
+    /// component is scaled by its range and hue wraps around.
+    pub fn difference(self, a: Rgb<u8>, b: Rgb<u8>) -> f64 {
+        let (a, b) = (self.components(a), self.components(b));
+        let scaled = match self {
+            ColorSpace::Srgb | ColorSpace::LinearRgb => [0, 1, 2].map(|i| (a[i] - b[i]).abs()),
+            ColorSpace::Lab => [
+                (a[0] - b[0]).abs() / 100.0,
+                ((a[1] - b[1]).abs() / 255.0).min(1.0),
+                ((a[2] - b[2]).abs() / 255.0).min(1.0),
+            ],
+            ColorSpace::Hsv => {
+                let hue = (a[0] - b[0]).abs();
+                [
+                    hue.min(360.0 - hue) / 180.0,
+                    (a[1] - b[1]).abs(),
+                    (a[2] - b[2]).abs(),
+                ]
+            }
+        };
+        scaled.iter().sum::<f64>() / 3.0
+    }
+}
+
+/// Components of every pixel of an sRGB image in `space`
+pub fn to_color_space(image: &DynamicImage, space: ColorSpace) -> ComponentImage {
+    let (width, height) = image.dimensions();
+    let mut output = ComponentImage::new(width, height);
+    for (x, y, pixel) in image.pixels() {
+        output.put_pixel(x, y, Rgb(space.components(pixel.to_rgb())));
+    }
+    output
+}
+
+/// Turn the components of `to_color_space` back into an sRGB image
+pub fn from_color_space(image: &ComponentImage, space: ColorSpace) -> RgbImage {
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        space.to_rgb(image.get_pixel(x, y).0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: [[u8; 3]; 6] = [
+        [0, 0, 0],
+        [255, 255, 255],
+        [255, 0, 0],
+        [12, 200, 90],
+        [128, 64, 250],
+        [90, 90, 91],
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        for space in [
+            ColorSpace::Srgb,
+            ColorSpace::LinearRgb,
+            ColorSpace::Lab,
+            ColorSpace::Hsv,
+        ] {
+            for sample in SAMPLES {
+                let back = space.to_rgb(space.components(Rgb(sample)));
+                for c in 0..3 {
+                    assert!(back[c].abs_diff(sample[c]) <= 1, "{:?} {:?}", space, sample);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lab_reference_values() {
+        let white = ColorSpace::Lab.components(Rgb([255, 255, 255]));
+        let red = ColorSpace::Lab.components(Rgb([255, 0, 0]));
+
+        assert!((white[0] - 100.0).abs() < 0.01);
+        assert!(white[1].abs() < 0.01 && white[2].abs() < 0.01);
+        assert!((red[0] - 53.24).abs() < 0.05);
+        assert!((red[1] - 80.09).abs() < 0.05);
+        assert!((red[2] - 67.20).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_hsv_difference_wraps_hue() {
+        // hue 350 and 10 degrees
+        let a = ColorSpace::Hsv.to_rgb([350.0, 1.0, 1.0]);
+        let b = ColorSpace::Hsv.to_rgb([10.0, 1.0, 1.0]);
+        let green = Rgb([0, 255, 0]);
+
+        assert!(ColorSpace::Hsv.difference(a, b) < 0.05);
+        assert!(ColorSpace::Hsv.difference(a, green) > 0.2);
+        assert_eq!(
+            ColorSpace::Srgb.difference(Rgb([0, 0, 0]), Rgb([255, 255, 255])),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_encoded_image_round_trip() {
+        let image =
+            DynamicImage::ImageRgb8(RgbImage::from_fn(6, 1, |x, _| Rgb(SAMPLES[x as usize])));
+
+        for space in [ColorSpace::LinearRgb, ColorSpace::Lab, ColorSpace::Hsv] {
+            let back = from_color_space(&to_color_space(&image, space), space);
+            for (a, b) in back.pixels().zip(image.to_rgb8().pixels()) {
+                for c in 0..3 {
+                    assert!(a[c].abs_diff(b[c]) <= 1, "{:?} {:?} {:?}", space, a, b);
+                }
+            }
+        }
+    }
+
+    // Minimal ICC header and tag table with a v2 description
+    fn icc_with_description(description: &str) -> Vec<u8> {
+        let mut icc = vec![0u8; 128];
+        icc[16..20].copy_from_slice(b"RGB ");
+        icc.extend_from_slice(&1u32.to_be_bytes());
+        icc.extend_from_slice(b"desc");
+        icc.extend_from_slice(&144u32.to_be_bytes());
+        icc.extend_from_slice(&(12 + description.len() as u32).to_be_bytes());
+        icc.extend_from_slice(b"desc");
+        icc.extend_from_slice(&[0; 4]);
+        icc.extend_from_slice(&(description.len() as u32).to_be_bytes());
+        icc.extend_from_slice(description.as_bytes());
+        icc
+    }
+
+    #[test]
+    fn test_detect_color_profile() {
+        assert_eq!(
+            detect_color_profile(&icc_with_description("sRGB IEC61966-2.1")),
+            Some(ColorProfile::Srgb)
+        );
+        assert_eq!(
+            detect_color_profile(&icc_with_description("Display P3")),
+            Some(ColorProfile::DisplayP3)
+        );
+        assert_eq!(
+            detect_color_profile(&icc_with_description("Adobe RGB (1998)")),
+            Some(ColorProfile::AdobeRgb)
+        );
+        assert_eq!(detect_color_profile(&icc_with_description("Camera")), None);
+        assert_eq!(detect_color_profile(&[0; 20]), None);
+    }
+
+    #[test]
+    fn test_convert_to_srgb() {
+        let image =
+            DynamicImage::ImageRgb8(RgbImage::from_fn(6, 1, |x, _| Rgb(SAMPLES[x as usize])));
+
+        assert_eq!(convert_to_srgb(&image, ColorProfile::Srgb), image);
+
+        // neutral colours stay neutral, saturated ones get clipped to the sRGB gamut
+        let converted = convert_to_srgb(&image, ColorProfile::DisplayP3).to_rgba8();
+        assert_eq!(converted.get_pixel(1, 0), &Rgba([255, 255, 255, 255]));
+        assert_eq!(converted.get_pixel(2, 0), &Rgba([255, 0, 0, 255]));
+        let grey = convert_to_srgb(&image, ColorProfile::AdobeRgb).to_rgba8();
+        let pixel = grey.get_pixel(5, 0);
+        assert!(pixel[0].abs_diff(pixel[1]) <= 1 && pixel[2].abs_diff(91) <= 2);
+    }
+}
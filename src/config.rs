@@ -1,27 +1,97 @@
-use std::process::exit;
+use std::io::{Cursor, Read};
 
 use clap::ArgMatches;
-use diff_img::BlendMode;
-use image::{DynamicImage, Rgba};
+use diff_img::{
+    convert_to_srgb, decode_animation, detect_color_profile, image_from_raw, is_pdf, is_svg,
+    BlendMode, Channels, ColorSpace, Decoders, DiffOptions, EncodeOptions, Frame, HashAlgorithm,
+    Metric, Normalization, OnionSkin, OutputLayout, PixelFormat, Prefilter, RawLayout, Region,
+    RowDiffAlgorithm, RowTolerance, SvgSize, MAX_MEDIAN_RADIUS,
+};
+use image::codecs::png::{CompressionType, FilterType as PngFilterType};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Pixel, Rgba};
 
-pub const DIFF_MODES: [&str; 3] = ["solid-color", "lcs", "blend"];
-pub const BLEND_MODES: [&str; 3] = ["bias", "hue", "overlay"];
+use crate::utils;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum DiffMode {
-    Blend,
-    MarkWithColor,
-    LCS,
-}
+pub const DIFF_MODES: [&str; 5] = ["solid-color", "lcs", "blend", "hash", "moves"];
+pub const BLEND_MODES: [&str; 11] = [
+    "bias",
+    "hue",
+    "overlay",
+    "average",
+    "difference",
+    "exclusion",
+    "multiply",
+    "screen",
+    "soft-light",
+    "hard-light",
+    "onion-skin",
+];
+pub const HASH_ALGORITHMS: [&str; 3] = ["ahash", "dhash", "phash"];
+pub const METRICS: [&str; 7] = [
+    "diff-ratio",
+    "ssim",
+    "ms-ssim",
+    "mse",
+    "rmse",
+    "psnr",
+    "max-abs-error",
+];
+pub const ROW_DIFF_ALGORITHMS: [&str; 3] = ["lcs", "myers", "patience"];
+pub const CHANNELS: [&str; 6] = ["rgb", "luma", "red", "green", "blue", "alpha"];
+pub const COLOR_SPACES: [&str; 4] = ["srgb", "linear", "lab", "hsv"];
+pub const NORMALIZATIONS: [&str; 3] = ["histogram", "mean-std", "auto-levels"];
+pub const PREFILTERS: [&str; 3] = ["blur", "downsample", "median"];
+pub const RESIZE_FILTERS: [&str; 5] =
+    ["nearest", "triangle", "catmull-rom", "gaussian", "lanczos3"];
+pub const OUTPUT_FORMATS: [&str; 6] = ["png", "jpeg", "gif", "bmp", "tiff", "webp"];
+pub const PNG_COMPRESSIONS: [&str; 3] = ["fast", "default", "best"];
+pub const LAYOUTS: [&str; 2] = ["diff", "side-by-side"];
+pub const RAW_FORMATS: [&str; 5] = ["rgba", "bgra", "rgb", "bgr", "gray"];
+pub const PNG_FILTERS: [&str; 6] = ["none", "sub", "up", "avg", "paeth", "adaptive"];
+
+pub use diff_img::DiffMode;
 
 #[derive(Debug)]
 pub struct Config<'a> {
-    pub image1: DynamicImage,
-    pub image2: DynamicImage,
+    pub image1_path: &'a String,
+    pub image2_path: &'a String,
+    pub frames: bool,
+    pub sequence: bool,
+    pub timeline_output: Option<&'a String>,
+    pub threshold: Option<f64>,
     pub filename: Option<&'a String>,
+    pub ssim_map_output: Option<&'a String>,
+    pub before_output: Option<&'a String>,
+    pub after_output: Option<&'a String>,
+    pub side_by_side_output: Option<&'a String>,
+    pub prefiltered_before_output: Option<&'a String>,
+    pub prefiltered_after_output: Option<&'a String>,
+    pub encode_options: EncodeOptions,
+    pub raw_layout: Option<RawLayout>,
+    pub decoders: Decoders,
     pub mode: Option<DiffMode>,
+    pub metric: Metric,
+    pub align: Option<u32>,
+    pub resize_filter: Option<FilterType>,
+    pub scale_invariant: bool,
+    pub prefilters: Vec<Prefilter>,
+    pub channels: Channels,
+    pub color_space: ColorSpace,
+    pub normalization: Option<Normalization>,
+    pub max_move: u32,
+    pub hash_algorithm: HashAlgorithm,
     pub blend_mode: BlendMode,
+    pub onion_skin: OnionSkin,
     pub color: Rgba<u8>,
+    pub added_color: Rgba<u8>,
+    pub removed_color: Rgba<u8>,
+    pub rate: f32,
+    pub row_tolerance: RowTolerance,
+    pub row_algorithm: RowDiffAlgorithm,
+    pub tolerance: u8,
+    pub masks: Vec<Region>,
+    pub layout: OutputLayout,
 }
 
 impl<'a> Config<'a> {
@@ -31,8 +101,51 @@ impl<'a> Config<'a> {
         let image1_path = matches.get_one::<String>("image1").unwrap();
         let image2_path: &String = matches.get_one::<String>("image2").unwrap();
         let filename: Option<&String> = matches.get_one::<String>("filename");
+        let ssim_map_output: Option<&String> = matches.get_one::<String>("ssim-map");
+        let before_output: Option<&String> = matches.get_one::<String>("before-output");
+        let after_output: Option<&String> = matches.get_one::<String>("after-output");
+        let side_by_side_output: Option<&String> = matches.get_one::<String>("side-by-side");
+        let prefiltered_before_output: Option<&String> =
+            matches.get_one::<String>("prefiltered-before");
+        let prefiltered_after_output: Option<&String> =
+            matches.get_one::<String>("prefiltered-after");
+        let format: Option<ImageFormat> = matches.get_one::<String>("output-format").map(
+            |format| match string_into_output_format(format) {
+                Ok(format) => format,
+                Err(err) => panic!("{}", err),
+            },
+        );
+        let png_compression = match matches.get_one::<String>("png-compression") {
+            Some(compression) => match string_into_png_compression(compression) {
+                Ok(compression) => compression,
+                Err(err) => panic!("{}", err),
+            },
+            None => CompressionType::default(),
+        };
+        let png_filter = match matches.get_one::<String>("png-filter") {
+            Some(filter) => match string_into_png_filter(filter) {
+                Ok(filter) => filter,
+                Err(err) => panic!("{}", err),
+            },
+            None => PngFilterType::default(),
+        };
+        let jpeg_quality_string = matches.get_one::<String>("jpeg-quality").unwrap();
+        let jpeg_quality: u8 = match jpeg_quality_string.parse::<u8>() {
+            Ok(quality) if (1..=100).contains(&quality) => quality,
+            _ => panic!(
+                "Invalid JPEG quality {}, expected 1 to 100",
+                jpeg_quality_string
+            ),
+        };
+        let encode_options = EncodeOptions {
+            format,
+            png_compression,
+            png_filter,
+            jpeg_quality,
+        };
         let mode_string = matches.get_one::<String>("mode");
         let color_string = matches.get_one::<String>("color").unwrap();
+        let row_tolerance_string = matches.get_one::<String>("row-tolerance").unwrap();
 
         let mode: Option<DiffMode> = match mode_string {
             Some(val) => match get_mode_from_string(val) {
@@ -42,6 +155,85 @@ impl<'a> Config<'a> {
             None => None,
         };
 
+        let metric: Metric = match matches.get_one::<String>("metric") {
+            Some(metric) => match string_into_metric(metric) {
+                Ok(metric) => metric,
+                Err(err) => panic!("{}", err),
+            },
+            None => Metric::DiffRatio,
+        };
+
+        let align: Option<u32> =
+            matches
+                .get_one::<String>("align")
+                .map(|max_offset| match max_offset.parse::<u32>() {
+                    Ok(max_offset) => max_offset,
+                    Err(err) => panic!("Invalid max offset {}: {}", max_offset, err),
+                });
+
+        let resize_filter: Option<FilterType> = matches.get_one::<String>("resize").map(|filter| {
+            match string_into_filter_type(filter) {
+                Ok(filter) => filter,
+                Err(err) => panic!("{}", err),
+            }
+        });
+        let scale_invariant = matches.get_flag("scale-invariant");
+        let frames = matches.get_flag("frames");
+        let sequence = matches.get_flag("sequence");
+        let timeline_output: Option<&String> = matches.get_one::<String>("timeline");
+        let threshold: Option<f64> = matches.get_one::<String>("threshold").map(|threshold| {
+            match threshold.parse::<f64>() {
+                Ok(threshold) => threshold,
+                Err(err) => panic!("Invalid threshold {}: {}", threshold, err),
+            }
+        });
+
+        let prefilters: Vec<Prefilter> = match matches.get_one::<String>("prefilter") {
+            Some(filters) => match prefilters_from_string(filters) {
+                Ok(filters) => filters,
+                Err(err) => panic!("{}", err),
+            },
+            None => Vec::new(),
+        };
+
+        let channels: Channels = match matches.get_one::<String>("channels") {
+            Some(channels) => match channels_from_string(channels) {
+                Ok(channels) => channels,
+                Err(err) => panic!("{}", err),
+            },
+            None => Channels::Rgb,
+        };
+
+        let color_space: ColorSpace = match matches.get_one::<String>("color-space") {
+            Some(space) => match string_into_color_space(space) {
+                Ok(space) => space,
+                Err(err) => panic!("{}", err),
+            },
+            None => ColorSpace::Srgb,
+        };
+
+        let normalization: Option<Normalization> =
+            matches.get_one::<String>("normalize").map(|normalization| {
+                match string_into_normalization(normalization) {
+                    Ok(normalization) => normalization,
+                    Err(err) => panic!("{}", err),
+                }
+            });
+
+        let max_move_string = matches.get_one::<String>("max-move").unwrap();
+        let max_move: u32 = match max_move_string.parse::<u32>() {
+            Ok(max_move) => max_move,
+            Err(err) => panic!("Invalid max move {}: {}", max_move_string, err),
+        };
+
+        let hash_algorithm: HashAlgorithm = match matches.get_one::<String>("hash") {
+            Some(algorithm) => match string_into_hash_algorithm(algorithm) {
+                Ok(algorithm) => algorithm,
+                Err(err) => panic!("{}", err),
+            },
+            None => HashAlgorithm::Perceptual,
+        };
+
         let blend_mode: BlendMode = match matches.get_one::<String>("blend") {
             Some(bias) => match string_into_blend_mode(bias) {
                 Ok(mode) => mode,
@@ -50,39 +242,425 @@ impl<'a> Config<'a> {
             None => BlendMode::Overlay,
         };
 
-        let image1 = match safe_load_image(image1_path) {
-            Ok(img) => img,
-            Err(msg) => {
-                println!("Error: {}", msg);
-                exit(1);
+        if utils::is_stdio(image1_path) && utils::is_stdio(image2_path) {
+            panic!("Only one of the images can be read from stdin");
+        }
+        let raw_layout: Option<RawLayout> = matches.get_one::<String>("raw-size").map(|size| {
+            let (width, height) = match size_from_string(size) {
+                Ok(size) => size,
+                Err(err) => panic!("{}", err),
+            };
+            let format =
+                match string_into_pixel_format(matches.get_one::<String>("raw-format").unwrap()) {
+                    Ok(format) => format,
+                    Err(err) => panic!("{}", err),
+                };
+            let stride = matches.get_one::<String>("raw-stride").map(|stride| {
+                match stride.parse::<usize>() {
+                    Ok(stride) => stride,
+                    Err(err) => panic!("Invalid stride {}: {}", stride, err),
+                }
+            });
+            RawLayout {
+                width,
+                height,
+                format,
+                stride,
             }
-        };
+        });
 
-        let image2 = match safe_load_image(image2_path) {
-            Ok(img) => img,
-            Err(msg) => {
-                println!("Error: {}", msg);
-                exit(1);
+        let svg_size = match matches.get_one::<String>("svg-size") {
+            Some(size) => match size_from_string(size) {
+                Ok((width, height)) => SvgSize::Pixels(width, height),
+                Err(err) => panic!("{}", err),
+            },
+            None => {
+                let scale_string = matches.get_one::<String>("svg-scale").unwrap();
+                match scale_string.parse::<f32>() {
+                    Ok(scale) if scale > 0.0 => SvgSize::Scale(scale),
+                    _ => panic!("Invalid SVG scale {}", scale_string),
+                }
             }
         };
 
+        let dpi_string = matches.get_one::<String>("pdf-dpi").unwrap();
+        let pdf_dpi = match dpi_string.parse::<f32>() {
+            Ok(dpi) if dpi > 0.0 => dpi,
+            _ => panic!("Invalid PDF resolution {}", dpi_string),
+        };
+
+        let decoders = input_decoders(svg_size, pdf_dpi);
+
+        let row_algorithm = match matches.get_one::<String>("row-algorithm") {
+            Some(algorithm) => match string_into_row_diff_algorithm(algorithm) {
+                Ok(algorithm) => algorithm,
+                Err(err) => panic!("{}", err),
+            },
+            None => RowDiffAlgorithm::Lcs,
+        };
+
         let color = rgba_from_string(color_string.as_str()).unwrap();
 
-        Config {
-            image1,
-            image2,
+        let added_color_string = matches.get_one::<String>("added-color").unwrap();
+        let added_color = match rgba_from_string(added_color_string) {
+            Ok(color) => color,
+            Err(err) => panic!("{}", err),
+        };
+
+        let removed_color_string = matches.get_one::<String>("removed-color").unwrap();
+        let removed_color = match rgba_from_string(removed_color_string) {
+            Ok(color) => color,
+            Err(err) => panic!("{}", err),
+        };
+
+        let onion_skin = OnionSkin {
+            before_opacity: match rate_from_string(
+                matches.get_one::<String>("before-opacity").unwrap(),
+            ) {
+                Ok(opacity) => opacity,
+                Err(err) => panic!("{}", err),
+            },
+            after_opacity: match rate_from_string(
+                matches.get_one::<String>("after-opacity").unwrap(),
+            ) {
+                Ok(opacity) => opacity,
+                Err(err) => panic!("{}", err),
+            },
+            before_tint: match rgba_from_string(matches.get_one::<String>("before-tint").unwrap()) {
+                Ok(color) => color.to_rgb(),
+                Err(err) => panic!("{}", err),
+            },
+            after_tint: match rgba_from_string(matches.get_one::<String>("after-tint").unwrap()) {
+                Ok(color) => color.to_rgb(),
+                Err(err) => panic!("{}", err),
+            },
+        };
+
+        let rate = match rate_from_string(matches.get_one::<String>("rate").unwrap()) {
+            Ok(rate) => rate,
+            Err(err) => panic!("{}", err),
+        };
+
+        let tolerance_string = matches.get_one::<String>("tolerance").unwrap();
+        let tolerance: u8 = match tolerance_string.parse::<u8>() {
+            Ok(tolerance) => tolerance,
+            Err(err) => panic!("Invalid tolerance {}: {}", tolerance_string, err),
+        };
+
+        let masks: Vec<Region> = matches
+            .get_many::<String>("mask")
+            .unwrap_or_default()
+            .map(|mask| match mask_from_string(mask) {
+                Ok(mask) => mask,
+                Err(err) => panic!("{}", err),
+            })
+            .collect();
+
+        let layout: OutputLayout = match matches.get_one::<String>("layout") {
+            Some(layout) => match string_into_layout(layout) {
+                Ok(layout) => layout,
+                Err(err) => panic!("{}", err),
+            },
+            None => OutputLayout::Diff,
+        };
+
+        let row_tolerance = match row_tolerance_from_string(row_tolerance_string) {
+            Ok(tolerance) => tolerance,
+            Err(err) => panic!("{}", err),
+        };
+
+        let config = Config {
+            image1_path,
+            image2_path,
+            frames,
+            sequence,
+            timeline_output,
+            threshold,
             filename,
+            ssim_map_output,
+            before_output,
+            after_output,
+            side_by_side_output,
+            prefiltered_before_output,
+            prefiltered_after_output,
+            encode_options,
+            raw_layout,
+            decoders,
             blend_mode,
+            onion_skin,
             mode,
+            metric,
+            align,
+            resize_filter,
+            scale_invariant,
+            prefilters,
+            channels,
+            color_space,
+            normalization,
+            max_move,
+            hash_algorithm,
             color,
+            added_color,
+            removed_color,
+            rate,
+            row_tolerance,
+            row_algorithm,
+            tolerance,
+            masks,
+            layout,
+        };
+        if let Err(msg) = config.check_frame_options() {
+            panic!("{}", msg);
+        }
+        if config.stdout_outputs() > 1 {
+            panic!("Only one output can be written to stdout");
+        }
+        if config.writes_to_stdout() && config.encode_options.format.is_none() {
+            panic!("Writing an image to stdout needs --output-format");
+        }
+        config
+    }
+
+    fn stdout_outputs(&self) -> usize {
+        [
+            self.filename,
+            self.ssim_map_output,
+            self.before_output,
+            self.after_output,
+            self.side_by_side_output,
+            self.prefiltered_before_output,
+            self.prefiltered_after_output,
+        ]
+        .iter()
+        .flatten()
+        .filter(|path| utils::is_stdio(path))
+        .count()
+    }
+
+    // --frames and --sequence draw one diff image per frame, so the modes and
+    // outputs of single images do not work with them
+    fn check_frame_options(&self) -> Result<(), String> {
+        if self.frames && self.sequence {
+            return Err("--frames and --sequence cannot be used together".to_string());
+        }
+        if !self.sequence && (self.timeline_output.is_some() || self.threshold.is_some()) {
+            return Err("--timeline and --threshold need --sequence".to_string());
+        }
+        let flag = match (self.frames, self.sequence) {
+            (true, _) => "--frames",
+            (_, true) => "--sequence",
+            _ => return Ok(()),
+        };
+        let reads_stdin = utils::is_stdio(self.image1_path) || utils::is_stdio(self.image2_path);
+        if self.sequence && reads_stdin {
+            return Err("Sequences cannot be read from stdin".to_string());
+        }
+        // one diff image is saved per frame, and the frame scores go to stdout
+        let outputs = [self.filename, self.timeline_output];
+        if outputs.iter().flatten().any(|path| utils::is_stdio(path)) {
+            return Err(format!("Nothing can be written to stdout with {}", flag));
+        }
+        if let Some(mode) = self.mode {
+            if !matches!(mode, DiffMode::MarkWithColor | DiffMode::Blend) {
+                return Err(format!(
+                    "Only solid-color and blend mode work with {}",
+                    flag
+                ));
+            }
+        }
+        let single_image_outputs = [
+            ("--ssim-map", self.ssim_map_output),
+            ("--before-output", self.before_output),
+            ("--after-output", self.after_output),
+            ("--side-by-side", self.side_by_side_output),
+            ("--prefiltered-before", self.prefiltered_before_output),
+            ("--prefiltered-after", self.prefiltered_after_output),
+        ];
+        match single_image_outputs.iter().find(|(_, path)| path.is_some()) {
+            Some((output, _)) => Err(format!("{} cannot be used with {}", output, flag)),
+            None => Ok(()),
         }
     }
+
+    /// Options of the comparison, shared by the library API
+    pub fn diff_options(&self) -> DiffOptions {
+        DiffOptions {
+            mode: self.mode,
+            metric: self.metric,
+            masks: self.masks.clone(),
+            resize_filter: self.resize_filter,
+            scale_invariant: self.scale_invariant,
+            align: self.align,
+            prefilters: self.prefilters.clone(),
+            channels: self.channels,
+            color_space: self.color_space,
+            normalization: self.normalization,
+            tolerance: self.tolerance,
+            row_tolerance: self.row_tolerance,
+            row_algorithm: self.row_algorithm,
+            color: self.color,
+            added_color: self.added_color,
+            removed_color: self.removed_color,
+            rate: self.rate,
+            blend_mode: self.blend_mode,
+            onion_skin: self.onion_skin,
+            hash_algorithm: self.hash_algorithm,
+            max_move: self.max_move,
+            ssim_map: self.ssim_map_output.is_some(),
+            side_by_side: self.side_by_side_output.is_some(),
+            layout: self.layout,
+        }
+    }
+
+    /// Load an image with the input decoders and the raw layout of the
+    /// command line, converting it to sRGB if it embeds a known ICC profile
+    pub fn load_image(&self, filename: &str) -> Result<DynamicImage, String> {
+        safe_load_image(filename, self.raw_layout, &self.decoders)
+    }
+
+    /// Every page of a document read by an input decoder, or every frame of
+    /// an animation or multi-page image
+    pub fn load_pages(&self, filename: &str) -> Result<Vec<Frame>, String> {
+        let bytes = read_input(filename)?;
+        if let Some(pages) = self.decoders.decode(&bytes, filename) {
+            return Ok(pages?.into_iter().map(Frame::still).collect());
+        }
+        // like in image_from_bytes, raw inputs are not sniffed
+        let animation = if is_raw_input(filename, self.raw_layout) {
+            None
+        } else {
+            decode_animation(&bytes).map_err(|err| {
+                format!("Error loading frames of {}: {}", input_name(filename), err)
+            })?
+        };
+        match animation {
+            Some(frames) => Ok(frames),
+            None => image_from_bytes(&bytes, filename, self.raw_layout)
+                .map(|image| vec![Frame::still(image)]),
+        }
+    }
+
+    /// Whether an image is written to stdout, text output then goes to stderr
+    pub fn writes_to_stdout(&self) -> bool {
+        self.stdout_outputs() > 0
+    }
 }
 
-fn safe_load_image(filename: &str) -> Result<DynamicImage, String> {
-    match image::open(filename) {
-        Ok(img) => Ok(img),
-        Err(msg) => Err(format!("Error loading image {}: {}", filename, msg)),
+// Decoders for the inputs the image crate does not read, as far as the
+// features enable them
+#[cfg_attr(not(all(feature = "svg", feature = "pdf")), allow(unused_variables))]
+fn input_decoders(svg_size: SvgSize, pdf_dpi: f32) -> Decoders {
+    let decoders = Decoders::new();
+    #[cfg(feature = "svg")]
+    let decoders = decoders.with(diff_img::SvgDecoder { size: svg_size });
+    #[cfg(feature = "pdf")]
+    let decoders = decoders.with(diff_img::PdfDecoder { dpi: pdf_dpi });
+    decoders
+}
+
+// Contents of a file, or of stdin for "-"
+fn read_input(filename: &str) -> Result<Vec<u8>, String> {
+    if utils::is_stdio(filename) {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|err| format!("Error reading image from stdin: {}", err))?;
+        Ok(bytes)
+    } else {
+        std::fs::read(filename).map_err(|err| format!("Error loading image {}: {}", filename, err))
+    }
+}
+
+// Name of an input in messages
+fn input_name(filename: &str) -> &str {
+    if utils::is_stdio(filename) {
+        "from stdin"
+    } else {
+        filename
+    }
+}
+
+// Whether an input is read as raw pixels, which is the case for files without
+// a known image extension when a raw layout is given
+fn is_raw_input(filename: &str, raw: Option<RawLayout>) -> bool {
+    raw.is_some() && ImageFormat::from_path(filename).is_err()
+}
+
+// Load an image and convert it to sRGB if it embeds a known ICC profile.
+// "-" reads the image from stdin, its format is detected from the first bytes.
+// Formats with an input decoder use their first page, with a warning if there
+// are more.
+fn safe_load_image(
+    filename: &str,
+    raw: Option<RawLayout>,
+    decoders: &Decoders,
+) -> Result<DynamicImage, String> {
+    let bytes = read_input(filename)?;
+    if let Some(pages) = decoders.decode(&bytes, filename) {
+        let mut pages = pages?;
+        if pages.len() > 1 {
+            eprintln!(
+                "Warning: {} has {} pages, only the first is compared, use --frames to compare every page",
+                input_name(filename),
+                pages.len()
+            );
+        }
+        return Ok(pages.swap_remove(0));
+    }
+    image_from_bytes(&bytes, filename, raw)
+}
+
+// Decode an input no input decoder reads. If a raw layout is given, files
+// without a known image extension are read as raw pixels.
+fn image_from_bytes(
+    bytes: &[u8],
+    filename: &str,
+    raw: Option<RawLayout>,
+) -> Result<DynamicImage, String> {
+    let name = input_name(filename);
+    if is_svg(bytes, filename) {
+        return Err(format!(
+            "Error loading {}: SVG images need diffimg built with the svg feature",
+            name
+        ));
+    }
+    if is_pdf(bytes, filename) {
+        return Err(format!(
+            "Error loading {}: PDF documents need diffimg built with the pdf feature",
+            name
+        ));
+    }
+    if let Some(layout) = raw.filter(|_| is_raw_input(filename, raw)) {
+        // raw dumps may start with bytes that look like a format signature,
+        // such as "BM", so their content is not sniffed
+        return image_from_raw(bytes, layout)
+            .map_err(|err| format!("Error loading raw image {}: {}", name, err));
+    }
+
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|err| format!("Error loading image {}: {}", name, err))?;
+    if reader.format().is_none() {
+        if let Ok(format) = ImageFormat::from_path(filename) {
+            reader.set_format(format);
+        }
+    }
+    decode_image(reader, name)
+}
+
+fn decode_image<R: std::io::BufRead + std::io::Seek>(
+    reader: ImageReader<R>,
+    name: &str,
+) -> Result<DynamicImage, String> {
+    let error = |msg: image::ImageError| format!("Error loading image {}: {}", name, msg);
+
+    let mut decoder = reader.into_decoder().map_err(error)?;
+    let icc = decoder.icc_profile().map_err(error)?;
+    let img = DynamicImage::from_decoder(decoder).map_err(error)?;
+
+    match icc.as_deref().and_then(detect_color_profile) {
+        Some(profile) => Ok(convert_to_srgb(&img, profile)),
+        None => Ok(img),
     }
 }
 
@@ -91,6 +669,8 @@ fn get_mode_from_string(input: &str) -> Result<DiffMode, String> {
         val if val == DIFF_MODES[0] => Ok(DiffMode::MarkWithColor),
         val if val == DIFF_MODES[1] => Ok(DiffMode::LCS),
         val if val == DIFF_MODES[2] => Ok(DiffMode::Blend),
+        val if val == DIFF_MODES[3] => Ok(DiffMode::Hash),
+        val if val == DIFF_MODES[4] => Ok(DiffMode::Moves),
         _ => Err(format!("Nothing matching {}", input)),
     }
 }
@@ -109,20 +689,273 @@ fn rgba_from_string(input: &str) -> Result<Rgba<u8>, String> {
 
     // Convert the parsed parts into an array with four elements
     let arr = [
-        parts.get(0).copied().unwrap_or(0), // First element, or default to 0
-        parts.get(1).copied().unwrap_or(0), // Second element, or default to 0
-        parts.get(2).copied().unwrap_or(0), // Third element, or default to 0
+        parts.first().copied().unwrap_or(0), // First element, or default to 0
+        parts.get(1).copied().unwrap_or(0),  // Second element, or default to 0
+        parts.get(2).copied().unwrap_or(0),  // Third element, or default to 0
         parts.get(3).copied().unwrap_or(0),
     ];
 
     Ok(Rgba::<u8>(arr))
 }
 
+// Parse a blend rate between 0 and 1
+fn rate_from_string(input: &str) -> Result<f32, String> {
+    let rate = input
+        .trim()
+        .parse::<f32>()
+        .map_err(|err| format!("Invalid rate {}: {}", input, err))?;
+
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(format!("Rate {} must be between 0 and 1", input));
+    }
+    Ok(rate)
+}
+
+// Parse a row tolerance, either a pixel count ("3") or a percentage of the row width ("2.5%")
+fn row_tolerance_from_string(input: &str) -> Result<RowTolerance, String> {
+    let input = input.trim();
+
+    if let Some(percent) = input.strip_suffix('%') {
+        let percent = percent
+            .trim()
+            .parse::<f32>()
+            .map_err(|err| format!("Invalid row tolerance {}: {}", input, err))?;
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(format!(
+                "Row tolerance {} must be between 0% and 100%",
+                input
+            ));
+        }
+        return Ok(RowTolerance::Ratio(percent / 100.0));
+    }
+
+    match input.parse::<usize>() {
+        Ok(0) => Ok(RowTolerance::Exact),
+        Ok(count) => Ok(RowTolerance::Pixels(count)),
+        Err(err) => Err(format!("Invalid row tolerance {}: {}", input, err)),
+    }
+}
+
+// Parse a comma separated pipeline of filters such as "median:1,blur:2,downsample:2"
+fn prefilters_from_string(input: &str) -> Result<Vec<Prefilter>, String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|step| !step.is_empty())
+        .map(|step| {
+            let (name, value) = step
+                .split_once(':')
+                .ok_or_else(|| format!("Prefilter {} must look like name:value", step))?;
+            let value = value.trim();
+            match name.trim() {
+                val if val == PREFILTERS[0] => match value.parse::<f32>() {
+                    Ok(radius) if radius >= 0.0 => Ok(Prefilter::GaussianBlur(radius)),
+                    _ => Err(format!("Invalid blur radius {}", value)),
+                },
+                val if val == PREFILTERS[1] => match value.parse::<u32>() {
+                    Ok(factor) if factor >= 1 => Ok(Prefilter::Downsample(factor)),
+                    _ => Err(format!("Invalid downsample factor {}", value)),
+                },
+                val if val == PREFILTERS[2] => match value.parse::<u32>() {
+                    Ok(radius) if radius <= MAX_MEDIAN_RADIUS => Ok(Prefilter::Median(radius)),
+                    Ok(_) => Err(format!(
+                        "Median radius {} must be at most {}",
+                        value, MAX_MEDIAN_RADIUS
+                    )),
+                    Err(err) => Err(format!("Invalid median radius {}: {}", value, err)),
+                },
+                _ => Err(format!("Nothing matching {}", name)),
+            }
+        })
+        .collect()
+}
+
+// Parse a channel selection, either one of CHANNELS or red, green, blue and
+// alpha weights such as "[1,1,0,0]"
+fn channels_from_string(input: &str) -> Result<Channels, String> {
+    match input.trim() {
+        val if val == CHANNELS[0] => Ok(Channels::Rgb),
+        val if val == CHANNELS[1] => Ok(Channels::Luma),
+        val if val == CHANNELS[2] => Ok(Channels::Red),
+        val if val == CHANNELS[3] => Ok(Channels::Green),
+        val if val == CHANNELS[4] => Ok(Channels::Blue),
+        val if val == CHANNELS[5] => Ok(Channels::Alpha),
+        val if val.starts_with('[') => {
+            let weights: Vec<f32> = val
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(',')
+                .map(|s| s.trim().parse::<f32>())
+                .collect::<Result<_, _>>()
+                .map_err(|err| format!("Invalid channel weights {}: {}", input, err))?;
+            if weights.len() != 4 || weights.iter().any(|&w| w < 0.0) {
+                return Err(format!(
+                    "Channel weights {} must be four non-negative numbers",
+                    input
+                ));
+            }
+            if weights.iter().sum::<f32>() <= 0.0 {
+                return Err(format!("Channel weights {} must not all be 0", input));
+            }
+            Ok(Channels::Weighted([
+                weights[0], weights[1], weights[2], weights[3],
+            ]))
+        }
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+fn string_into_color_space(input: &str) -> Result<ColorSpace, String> {
+    match input {
+        val if val == COLOR_SPACES[0] => Ok(ColorSpace::Srgb),
+        val if val == COLOR_SPACES[1] => Ok(ColorSpace::LinearRgb),
+        val if val == COLOR_SPACES[2] => Ok(ColorSpace::Lab),
+        val if val == COLOR_SPACES[3] => Ok(ColorSpace::Hsv),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+fn string_into_normalization(input: &str) -> Result<Normalization, String> {
+    match input {
+        val if val == NORMALIZATIONS[0] => Ok(Normalization::HistogramMatch),
+        val if val == NORMALIZATIONS[1] => Ok(Normalization::MeanStd),
+        val if val == NORMALIZATIONS[2] => Ok(Normalization::AutoLevels),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
 fn string_into_blend_mode(input: &str) -> Result<BlendMode, String> {
     match input {
         val if val == BLEND_MODES[0] => Ok(BlendMode::BIAS),
         val if val == BLEND_MODES[1] => Ok(BlendMode::HUE),
         val if val == BLEND_MODES[2] => Ok(BlendMode::Overlay),
+        val if val == BLEND_MODES[3] => Ok(BlendMode::Average),
+        val if val == BLEND_MODES[4] => Ok(BlendMode::Difference),
+        val if val == BLEND_MODES[5] => Ok(BlendMode::Exclusion),
+        val if val == BLEND_MODES[6] => Ok(BlendMode::Multiply),
+        val if val == BLEND_MODES[7] => Ok(BlendMode::Screen),
+        val if val == BLEND_MODES[8] => Ok(BlendMode::SoftLight),
+        val if val == BLEND_MODES[9] => Ok(BlendMode::HardLight),
+        val if val == BLEND_MODES[10] => Ok(BlendMode::OnionSkin),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+fn string_into_hash_algorithm(input: &str) -> Result<HashAlgorithm, String> {
+    match input {
+        val if val == HASH_ALGORITHMS[0] => Ok(HashAlgorithm::Average),
+        val if val == HASH_ALGORITHMS[1] => Ok(HashAlgorithm::Difference),
+        val if val == HASH_ALGORITHMS[2] => Ok(HashAlgorithm::Perceptual),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+fn string_into_metric(input: &str) -> Result<Metric, String> {
+    match input {
+        val if val == METRICS[0] => Ok(Metric::DiffRatio),
+        val if val == METRICS[1] => Ok(Metric::Ssim),
+        val if val == METRICS[2] => Ok(Metric::MsSsim),
+        val if val == METRICS[3] => Ok(Metric::Mse),
+        val if val == METRICS[4] => Ok(Metric::Rmse),
+        val if val == METRICS[5] => Ok(Metric::Psnr),
+        val if val == METRICS[6] => Ok(Metric::MaxAbsError),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+fn string_into_row_diff_algorithm(input: &str) -> Result<RowDiffAlgorithm, String> {
+    match input {
+        val if val == ROW_DIFF_ALGORITHMS[0] => Ok(RowDiffAlgorithm::Lcs),
+        val if val == ROW_DIFF_ALGORITHMS[1] => Ok(RowDiffAlgorithm::Myers),
+        val if val == ROW_DIFF_ALGORITHMS[2] => Ok(RowDiffAlgorithm::Patience),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+fn string_into_output_format(input: &str) -> Result<ImageFormat, String> {
+    match input {
+        val if val == OUTPUT_FORMATS[0] => Ok(ImageFormat::Png),
+        val if val == OUTPUT_FORMATS[1] => Ok(ImageFormat::Jpeg),
+        val if val == OUTPUT_FORMATS[2] => Ok(ImageFormat::Gif),
+        val if val == OUTPUT_FORMATS[3] => Ok(ImageFormat::Bmp),
+        val if val == OUTPUT_FORMATS[4] => Ok(ImageFormat::Tiff),
+        val if val == OUTPUT_FORMATS[5] => Ok(ImageFormat::WebP),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+fn string_into_png_compression(input: &str) -> Result<CompressionType, String> {
+    match input {
+        val if val == PNG_COMPRESSIONS[0] => Ok(CompressionType::Fast),
+        val if val == PNG_COMPRESSIONS[1] => Ok(CompressionType::Default),
+        val if val == PNG_COMPRESSIONS[2] => Ok(CompressionType::Best),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+fn string_into_png_filter(input: &str) -> Result<PngFilterType, String> {
+    match input {
+        val if val == PNG_FILTERS[0] => Ok(PngFilterType::NoFilter),
+        val if val == PNG_FILTERS[1] => Ok(PngFilterType::Sub),
+        val if val == PNG_FILTERS[2] => Ok(PngFilterType::Up),
+        val if val == PNG_FILTERS[3] => Ok(PngFilterType::Avg),
+        val if val == PNG_FILTERS[4] => Ok(PngFilterType::Paeth),
+        val if val == PNG_FILTERS[5] => Ok(PngFilterType::Adaptive),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+fn string_into_pixel_format(input: &str) -> Result<PixelFormat, String> {
+    match input {
+        val if val == RAW_FORMATS[0] => Ok(PixelFormat::Rgba8),
+        val if val == RAW_FORMATS[1] => Ok(PixelFormat::Bgra8),
+        val if val == RAW_FORMATS[2] => Ok(PixelFormat::Rgb8),
+        val if val == RAW_FORMATS[3] => Ok(PixelFormat::Bgr8),
+        val if val == RAW_FORMATS[4] => Ok(PixelFormat::Gray8),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+// "1920x1080" into width and height
+fn size_from_string(input: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Invalid size {}, expected <width>x<height>", input);
+    let (width, height) = input.split_once('x').ok_or_else(invalid)?;
+    let width = width.trim().parse::<u32>().map_err(|_| invalid())?;
+    let height = height.trim().parse::<u32>().map_err(|_| invalid())?;
+    Ok((width, height))
+}
+
+fn string_into_layout(input: &str) -> Result<OutputLayout, String> {
+    match input {
+        val if val == LAYOUTS[0] => Ok(OutputLayout::Diff),
+        val if val == LAYOUTS[1] => Ok(OutputLayout::SideBySide),
+        _ => Err(format!("Nothing matching {}", input)),
+    }
+}
+
+// "10,20,100x50" into a region at 10,20 that is 100 wide and 50 high
+fn mask_from_string(input: &str) -> Result<Region, String> {
+    let invalid = || format!("Invalid mask {}, expected <x>,<y>,<width>x<height>", input);
+    let parts: Vec<&str> = input.split(',').map(str::trim).collect();
+    let [x, y, size] = parts[..] else {
+        return Err(invalid());
+    };
+    let (width, height) = size_from_string(size).map_err(|_| invalid())?;
+    Ok(Region {
+        x: x.parse().map_err(|_| invalid())?,
+        y: y.parse().map_err(|_| invalid())?,
+        width,
+        height,
+    })
+}
+
+fn string_into_filter_type(input: &str) -> Result<FilterType, String> {
+    match input {
+        val if val == RESIZE_FILTERS[0] => Ok(FilterType::Nearest),
+        val if val == RESIZE_FILTERS[1] => Ok(FilterType::Triangle),
+        val if val == RESIZE_FILTERS[2] => Ok(FilterType::CatmullRom),
+        val if val == RESIZE_FILTERS[3] => Ok(FilterType::Gaussian),
+        val if val == RESIZE_FILTERS[4] => Ok(FilterType::Lanczos3),
         _ => Err(format!("Nothing matching {}", input)),
     }
 }
@@ -135,11 +968,11 @@ mod tests {
     fn test_rgba_from_string() {
         let mut res = rgba_from_string("[0,255,0,0]");
 
-        assert_eq!(res.is_ok() == true, true);
+        assert!(res.is_ok());
 
         res = rgba_from_string("[0,255,0,as]");
 
-        assert_eq!(res.is_err() == true, true);
+        assert!(res.is_err());
     }
 
     #[test]
@@ -151,6 +984,8 @@ mod tests {
         );
         assert_eq!(get_mode_from_string(DIFF_MODES[1]), Ok(DiffMode::LCS));
         assert_eq!(get_mode_from_string(DIFF_MODES[2]), Ok(DiffMode::Blend));
+        assert_eq!(get_mode_from_string(DIFF_MODES[3]), Ok(DiffMode::Hash));
+        assert_eq!(get_mode_from_string(DIFF_MODES[4]), Ok(DiffMode::Moves));
     }
 
     #[test]
@@ -162,6 +997,381 @@ mod tests {
             string_into_blend_mode(BLEND_MODES[2]),
             Ok(BlendMode::Overlay)
         );
+        assert_eq!(
+            string_into_blend_mode(BLEND_MODES[3]),
+            Ok(BlendMode::Average)
+        );
+        assert_eq!(
+            string_into_blend_mode(BLEND_MODES[4]),
+            Ok(BlendMode::Difference)
+        );
+        assert_eq!(
+            string_into_blend_mode(BLEND_MODES[5]),
+            Ok(BlendMode::Exclusion)
+        );
+        assert_eq!(
+            string_into_blend_mode(BLEND_MODES[6]),
+            Ok(BlendMode::Multiply)
+        );
+        assert_eq!(
+            string_into_blend_mode(BLEND_MODES[7]),
+            Ok(BlendMode::Screen)
+        );
+        assert_eq!(
+            string_into_blend_mode(BLEND_MODES[8]),
+            Ok(BlendMode::SoftLight)
+        );
+        assert_eq!(
+            string_into_blend_mode(BLEND_MODES[9]),
+            Ok(BlendMode::HardLight)
+        );
+        assert_eq!(
+            string_into_blend_mode(BLEND_MODES[10]),
+            Ok(BlendMode::OnionSkin)
+        );
+    }
+
+    #[test]
+    fn test_get_hash_algorithm_from_string() {
+        assert_eq!(
+            string_into_hash_algorithm(HASH_ALGORITHMS[0]),
+            Ok(HashAlgorithm::Average)
+        );
+        assert_eq!(
+            string_into_hash_algorithm(HASH_ALGORITHMS[1]),
+            Ok(HashAlgorithm::Difference)
+        );
+        assert_eq!(
+            string_into_hash_algorithm(HASH_ALGORITHMS[2]),
+            Ok(HashAlgorithm::Perceptual)
+        );
+        assert!(string_into_hash_algorithm("whash").is_err());
+    }
+
+    #[test]
+    fn test_get_metric_from_string() {
+        assert_eq!(string_into_metric(METRICS[0]), Ok(Metric::DiffRatio));
+        assert_eq!(string_into_metric(METRICS[1]), Ok(Metric::Ssim));
+        assert_eq!(string_into_metric(METRICS[2]), Ok(Metric::MsSsim));
+        assert_eq!(string_into_metric(METRICS[3]), Ok(Metric::Mse));
+        assert_eq!(string_into_metric(METRICS[4]), Ok(Metric::Rmse));
+        assert_eq!(string_into_metric(METRICS[5]), Ok(Metric::Psnr));
+        assert_eq!(string_into_metric(METRICS[6]), Ok(Metric::MaxAbsError));
+        assert!(string_into_metric("vmaf").is_err());
+    }
+
+    #[test]
+    fn test_get_row_diff_algorithm_from_string() {
+        assert_eq!(
+            string_into_row_diff_algorithm(ROW_DIFF_ALGORITHMS[0]),
+            Ok(RowDiffAlgorithm::Lcs)
+        );
+        assert_eq!(
+            string_into_row_diff_algorithm(ROW_DIFF_ALGORITHMS[1]),
+            Ok(RowDiffAlgorithm::Myers)
+        );
+        assert_eq!(
+            string_into_row_diff_algorithm(ROW_DIFF_ALGORITHMS[2]),
+            Ok(RowDiffAlgorithm::Patience)
+        );
+        assert!(string_into_row_diff_algorithm("histogram").is_err());
+    }
+
+    #[test]
+    fn test_channels_from_string() {
+        assert_eq!(channels_from_string(CHANNELS[0]), Ok(Channels::Rgb));
+        assert_eq!(channels_from_string(CHANNELS[1]), Ok(Channels::Luma));
+        assert_eq!(channels_from_string(CHANNELS[2]), Ok(Channels::Red));
+        assert_eq!(channels_from_string(CHANNELS[3]), Ok(Channels::Green));
+        assert_eq!(channels_from_string(CHANNELS[4]), Ok(Channels::Blue));
+        assert_eq!(channels_from_string(CHANNELS[5]), Ok(Channels::Alpha));
+        assert_eq!(
+            channels_from_string("[0.5, 0.5, 0, 0]"),
+            Ok(Channels::Weighted([0.5, 0.5, 0.0, 0.0]))
+        );
+        assert!(channels_from_string("[1,1,0]").is_err());
+        assert!(channels_from_string("[0,0,0,0]").is_err());
+        assert!(channels_from_string("[-1,1,1,1]").is_err());
+        assert!(channels_from_string("cyan").is_err());
+    }
+
+    #[test]
+    fn test_get_color_space_from_string() {
+        assert_eq!(
+            string_into_color_space(COLOR_SPACES[0]),
+            Ok(ColorSpace::Srgb)
+        );
+        assert_eq!(
+            string_into_color_space(COLOR_SPACES[1]),
+            Ok(ColorSpace::LinearRgb)
+        );
+        assert_eq!(
+            string_into_color_space(COLOR_SPACES[2]),
+            Ok(ColorSpace::Lab)
+        );
+        assert_eq!(
+            string_into_color_space(COLOR_SPACES[3]),
+            Ok(ColorSpace::Hsv)
+        );
+        assert!(string_into_color_space("cmyk").is_err());
+    }
+
+    #[test]
+    fn test_get_normalization_from_string() {
+        assert_eq!(
+            string_into_normalization(NORMALIZATIONS[0]),
+            Ok(Normalization::HistogramMatch)
+        );
+        assert_eq!(
+            string_into_normalization(NORMALIZATIONS[1]),
+            Ok(Normalization::MeanStd)
+        );
+        assert_eq!(
+            string_into_normalization(NORMALIZATIONS[2]),
+            Ok(Normalization::AutoLevels)
+        );
+        assert!(string_into_normalization("gamma").is_err());
+    }
+
+    #[test]
+    fn test_prefilters_from_string() {
+        assert_eq!(
+            prefilters_from_string("median:1, blur:2.5,downsample:2"),
+            Ok(vec![
+                Prefilter::Median(1),
+                Prefilter::GaussianBlur(2.5),
+                Prefilter::Downsample(2),
+            ])
+        );
+        assert_eq!(prefilters_from_string(""), Ok(vec![]));
+        assert!(prefilters_from_string("blur").is_err());
+        assert!(prefilters_from_string("blur:-1").is_err());
+        assert!(prefilters_from_string("downsample:0").is_err());
+        assert!(prefilters_from_string("median:33").is_err());
+        assert!(prefilters_from_string("sharpen:1").is_err());
+    }
+
+    #[test]
+    fn test_get_output_format_from_string() {
+        assert_eq!(
+            string_into_output_format(OUTPUT_FORMATS[0]),
+            Ok(ImageFormat::Png)
+        );
+        assert_eq!(
+            string_into_output_format(OUTPUT_FORMATS[1]),
+            Ok(ImageFormat::Jpeg)
+        );
+        assert_eq!(
+            string_into_output_format(OUTPUT_FORMATS[2]),
+            Ok(ImageFormat::Gif)
+        );
+        assert_eq!(
+            string_into_output_format(OUTPUT_FORMATS[3]),
+            Ok(ImageFormat::Bmp)
+        );
+        assert_eq!(
+            string_into_output_format(OUTPUT_FORMATS[4]),
+            Ok(ImageFormat::Tiff)
+        );
+        assert_eq!(
+            string_into_output_format(OUTPUT_FORMATS[5]),
+            Ok(ImageFormat::WebP)
+        );
+        assert!(string_into_output_format("psd").is_err());
+    }
+
+    #[test]
+    fn test_get_png_compression_from_string() {
+        assert_eq!(
+            string_into_png_compression(PNG_COMPRESSIONS[0]),
+            Ok(CompressionType::Fast)
+        );
+        assert_eq!(
+            string_into_png_compression(PNG_COMPRESSIONS[1]),
+            Ok(CompressionType::Default)
+        );
+        assert_eq!(
+            string_into_png_compression(PNG_COMPRESSIONS[2]),
+            Ok(CompressionType::Best)
+        );
+        assert!(string_into_png_compression("9").is_err());
+    }
+
+    #[test]
+    fn test_get_png_filter_from_string() {
+        assert_eq!(
+            string_into_png_filter(PNG_FILTERS[0]),
+            Ok(PngFilterType::NoFilter)
+        );
+        assert_eq!(
+            string_into_png_filter(PNG_FILTERS[1]),
+            Ok(PngFilterType::Sub)
+        );
+        assert_eq!(
+            string_into_png_filter(PNG_FILTERS[2]),
+            Ok(PngFilterType::Up)
+        );
+        assert_eq!(
+            string_into_png_filter(PNG_FILTERS[3]),
+            Ok(PngFilterType::Avg)
+        );
+        assert_eq!(
+            string_into_png_filter(PNG_FILTERS[4]),
+            Ok(PngFilterType::Paeth)
+        );
+        assert_eq!(
+            string_into_png_filter(PNG_FILTERS[5]),
+            Ok(PngFilterType::Adaptive)
+        );
+        assert!(string_into_png_filter("median").is_err());
+    }
+
+    #[test]
+    fn test_get_pixel_format_from_string() {
+        assert_eq!(
+            string_into_pixel_format(RAW_FORMATS[0]),
+            Ok(PixelFormat::Rgba8)
+        );
+        assert_eq!(
+            string_into_pixel_format(RAW_FORMATS[1]),
+            Ok(PixelFormat::Bgra8)
+        );
+        assert_eq!(
+            string_into_pixel_format(RAW_FORMATS[2]),
+            Ok(PixelFormat::Rgb8)
+        );
+        assert_eq!(
+            string_into_pixel_format(RAW_FORMATS[3]),
+            Ok(PixelFormat::Bgr8)
+        );
+        assert_eq!(
+            string_into_pixel_format(RAW_FORMATS[4]),
+            Ok(PixelFormat::Gray8)
+        );
+        assert!(string_into_pixel_format("yuv").is_err());
+    }
+
+    #[test]
+    fn test_size_from_string() {
+        assert_eq!(size_from_string("1920x1080"), Ok((1920, 1080)));
+        assert!(size_from_string("1920").is_err());
+        assert!(size_from_string("1920x-1").is_err());
+    }
+
+    #[test]
+    fn test_load_raw_and_ppm_files() {
+        let path = |name: &str| {
+            std::env::temp_dir()
+                .join(format!("diff_img_{}_{}", std::process::id(), name))
+                .to_string_lossy()
+                .to_string()
+        };
+        let (raw_path, ppm_path) = (path("dump.raw"), path("image.ppm"));
+        // starts like a BMP header
+        std::fs::write(&raw_path, b"BM\x03\x04\x05\x06").unwrap();
+        std::fs::write(&ppm_path, b"P6\n2 1\n255\nBM\x03\x04\x05\x06").unwrap();
+        let layout = RawLayout {
+            width: 2,
+            height: 1,
+            format: PixelFormat::Rgb8,
+            stride: None,
+        };
+
+        let raw = safe_load_image(&raw_path, Some(layout), &Decoders::new());
+        let ppm = safe_load_image(&ppm_path, Some(layout), &Decoders::new());
+        let unknown = safe_load_image(&raw_path, None, &Decoders::new());
+        std::fs::remove_file(&raw_path).unwrap();
+        std::fs::remove_file(&ppm_path).unwrap();
+
+        assert_eq!(raw.unwrap().to_rgb8(), ppm.unwrap().to_rgb8());
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn test_get_layout_from_string() {
+        assert_eq!(string_into_layout(LAYOUTS[0]), Ok(OutputLayout::Diff));
+        assert_eq!(string_into_layout(LAYOUTS[1]), Ok(OutputLayout::SideBySide));
+        assert!(string_into_layout("grid").is_err());
+    }
+
+    #[test]
+    fn test_mask_from_string() {
+        assert_eq!(
+            mask_from_string("10, 20, 100x50"),
+            Ok(Region {
+                x: 10,
+                y: 20,
+                width: 100,
+                height: 50
+            })
+        );
+        assert!(mask_from_string("10,20").is_err());
+        assert!(mask_from_string("10,20,100").is_err());
+    }
+
+    #[test]
+    fn test_decode_image_detects_format() {
+        let image =
+            DynamicImage::ImageRgb8(image::RgbImage::from_pixel(3, 2, image::Rgb([1, 2, 3])));
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageFormat::Bmp).unwrap();
+
+        let reader = ImageReader::new(Cursor::new(bytes.into_inner()))
+            .with_guessed_format()
+            .unwrap();
+
+        assert_eq!(reader.format(), Some(ImageFormat::Bmp));
+        assert_eq!(
+            decode_image(reader, "-").unwrap().to_rgb8(),
+            image.to_rgb8()
+        );
+        assert!(decode_image(ImageReader::new(Cursor::new(vec![0u8; 8])), "-").is_err());
+    }
+
+    #[test]
+    fn test_get_filter_type_from_string() {
+        assert_eq!(
+            string_into_filter_type(RESIZE_FILTERS[0]),
+            Ok(FilterType::Nearest)
+        );
+        assert_eq!(
+            string_into_filter_type(RESIZE_FILTERS[1]),
+            Ok(FilterType::Triangle)
+        );
+        assert_eq!(
+            string_into_filter_type(RESIZE_FILTERS[2]),
+            Ok(FilterType::CatmullRom)
+        );
+        assert_eq!(
+            string_into_filter_type(RESIZE_FILTERS[3]),
+            Ok(FilterType::Gaussian)
+        );
+        assert_eq!(
+            string_into_filter_type(RESIZE_FILTERS[4]),
+            Ok(FilterType::Lanczos3)
+        );
+        assert!(string_into_filter_type("bicubic").is_err());
+    }
+
+    #[test]
+    fn test_rate_from_string() {
+        assert_eq!(rate_from_string("0.5"), Ok(0.5));
+        assert_eq!(rate_from_string("1"), Ok(1.0));
+        assert!(rate_from_string("1.5").is_err());
+        assert!(rate_from_string("half").is_err());
+    }
+
+    #[test]
+    fn test_row_tolerance_from_string() {
+        assert_eq!(row_tolerance_from_string("0"), Ok(RowTolerance::Exact));
+        assert_eq!(row_tolerance_from_string("3"), Ok(RowTolerance::Pixels(3)));
+        assert_eq!(
+            row_tolerance_from_string("50%"),
+            Ok(RowTolerance::Ratio(0.5))
+        );
+        assert!(row_tolerance_from_string("150%").is_err());
+        assert!(row_tolerance_from_string("-1").is_err());
+        assert!(row_tolerance_from_string("abc").is_err());
     }
 
     #[test]
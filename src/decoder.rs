@@ -0,0 +1,106 @@
+use std::fmt;
+
+use image::DynamicImage;
+
+/// Decoder for an input format the `image` crate does not read, such as
+/// vector or document formats. Each page of the input becomes one image.
+pub trait InputDecoder {
+    /// Short name of the format, used in error messages
+    fn name(&self) -> &str;
+
+    /// Whether this decoder reads the input, judging by its file name and content
+    fn can_decode(&self, data: &[u8], filename: &str) -> bool;
+
+    /// Rasterize every page of the input
+    fn decode_pages(&self, data: &[u8]) -> Result<Vec<DynamicImage>, String>;
+}
+
+/// The input decoders tried in order before falling back to the `image` crate
+#[derive(Default)]
+pub struct Decoders {
+    decoders: Vec<Box<dyn InputDecoder>>,
+}
+
+impl fmt::Debug for Decoders {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.decoders.iter().map(|decoder| decoder.name()))
+            .finish()
+    }
+}
+
+impl Decoders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, decoder: impl InputDecoder + 'static) -> Self {
+        self.decoders.push(Box::new(decoder));
+        self
+    }
+
+    /// Pages of the input from the first decoder that reads it, None if no
+    /// decoder does
+    pub fn decode(&self, data: &[u8], filename: &str) -> Option<Result<Vec<DynamicImage>, String>> {
+        let decoder = self
+            .decoders
+            .iter()
+            .find(|decoder| decoder.can_decode(data, filename))?;
+        let pages = decoder
+            .decode_pages(data)
+            .map_err(|err| format!("Error decoding {} {}: {}", decoder.name(), filename, err));
+        Some(pages.and_then(|pages| {
+            if pages.is_empty() {
+                Err(format!("{} {} has no pages", decoder.name(), filename))
+            } else {
+                Ok(pages)
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    // Pages separated by form feeds, the value of each page is its byte count
+    struct PagesDecoder;
+
+    impl InputDecoder for PagesDecoder {
+        fn name(&self) -> &str {
+            "pages"
+        }
+
+        fn can_decode(&self, _data: &[u8], filename: &str) -> bool {
+            filename.ends_with(".pages")
+        }
+
+        fn decode_pages(&self, data: &[u8]) -> Result<Vec<DynamicImage>, String> {
+            Ok(data
+                .split(|&b| b == b'\x0c')
+                .filter(|page| !page.is_empty())
+                .map(|page| {
+                    let value = page.len() as u8;
+                    DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([value, 0, 0, 255])))
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_decoders() {
+        let decoders = Decoders::new().with(PagesDecoder);
+
+        let pages = decoders
+            .decode(b"ab\x0cabc", "report.pages")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].get_pixel(0, 0), Rgba([3, 0, 0, 255]));
+        assert!(decoders.decode(b"ab", "image.png").is_none());
+        assert!(decoders.decode(b"", "empty.pages").unwrap().is_err());
+        assert_eq!(format!("{:?}", decoders), "[\"pages\"]");
+    }
+}
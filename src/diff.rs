@@ -1,36 +1,150 @@
-use base64::encode;
-use image::Rgba;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use image::{DynamicImage, GenericImageView};
+
+use crate::row_diff::{myers_diff, patience_diff};
+
+/// How far two rows may differ and still be treated as the same row by the
+/// LCS row matching.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RowTolerance {
+    /// Rows must be byte-for-byte identical
+    Exact,
+    /// Rows match when at most this many pixels differ
+    Pixels(usize),
+    /// Rows match when at most this fraction (0.0 - 1.0) of the pixels differ
+    Ratio(f32),
+}
+
+impl RowTolerance {
+    fn max_differing_pixels(&self, row_width: usize) -> usize {
+        match *self {
+            RowTolerance::Exact => 0,
+            RowTolerance::Pixels(count) => count,
+            RowTolerance::Ratio(ratio) => (row_width as f32 * ratio.clamp(0.0, 1.0)) as usize,
+        }
+    }
+}
+
+/// Sequence diff used to line up the rows of the two images.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RowDiffAlgorithm {
+    /// Classic quadratic LCS from the `lcs-diff` crate
+    Lcs,
+    /// Linear space O(ND) Myers diff, suited to very tall images
+    Myers,
+    /// Patience diff anchored on unique rows, falling back to Myers
+    Patience,
+}
+
+/// A single row of a `CompareImage`, borrowed from its pixel buffer. The
+/// hash lets identical rows be matched without a full comparison.
+#[derive(Copy, Clone, Debug)]
+pub struct CompareRow<'a> {
+    pub pixels: &'a [u8],
+    hash: u64,
+    tolerance: RowTolerance,
+}
+
+impl<'a> CompareRow<'a> {
+    fn new(pixels: &'a [u8], tolerance: RowTolerance) -> Self {
+        let mut hasher = DefaultHasher::new();
+        pixels.hash(&mut hasher);
+        CompareRow {
+            pixels,
+            hash: hasher.finish(),
+            tolerance,
+        }
+    }
+}
+
+impl PartialEq for CompareRow<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.pixels.len() != other.pixels.len() {
+            return false;
+        }
+        // Equal hashes are still compared in full to rule out collisions
+        if self.hash == other.hash && self.pixels == other.pixels {
+            return true;
+        }
+        if self.tolerance == RowTolerance::Exact {
+            return false;
+        }
+
+        let allowed = self.tolerance.max_differing_pixels(self.pixels.len() / 4);
+        let mut differing = 0;
+        for (a, b) in self
+            .pixels
+            .chunks_exact(4)
+            .zip(other.pixels.chunks_exact(4))
+        {
+            if a != b {
+                differing += 1;
+                if differing > allowed {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+// Exact identity of a row, used to find unique rows for patience diff
+#[derive(Eq)]
+struct ExactRow<'a>(&'a [u8], u64);
+
+impl PartialEq for ExactRow<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.1 == other.1 && self.0 == other.0
+    }
+}
+
+impl Hash for ExactRow<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.1);
+    }
+}
 
 #[derive(Debug)]
 pub struct CompareImage {
     dimensions: (u32, u32),
-    pixels: Vec<Rgba<u8>>,
+    pixels: Vec<u8>,
 }
 
 impl CompareImage {
-    pub fn new(dimensions: (u32, u32), pixels: Vec<Rgba<u8>>) -> Self {
-        CompareImage { dimensions, pixels }
-    }
-
-    pub fn create_encoded_rows(&self) -> Vec<String> {
-        let mut rows = Vec::new();
-        let mut row = Vec::new();
-        for pixel in &self.pixels {
-            row.push(pixel.0[0]);
-            row.push(pixel.0[1]);
-            row.push(pixel.0[2]);
-            row.push(pixel.0[3]);
-            if row.len() == self.dimensions.0 as usize * 4 {
-                rows.push(encode(&row));
-                row.clear();
-            }
+    pub fn new(image: &DynamicImage) -> Self {
+        CompareImage {
+            dimensions: image.dimensions(),
+            pixels: image.to_rgba8().into_raw(),
         }
-        rows
+    }
+
+    pub fn width(&self) -> u32 {
+        self.dimensions.0
+    }
+
+    pub fn rows(&self, tolerance: RowTolerance) -> Vec<CompareRow<'_>> {
+        let row_len = self.dimensions.0 as usize * 4;
+        (0..self.dimensions.1 as usize)
+            .map(|y| CompareRow::new(&self.pixels[y * row_len..(y + 1) * row_len], tolerance))
+            .collect()
     }
 }
 
-pub fn diff(imga: CompareImage, imgb: CompareImage) -> Vec<lcs_diff::DiffResult<String>> {
-    let imga = imga.create_encoded_rows();
-    let imgb = imgb.create_encoded_rows();
-    lcs_diff::diff(&imga, &imgb)
+pub fn diff<'a>(
+    imga: &'a CompareImage,
+    imgb: &'a CompareImage,
+    tolerance: RowTolerance,
+    algorithm: RowDiffAlgorithm,
+) -> Vec<lcs_diff::DiffResult<CompareRow<'a>>> {
+    let imga = imga.rows(tolerance);
+    let imgb = imgb.rows(tolerance);
+    match algorithm {
+        RowDiffAlgorithm::Lcs => lcs_diff::diff(&imga, &imgb),
+        RowDiffAlgorithm::Myers => myers_diff(&imga, &imgb),
+        RowDiffAlgorithm::Patience => {
+            patience_diff(&imga, &imgb, |row| ExactRow(row.pixels, row.hash))
+        }
+    }
 }
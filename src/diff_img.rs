@@ -1,12 +1,78 @@
-use base64::DecodeError;
 use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel, Rgb, RgbImage, Rgba};
 
+mod align;
+mod blend;
+mod channels;
+mod color;
+mod decoder;
 mod diff;
+mod differ;
+mod encode;
+mod frames;
 mod image_creator;
-
+mod metrics;
+mod moves;
+mod normalize;
+mod pdf;
+#[cfg(feature = "pdf")]
+mod pdf_font;
+#[cfg(feature = "pdf")]
+mod pdf_render;
+#[cfg(feature = "pdf")]
+mod pdf_type1;
+mod phash;
+mod prefilter;
+mod raw;
+mod row_diff;
+mod scale;
+mod sequence;
+mod ssim;
+mod svg;
+
+use blend::*;
 use diff::*;
 use image_creator::*;
 
+pub use align::{align_images, estimate_offset, AlignedImages};
+pub use blend::OnionSkin;
+pub use channels::{channel_values, select_channels, Channels};
+pub use color::{
+    convert_to_srgb, detect_color_profile, from_color_space, to_color_space, ColorProfile,
+    ColorSpace, ComponentImage,
+};
+pub use decoder::{Decoders, InputDecoder};
+pub use diff::{RowDiffAlgorithm, RowTolerance};
+pub use differ::{DiffMode, DiffOptions, DiffOutcome, Differ, OutputLayout};
+pub use encode::{encode_image, save_image, EncodeOptions};
+pub use frames::{
+    compare_frames, decode_animation, diff_frames, load_frames, save_animation, Frame, FrameScore,
+    FramesComparison,
+};
+pub use metrics::{calculate_error_metrics, ChannelErrors, ErrorMetrics};
+pub use moves::{detect_moves, draw_moves, MoveDetection, MovedBlock, Region};
+pub use normalize::{normalize_images, ChannelMapping, Normalization, NormalizedImages};
+pub use pdf::is_pdf;
+#[cfg(feature = "pdf")]
+pub use pdf::{load_pdf, rasterize_pdf, PdfDecoder};
+pub use phash::{compute_hash, hash_distance, HashAlgorithm, ImageHash};
+pub use prefilter::{apply_prefilters, prefilter_images, Prefilter, MAX_MEDIAN_RADIUS};
+pub use raw::{image_from_raw, load_raw_image, PixelFormat, RawLayout};
+pub use scale::{estimate_scale, match_scale, resample_to_match, ScaledImages};
+pub use sequence::{
+    compare_sequences, diff_sequences, expand_pattern, SequenceComparison, SequenceFrame,
+};
+pub use ssim::{calculate_ms_ssim, calculate_ssim, create_ssim_map};
+pub use svg::{is_svg, SvgSize};
+#[cfg(feature = "svg")]
+pub use svg::{load_svg, rasterize_svg, SvgDecoder};
+
+/// Default colour for rows only present in the second image in LCS mode
+pub const LCS_ADDED_COLOR: Rgba<u8> = Rgba([99, 195, 99, 255]);
+/// Default colour for rows only present in the first image in LCS mode
+pub const LCS_REMOVED_COLOR: Rgba<u8> = Rgba([255, 119, 119, 255]);
+/// Default rate at which the LCS colours are blended into changed rows
+pub const LCS_RATE: f32 = 100.0 / 256.0;
+
 pub fn highlight_changes_with_color(
     before: DynamicImage,
     after: DynamicImage,
@@ -16,7 +82,6 @@ pub fn highlight_changes_with_color(
 
     before
         .pixels()
-        .into_iter()
         .zip(after.pixels())
         .map(|(a, b)| if !a.2.eq(&b.2) { (a.0, a.1, color) } else { a })
         .for_each(|(x, y, pixel)| {
@@ -28,32 +93,95 @@ pub fn highlight_changes_with_color(
 
 // Return a difference ratio between 0 and 1 for the two images
 pub fn calculate_diff_ratio(image1: DynamicImage, image2: DynamicImage) -> f64 {
-    use std::u8;
+    calculate_diff_ratio_with_channels(&image1, &image2, Channels::Rgb)
+}
 
-    let image1_raw = get_raw_pixels(&image1);
-    let image2_raw = get_raw_pixels(&image2);
+// Return a difference ratio between 0 and 1 for the selected channels of the two images
+pub fn calculate_diff_ratio_with_channels(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    channels: Channels,
+) -> f64 {
+    let image1_raw = channel_values(image1, channels);
+    let image2_raw = channel_values(image2, channels);
+    let weights = channels.value_weights();
 
     // All color types wrap an 8-bit value for each channel
-    let total_possible = (u8::MAX as usize * image1_raw.len()) as f64;
+    let pixels = image1_raw.len() / weights.len();
+    let total_possible = u8::MAX as f64 * pixels as f64 * weights.iter().sum::<f64>();
 
     image1_raw
-        .into_iter()
-        .zip(image2_raw)
-        .map(|(a, b)| abs_diff(a, b) as u64)
-        .sum::<u64>() as f64
+        .chunks_exact(weights.len())
+        .zip(image2_raw.chunks_exact(weights.len()))
+        .map(|(a, b)| {
+            (0..weights.len())
+                .map(|c| weights[c] * abs_diff(a[c], b[c]) as f64)
+                .sum::<f64>()
+        })
+        .sum::<f64>()
         / total_possible
 }
 
-fn get_raw_pixels(image: &DynamicImage) -> Vec<u8> {
-    let mut pixels = Vec::new();
+// Return a difference ratio between 0 and 1 for the two images, comparing
+// the pixels in `space` instead of as sRGB bytes
+pub fn calculate_diff_ratio_in_color_space(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    space: ColorSpace,
+) -> f64 {
+    if space == ColorSpace::Srgb {
+        return calculate_diff_ratio(image1.clone(), image2.clone());
+    }
+    let count = (image1.width() as u64 * image1.height() as u64) as f64;
+
+    image1
+        .pixels()
+        .zip(image2.pixels())
+        .map(|(a, b)| space.difference(a.2.to_rgb(), b.2.to_rgb()))
+        .sum::<f64>()
+        / count
+}
+
+/// Score used to summarize how different two images are
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Metric {
+    /// Mean absolute error as returned by `calculate_diff_ratio`, 0 means identical
+    DiffRatio,
+    /// Structural similarity, 1 means identical
+    Ssim,
+    /// Multi-scale structural similarity, 1 means identical
+    MsSsim,
+    /// Mean squared error, 0 means identical
+    Mse,
+    /// Root mean squared error, 0 means identical
+    Rmse,
+    /// Peak signal-to-noise ratio in dB, infinite means identical
+    Psnr,
+    /// Largest absolute difference of any channel value, 0 means identical
+    MaxAbsError,
+}
 
-    for pixel in image.pixels() {
-        let rgba = pixel.2.to_rgb();
-        pixels.push(rgba[0]);
-        pixels.push(rgba[1]);
-        pixels.push(rgba[2]);
+impl Metric {
+    /// Whether a higher score means more similar images
+    pub fn higher_is_better(self) -> bool {
+        matches!(self, Metric::Ssim | Metric::MsSsim | Metric::Psnr)
+    }
+}
+
+pub fn calculate_score(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    metric: Metric,
+) -> Result<f64, String> {
+    match metric {
+        Metric::DiffRatio => Ok(calculate_diff_ratio(image1.clone(), image2.clone())),
+        Metric::Ssim => calculate_ssim(image1, image2),
+        Metric::MsSsim => calculate_ms_ssim(image1, image2),
+        Metric::Mse | Metric::Rmse | Metric::Psnr | Metric::MaxAbsError => {
+            let errors = calculate_error_metrics(image1, image2)?;
+            Ok(errors.overall.value(metric).unwrap())
+        }
     }
-    pixels
 }
 
 /// abs(x - y) for u8
@@ -61,32 +189,44 @@ fn abs_diff(x: u8, y: u8) -> u8 {
     if x > y {
         return x - y;
     }
-    return y - x;
+    y - x
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum BlendMode {
     BIAS,
     HUE,
+    /// 50/50 average of the two images
+    Average,
+    /// Standard compositing modes, image1 is the base layer and image2 the blend layer
     Overlay,
+    Difference,
+    Exclusion,
+    Multiply,
+    Screen,
+    SoftLight,
+    HardLight,
+    /// Tinted layers of both images, see `OnionSkin`
+    OnionSkin,
 }
 
 pub fn blend_images(
     image1: DynamicImage,
     image2: DynamicImage,
     blend_mode: BlendMode,
+    onion_skin: OnionSkin,
 ) -> Result<DynamicImage, String> {
     let mut result: RgbImage = ImageBuffer::new(image1.width(), image2.height());
 
     image1
         .pixels()
-        .into_iter()
         .zip(image2.pixels())
         .map(|(a, b)| {
             blend_pixel(
                 (a.0, a.1, a.2.to_rgb()),
                 (b.0, b.1, b.2.to_rgb()),
                 blend_mode,
+                &onion_skin,
             )
         })
         .for_each(|(x, y, pixel)| {
@@ -96,10 +236,91 @@ pub fn blend_images(
     Ok(DynamicImage::ImageRgb8(result))
 }
 
+/// Whether `blend_images_in_color_space` can blend in `space` with
+/// `blend_mode`. sRGB takes every mode. Linear light takes average,
+/// difference and onion-skin, which add up or subtract light. Lab and HSV
+/// take average only.
+pub fn blends_in_color_space(blend_mode: BlendMode, space: ColorSpace) -> bool {
+    match space {
+        ColorSpace::Srgb => true,
+        ColorSpace::LinearRgb => matches!(
+            blend_mode,
+            BlendMode::Average | BlendMode::Difference | BlendMode::OnionSkin
+        ),
+        ColorSpace::Lab | ColorSpace::Hsv => blend_mode == BlendMode::Average,
+    }
+}
+
+// Average of two HSV colours, hue takes the short way around the circle and
+// the hue of a grey pixel is ignored
+fn average_hsv(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    let hue = match (a[1] == 0.0, b[1] == 0.0) {
+        (true, false) => b[0],
+        (false, true) => a[0],
+        _ => {
+            let (low, high) = (a[0].min(b[0]), a[0].max(b[0]));
+            let high = if high - low > 180.0 {
+                high - 360.0
+            } else {
+                high
+            };
+            ((low + high) / 2.0).rem_euclid(360.0)
+        }
+    };
+    [hue, (a[1] + b[1]) / 2.0, (a[2] + b[2]) / 2.0]
+}
+
+/// Like `blend_images`, but the pixels are blended as components of `space`
+/// and the result is converted back to sRGB. Fails for the blend modes
+/// `blends_in_color_space` rules out.
+pub fn blend_images_in_color_space(
+    image1: DynamicImage,
+    image2: DynamicImage,
+    blend_mode: BlendMode,
+    onion_skin: OnionSkin,
+    space: ColorSpace,
+) -> Result<DynamicImage, String> {
+    if space == ColorSpace::Srgb {
+        return blend_images(image1, image2, blend_mode, onion_skin);
+    }
+    if !blends_in_color_space(blend_mode, space) {
+        return Err(format!(
+            "{:?} blending only works in sRGB, {:?} takes {}",
+            blend_mode,
+            space,
+            match space {
+                ColorSpace::LinearRgb => "average, difference and onion-skin",
+                _ => "average",
+            }
+        ));
+    }
+    if image1.dimensions() != image2.dimensions() {
+        return Err(format!(
+            "Images must have the same dimensions, got {:?} and {:?}",
+            image1.dimensions(),
+            image2.dimensions()
+        ));
+    }
+
+    let components1 = to_color_space(&image1, space);
+    let components2 = to_color_space(&image2, space);
+    let blended = ComponentImage::from_fn(image1.width(), image1.height(), |x, y| {
+        let (a, b) = (components1.get_pixel(x, y).0, components2.get_pixel(x, y).0);
+        Rgb(match blend_mode {
+            BlendMode::Difference => [0, 1, 2].map(|c| (a[c] - b[c]).abs()),
+            BlendMode::OnionSkin => onion_skin.blend_linear(a, b),
+            _ if space == ColorSpace::Hsv => average_hsv(a, b),
+            _ => [0, 1, 2].map(|c| (a[c] + b[c]) / 2.0),
+        })
+    });
+    Ok(DynamicImage::ImageRgb8(from_color_space(&blended, space)))
+}
+
 fn blend_pixel(
     pixel_x: (u32, u32, Rgb<u8>),
     pixel_y: (u32, u32, Rgb<u8>),
     blend_mode: BlendMode,
+    onion_skin: &OnionSkin,
 ) -> (u32, u32, Rgba<u8>) {
     let a_rgb = pixel_x.2.to_rgb();
     let b_rgb = pixel_y.2.to_rgb();
@@ -112,57 +333,73 @@ fn blend_pixel(
 
     let avg_diff = total_diff / 3.0;
 
-    if avg_diff == 0.0 {
-        return (
+    match blend_mode {
+        BlendMode::BIAS | BlendMode::HUE | BlendMode::Average if avg_diff == 0.0 => (
             pixel_x.0,
             pixel_x.1,
             Rgba([a_rgb[0], a_rgb[1], a_rgb[2], 0]),
-        );
-    } else {
-        match blend_mode {
-            BlendMode::BIAS => {
-                let red_bias = get_bias_from_diff(red_diff, b_rgb[0], 128);
-                let green_bias = get_bias_from_diff(green_diff, b_rgb[1], 0);
-                let blue_bias = get_bias_from_diff(blue_diff, b_rgb[2], 128);
-
-                let blended_pixel = blend_rgb_pixels(
-                    (a_rgb[0], a_rgb[1], a_rgb[2]),
-                    (b_rgb[0], b_rgb[1], b_rgb[2]),
-                    (red_bias, green_bias, blue_bias),
-                );
-                return (
-                    pixel_x.0,
-                    pixel_x.1,
-                    Rgba([blended_pixel.0, blended_pixel.1, blended_pixel.2, 0]),
-                );
-            }
-            BlendMode::HUE => {
-                // make more purple
-                let blended_pixel = blend_rgb_pixels(
-                    (a_rgb[0], a_rgb[1], a_rgb[2]),
-                    (b_rgb[0], b_rgb[1], b_rgb[2]),
-                    (0.3, -0.3, 0.3),
-                );
-                return (
-                    pixel_x.0,
-                    pixel_x.1,
-                    Rgba([blended_pixel.0, blended_pixel.1, blended_pixel.2, 0]),
-                );
-            }
-            BlendMode::Overlay => {
-                let overlayed_pixel = create_overlayed_pixel(
-                    (a_rgb[0], a_rgb[1], a_rgb[2]),
-                    (b_rgb[0], b_rgb[1], b_rgb[2]),
-                    0.5,
-                );
-
-                return (
-                    pixel_x.0,
-                    pixel_x.1,
-                    Rgba([overlayed_pixel.0, overlayed_pixel.1, overlayed_pixel.2, 0]),
-                );
-            }
+        ),
+        BlendMode::BIAS => {
+            let red_bias = get_bias_from_diff(red_diff, b_rgb[0], 128);
+            let green_bias = get_bias_from_diff(green_diff, b_rgb[1], 0);
+            let blue_bias = get_bias_from_diff(blue_diff, b_rgb[2], 128);
+
+            let blended_pixel = blend_rgb_pixels(
+                (a_rgb[0], a_rgb[1], a_rgb[2]),
+                (b_rgb[0], b_rgb[1], b_rgb[2]),
+                (red_bias, green_bias, blue_bias),
+            );
+            (
+                pixel_x.0,
+                pixel_x.1,
+                Rgba([blended_pixel.0, blended_pixel.1, blended_pixel.2, 0]),
+            )
+        }
+        BlendMode::HUE => {
+            // make more purple
+            let blended_pixel = blend_rgb_pixels(
+                (a_rgb[0], a_rgb[1], a_rgb[2]),
+                (b_rgb[0], b_rgb[1], b_rgb[2]),
+                (0.3, -0.3, 0.3),
+            );
+            (
+                pixel_x.0,
+                pixel_x.1,
+                Rgba([blended_pixel.0, blended_pixel.1, blended_pixel.2, 0]),
+            )
+        }
+        BlendMode::Average => {
+            let overlayed_pixel = create_overlayed_pixel(
+                (a_rgb[0], a_rgb[1], a_rgb[2]),
+                (b_rgb[0], b_rgb[1], b_rgb[2]),
+                0.5,
+            );
+
+            (
+                pixel_x.0,
+                pixel_x.1,
+                Rgba([overlayed_pixel.0, overlayed_pixel.1, overlayed_pixel.2, 0]),
+            )
+        }
+        BlendMode::OnionSkin => {
+            let blended_pixel = onion_skin.blend(a_rgb, b_rgb);
+            (
+                pixel_x.0,
+                pixel_x.1,
+                Rgba([blended_pixel[0], blended_pixel[1], blended_pixel[2], 0]),
+            )
         }
+        // Separable modes are applied to every pixel, unchanged ones included
+        mode => (
+            pixel_x.0,
+            pixel_x.1,
+            Rgba([
+                blend_channel(a_rgb[0], b_rgb[0], mode),
+                blend_channel(a_rgb[1], b_rgb[1], mode),
+                blend_channel(a_rgb[2], b_rgb[2], mode),
+                0,
+            ]),
+        ),
     }
 }
 
@@ -178,7 +415,7 @@ fn create_overlayed_pixel(
     let g_blended = ((alpha * green_x as f32) + ((1.0 - alpha) * green_y as f32)).min(255.0) as u8;
     let b_blended = ((alpha * blue_x as f32) + ((1.0 - alpha) * blue_y as f32)).min(255.0) as u8;
 
-    return (r_blended, g_blended, b_blended);
+    (r_blended, g_blended, b_blended)
 }
 
 // Calculate the bias for a color channel based on the difference between two pixels
@@ -188,9 +425,7 @@ fn get_bias_from_diff(diff: u8, current: u8, target: u8) -> f32 {
     let target = target as f32;
 
     let bias = diff / current;
-    let bias = bias * target;
-
-    bias
+    bias * target
 }
 
 // Blend two RGB pixels together
@@ -214,41 +449,82 @@ fn blend_rgb_pixels(
 
     // Return the blended pixel, clamping each value to [0, 255]
     (
-        out_r.min(255.0).max(0.0) as u8,
-        out_g.min(255.0).max(0.0) as u8,
-        out_b.min(255.0).max(0.0) as u8,
+        out_r.clamp(0.0, 255.0) as u8,
+        out_g.clamp(0.0, 255.0) as u8,
+        out_b.clamp(0.0, 255.0) as u8,
     )
 }
 
+/// Result of `lcs_diff`
+#[derive(Debug)]
+pub struct LcsOutput {
+    /// Every row of both images in diff order, added and removed rows tinted
+    pub diff_image: DynamicImage,
+    // Before and after row index of every row of the diff
+    rows: Vec<(Option<usize>, Option<usize>)>,
+}
+
+impl LcsOutput {
+    /// The marked before and after images next to each other, padded so
+    /// that common rows line up
+    pub fn side_by_side(&self, before: &DynamicImage, after: &DynamicImage) -> DynamicImage {
+        get_side_by_side_image(before, after, &self.rows)
+    }
+}
+
+/// Line up the rows of `before` and `after` and mark the rows that were
+/// removed from `before` and added in `after`. Both inputs are marked up in
+/// place.
 pub fn lcs_diff(
     before: &mut DynamicImage,
     after: &mut DynamicImage,
     rate: f32,
-) -> Result<DynamicImage, DecodeError> {
-    let compare_before = CompareImage::new(
-        before.dimensions(),
-        before.pixels().map(|pix| pix.2).collect(),
-    );
-    let compare_after = CompareImage::new(
-        after.dimensions(),
-        after.pixels().map(|pix| pix.2).collect(),
-    );
-    let result = diff(compare_before, compare_after);
+    added_color: Rgba<u8>,
+    removed_color: Rgba<u8>,
+    tolerance: RowTolerance,
+    algorithm: RowDiffAlgorithm,
+) -> Result<LcsOutput, String> {
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(format!("Blend rate {} must be between 0 and 1", rate));
+    }
+    let added_color = (added_color[0], added_color[1], added_color[2]);
+    let removed_color = (removed_color[0], removed_color[1], removed_color[2]);
+
+    let compare_before = CompareImage::new(before);
+    let compare_after = CompareImage::new(after);
+    let result = diff(&compare_before, &compare_after, tolerance, algorithm);
 
     let mut added: Vec<usize> = Vec::new();
     let mut removed: Vec<usize> = Vec::new();
+    let mut rows: Vec<(Option<usize>, Option<usize>)> = Vec::with_capacity(result.len());
     for d in result.iter() {
         match d {
-            &lcs_diff::DiffResult::Added(ref a) => added.push(a.new_index.unwrap()),
-            &lcs_diff::DiffResult::Removed(ref r) => removed.push(r.old_index.unwrap()),
-            _ => (),
+            lcs_diff::DiffResult::Added(a) => {
+                added.push(a.new_index.unwrap());
+                rows.push((None, a.new_index));
+            }
+            lcs_diff::DiffResult::Removed(r) => {
+                removed.push(r.old_index.unwrap());
+                rows.push((r.old_index, None));
+            }
+            lcs_diff::DiffResult::Common(c) => rows.push((c.old_index, c.new_index)),
         }
     }
 
-    mark_org_image(before, RED, rate, &removed);
-    mark_org_image(after, GREEN, rate, &added);
+    // Rendered from the unmarked copies before the originals are marked up
+    let diff_image = get_diff_image(
+        compare_before.width(),
+        compare_after.width(),
+        &result,
+        added_color,
+        removed_color,
+        rate,
+    );
+
+    mark_org_image(before, removed_color, rate, &removed);
+    mark_org_image(after, added_color, rate, &added);
 
-    get_diff_image(before.dimensions().0, after.dimensions().0, &result, rate)
+    Ok(LcsOutput { diff_image, rows })
 }
 
 #[cfg(test)]
@@ -274,6 +550,90 @@ mod tests {
         assert_eq!(result, EXPECTED_RESULT);
     }
 
+    #[test]
+    fn test_calculate_diff_ratio_in_color_space() {
+        let image1 = DynamicImage::ImageRgb8(ImageBuffer::from_fn(4, 1, |x, _| {
+            Rgb([x as u8 * 60, 10, 200])
+        }));
+        let image2 = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(4, 1, Rgb([20, 10, 200])));
+
+        assert_eq!(
+            calculate_diff_ratio_in_color_space(&image1, &image2, ColorSpace::Srgb),
+            calculate_diff_ratio(image1.clone(), image2.clone())
+        );
+        for space in [ColorSpace::LinearRgb, ColorSpace::Lab, ColorSpace::Hsv] {
+            let ratio = calculate_diff_ratio_in_color_space(&image1, &image2, space);
+            assert!(ratio > 0.0 && ratio < 1.0);
+            assert_eq!(
+                calculate_diff_ratio_in_color_space(&image1, &image1, space),
+                0.0
+            );
+        }
+    }
+
+    #[test]
+    fn test_blend_images_in_linear_light() {
+        let black = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(2, 2, Rgb([0, 0, 0])));
+        let white = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(2, 2, Rgb([255, 255, 255])));
+
+        let gamma = blend_images_in_color_space(
+            black.clone(),
+            white.clone(),
+            BlendMode::Average,
+            OnionSkin::default(),
+            ColorSpace::Srgb,
+        )
+        .unwrap();
+        let linear = blend_images_in_color_space(
+            black,
+            white,
+            BlendMode::Average,
+            OnionSkin::default(),
+            ColorSpace::LinearRgb,
+        )
+        .unwrap();
+
+        // half of the light is much brighter than half of the encoded value
+        assert_eq!(gamma.to_rgb8().get_pixel(0, 0), &Rgb([127, 127, 127]));
+        assert_eq!(linear.to_rgb8().get_pixel(0, 0), &Rgb([188, 188, 188]));
+    }
+
+    #[test]
+    fn test_blend_images_in_hsv_and_lab() {
+        let image = |rgb: Rgb<u8>| DynamicImage::ImageRgb8(ImageBuffer::from_pixel(2, 2, rgb));
+        let blend = |a: Rgb<u8>, b: Rgb<u8>, mode, space| {
+            blend_images_in_color_space(image(a), image(b), mode, OnionSkin::default(), space)
+        };
+        // hue 350 and 10 degrees
+        let (pink, orange) = (Rgb([255, 0, 42]), Rgb([255, 42, 0]));
+
+        let hsv = blend(pink, orange, BlendMode::Average, ColorSpace::Hsv).unwrap();
+
+        assert_eq!(hsv.to_rgb8().get_pixel(0, 0), &Rgb([255, 0, 0]));
+        assert!(blend(pink, orange, BlendMode::Multiply, ColorSpace::Lab).is_err());
+        assert!(blend(pink, orange, BlendMode::Difference, ColorSpace::Hsv).is_err());
+        assert!(blend(pink, orange, BlendMode::Difference, ColorSpace::LinearRgb).is_ok());
+    }
+
+    #[test]
+    fn test_calculate_diff_ratio_with_channels() {
+        // same brightness, red and green swapped, half transparent
+        let image1 =
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([255, 0, 0, 255])));
+        let image2 =
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([0, 255, 0, 127])));
+
+        let ratio = |channels| calculate_diff_ratio_with_channels(&image1, &image2, channels);
+
+        assert!((ratio(Channels::Rgb) - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(ratio(Channels::Red), 1.0);
+        assert_eq!(ratio(Channels::Blue), 0.0);
+        assert_eq!(ratio(Channels::Alpha), 128.0 / 255.0);
+        // half of the weight on each of the swapped channels
+        assert_eq!(ratio(Channels::Weighted([1.0, 1.0, 0.0, 0.0])), 1.0);
+        assert_eq!(ratio(Channels::Weighted([1.0, 0.0, 1.0, 0.0])), 0.5);
+    }
+
     #[test]
     fn test_create_overlayed_pixel() {
         let pixel_x = (100, 150, 200);
@@ -335,7 +695,175 @@ mod tests {
         let image1 = image::open("tests/images/image1.png").unwrap();
         let image2 = image::open("tests/images/image2.png").unwrap();
         let blend_mode = BlendMode::Overlay;
-        let result = blend_images(image1, image2, blend_mode);
-        assert_eq!(result.is_ok(), true);
+        let result = blend_images(image1, image2, blend_mode, OnionSkin::default());
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_blend_images_onion_skin() {
+        let image1 = striped_image(&[10, 20], 2);
+        let image2 = striped_image(&[10, 0], 2);
+        let result =
+            blend_images(image1, image2, BlendMode::OnionSkin, OnionSkin::default()).unwrap();
+
+        assert_eq!(result.get_pixel(0, 0), Rgba([10, 10, 10, 255]));
+        assert_eq!(result.get_pixel(0, 1), Rgba([20, 0, 0, 255]));
+    }
+
+    fn striped_image(rows: &[u8], width: u32) -> DynamicImage {
+        let img = ImageBuffer::from_fn(width, rows.len() as u32, |_, y| {
+            let v = rows[y as usize];
+            Rgba([v, v, v, 255])
+        });
+        DynamicImage::ImageRgba8(img)
+    }
+
+    fn count_lcs_changes(
+        before: &DynamicImage,
+        after: &DynamicImage,
+        tolerance: RowTolerance,
+        algorithm: RowDiffAlgorithm,
+    ) -> usize {
+        let compare_before = CompareImage::new(before);
+        let compare_after = CompareImage::new(after);
+        diff(&compare_before, &compare_after, tolerance, algorithm)
+            .iter()
+            .filter(|d| !matches!(d, lcs_diff::DiffResult::Common(_)))
+            .count()
+    }
+
+    #[test]
+    fn test_lcs_row_tolerance() {
+        let before = striped_image(&[10, 20, 30, 40], 10);
+        let mut after = striped_image(&[10, 99, 20, 30, 40], 10);
+        // one noisy pixel in an otherwise unchanged row
+        if let DynamicImage::ImageRgba8(ref mut buf) = after {
+            buf.put_pixel(3, 3, Rgba([31, 30, 30, 255]));
+        }
+
+        for algorithm in [
+            RowDiffAlgorithm::Lcs,
+            RowDiffAlgorithm::Myers,
+            RowDiffAlgorithm::Patience,
+        ] {
+            let changes = |tolerance| count_lcs_changes(&before, &after, tolerance, algorithm);
+
+            assert_eq!(changes(RowTolerance::Exact), 3);
+            assert_eq!(changes(RowTolerance::Pixels(1)), 1);
+            assert_eq!(changes(RowTolerance::Ratio(0.1)), 1);
+            assert_eq!(changes(RowTolerance::Ratio(0.05)), 3);
+        }
+    }
+
+    #[test]
+    fn test_lcs_diff_different_widths() {
+        let mut before = striped_image(&[10, 20, 30], 8);
+        let mut after = striped_image(&[10, 25, 20, 30], 12);
+
+        let result = lcs_diff(
+            &mut before,
+            &mut after,
+            0.5,
+            LCS_ADDED_COLOR,
+            LCS_REMOVED_COLOR,
+            RowTolerance::Exact,
+            RowDiffAlgorithm::Lcs,
+        )
+        .unwrap()
+        .diff_image;
+
+        // rows of different widths never match
+        assert_eq!(result.dimensions(), (12, 7));
+        // removed rows come last, padded past the narrower width
+        assert_eq!(result.get_pixel(0, 6).0[3], 255);
+        assert_eq!(result.get_pixel(10, 6).0[3], 0);
+    }
+
+    #[test]
+    fn test_lcs_diff_colors_and_rate() {
+        let mut before = striped_image(&[10, 20], 4);
+        let mut after = striped_image(&[10, 30], 4);
+        let added = Rgba([0, 0, 255, 255]);
+        let removed = Rgba([255, 0, 0, 255]);
+
+        let result = lcs_diff(
+            &mut before,
+            &mut after,
+            1.0,
+            added,
+            removed,
+            RowTolerance::Exact,
+            RowDiffAlgorithm::Myers,
+        )
+        .unwrap()
+        .diff_image;
+
+        assert_eq!(result.get_pixel(0, 0), Rgba([10, 10, 10, 255]));
+        assert_eq!(result.get_pixel(0, 1), Rgba([0, 0, 255, 255]));
+        assert_eq!(result.get_pixel(0, 2), Rgba([255, 0, 0, 255]));
+        assert_eq!(before.get_pixel(0, 1), Rgba([255, 0, 0, 255]));
+        assert_eq!(after.get_pixel(0, 1), Rgba([0, 0, 255, 255]));
+
+        let invalid_rate = lcs_diff(
+            &mut before,
+            &mut after,
+            1.5,
+            added,
+            removed,
+            RowTolerance::Exact,
+            RowDiffAlgorithm::Myers,
+        );
+        assert!(invalid_rate.is_err());
+    }
+
+    #[test]
+    fn test_lcs_diff_side_by_side() {
+        let mut before = striped_image(&[10, 20, 30], 4);
+        let mut after = striped_image(&[10, 25, 20, 30], 6);
+        let output = lcs_diff(
+            &mut before,
+            &mut after,
+            0.0,
+            LCS_ADDED_COLOR,
+            LCS_REMOVED_COLOR,
+            RowTolerance::Exact,
+            RowDiffAlgorithm::Myers,
+        )
+        .unwrap();
+
+        // widths differ, so every row is a change: 3 removed, 4 added
+        let side_by_side = output.side_by_side(&before, &after);
+        assert_eq!(side_by_side.dimensions(), (4 + 8 + 6, 7));
+        for y in 0..7 {
+            let left = side_by_side.get_pixel(0, y).0[3] != 0;
+            let right = side_by_side.get_pixel(12, y).0[3] != 0;
+            assert!(left != right);
+        }
+    }
+
+    #[test]
+    fn test_lcs_diff_side_by_side_aligns_common_rows() {
+        let mut before = striped_image(&[10, 20, 30], 4);
+        let mut after = striped_image(&[10, 25, 20, 30], 4);
+        let output = lcs_diff(
+            &mut before,
+            &mut after,
+            0.0,
+            LCS_ADDED_COLOR,
+            LCS_REMOVED_COLOR,
+            RowTolerance::Exact,
+            RowDiffAlgorithm::Lcs,
+        )
+        .unwrap();
+
+        let side_by_side = output.side_by_side(&before, &after);
+        assert_eq!(side_by_side.dimensions(), (16, 4));
+        // the inserted row is padded on the left, the rest line up
+        assert_eq!(side_by_side.get_pixel(0, 1), Rgba([0, 0, 0, 0]));
+        assert_eq!(side_by_side.get_pixel(12, 1), Rgba([25, 25, 25, 255]));
+        for (y, v) in [(0, 10), (2, 20), (3, 30)] {
+            assert_eq!(side_by_side.get_pixel(0, y), Rgba([v, v, v, 255]));
+            assert_eq!(side_by_side.get_pixel(12, y), Rgba([v, v, v, 255]));
+        }
     }
 }
@@ -0,0 +1,596 @@
+use image::imageops::FilterType;
+use image::{imageops, DynamicImage, GenericImageView, Rgb, RgbImage, Rgba, RgbaImage};
+
+use crate::{
+    align_images, blend_images_in_color_space, calculate_diff_ratio_in_color_space,
+    calculate_diff_ratio_with_channels, calculate_error_metrics, calculate_score, compute_hash,
+    create_ssim_map, detect_moves, draw_moves, highlight_changes_with_color, lcs_diff, match_scale,
+    normalize_images, prefilter_images, resample_to_match, select_channels, BlendMode,
+    ChannelMapping, Channels, ColorSpace, ErrorMetrics, HashAlgorithm, ImageHash, Metric,
+    MoveDetection, Normalization, OnionSkin, Prefilter, Region, RowDiffAlgorithm, RowTolerance,
+    LCS_ADDED_COLOR, LCS_RATE, LCS_REMOVED_COLOR,
+};
+
+/// What `Differ::compare` draws or reports besides the score
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DiffMode {
+    /// Paint every changed pixel of image1 in the mark colour
+    MarkWithColor,
+    /// Line up the rows of both images and tint the added and removed rows
+    LCS,
+    /// Blend both images with the blend mode
+    Blend,
+    /// Perceptual hashes of both images, no diff image
+    Hash,
+    /// Blocks that moved, drawn over image2
+    Moves,
+}
+
+/// How the diff image is laid out
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputLayout {
+    /// The diff image on its own
+    Diff,
+    /// image1, the diff image and image2 next to each other. In LCS mode the
+    /// marked images next to each other with common rows lined up.
+    SideBySide,
+}
+
+/// Options of a `Differ`. Start from `DiffOptions::new()` and chain the
+/// setters, every option left out keeps its default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffOptions {
+    /// None only scores the images
+    pub mode: Option<DiffMode>,
+    pub metric: Metric,
+    /// Regions of the input images left out of the comparison
+    pub masks: Vec<Region>,
+    /// Resample image2 to the size of image1 with this filter
+    pub resize_filter: Option<FilterType>,
+    /// Detect the scale of image2 and resample it to image1, with
+    /// `resize_filter` or a triangle filter
+    pub scale_invariant: bool,
+    /// Largest translation between the images to compensate for
+    pub align: Option<u32>,
+    pub prefilters: Vec<Prefilter>,
+    pub channels: Channels,
+    pub color_space: ColorSpace,
+    pub normalization: Option<Normalization>,
+    /// Largest channel difference of a pixel that is not marked as changed
+    pub tolerance: u8,
+    pub row_tolerance: RowTolerance,
+    pub row_algorithm: RowDiffAlgorithm,
+    pub color: Rgba<u8>,
+    pub added_color: Rgba<u8>,
+    pub removed_color: Rgba<u8>,
+    pub rate: f32,
+    pub blend_mode: BlendMode,
+    pub onion_skin: OnionSkin,
+    pub hash_algorithm: HashAlgorithm,
+    pub max_move: u32,
+    /// Also create the per-pixel SSIM map
+    pub ssim_map: bool,
+    /// LCS: also create the marked images next to each other
+    pub side_by_side: bool,
+    pub layout: OutputLayout,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            mode: None,
+            metric: Metric::DiffRatio,
+            masks: Vec::new(),
+            resize_filter: None,
+            scale_invariant: false,
+            align: None,
+            prefilters: Vec::new(),
+            channels: Channels::Rgb,
+            color_space: ColorSpace::Srgb,
+            normalization: None,
+            tolerance: 0,
+            row_tolerance: RowTolerance::Exact,
+            row_algorithm: RowDiffAlgorithm::Lcs,
+            color: Rgba([0, 255, 0, 0]),
+            added_color: LCS_ADDED_COLOR,
+            removed_color: LCS_REMOVED_COLOR,
+            rate: LCS_RATE,
+            blend_mode: BlendMode::Overlay,
+            onion_skin: OnionSkin::default(),
+            hash_algorithm: HashAlgorithm::Perceptual,
+            max_move: 64,
+            ssim_map: false,
+            side_by_side: false,
+            layout: OutputLayout::Diff,
+        }
+    }
+}
+
+impl DiffOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(mut self, mode: DiffMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Leave a region out of the comparison, it is filled with transparent
+    /// black in both images before anything else happens
+    pub fn mask(mut self, region: Region) -> Self {
+        self.masks.push(region);
+        self
+    }
+
+    pub fn resize(mut self, filter: FilterType) -> Self {
+        self.resize_filter = Some(filter);
+        self
+    }
+
+    pub fn scale_invariant(mut self, scale_invariant: bool) -> Self {
+        self.scale_invariant = scale_invariant;
+        self
+    }
+
+    pub fn align(mut self, max_offset: u32) -> Self {
+        self.align = Some(max_offset);
+        self
+    }
+
+    pub fn prefilters(mut self, prefilters: Vec<Prefilter>) -> Self {
+        self.prefilters = prefilters;
+        self
+    }
+
+    pub fn channels(mut self, channels: Channels) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    pub fn color_space(mut self, space: ColorSpace) -> Self {
+        self.color_space = space;
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn row_tolerance(mut self, tolerance: RowTolerance) -> Self {
+        self.row_tolerance = tolerance;
+        self
+    }
+
+    pub fn row_algorithm(mut self, algorithm: RowDiffAlgorithm) -> Self {
+        self.row_algorithm = algorithm;
+        self
+    }
+
+    pub fn color(mut self, color: Rgba<u8>) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn lcs_colors(mut self, added: Rgba<u8>, removed: Rgba<u8>, rate: f32) -> Self {
+        self.added_color = added;
+        self.removed_color = removed;
+        self.rate = rate;
+        self
+    }
+
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn onion_skin(mut self, onion_skin: OnionSkin) -> Self {
+        self.onion_skin = onion_skin;
+        self
+    }
+
+    pub fn hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = algorithm;
+        self
+    }
+
+    pub fn max_move(mut self, max_move: u32) -> Self {
+        self.max_move = max_move;
+        self
+    }
+
+    pub fn ssim_map(mut self, ssim_map: bool) -> Self {
+        self.ssim_map = ssim_map;
+        self
+    }
+
+    pub fn side_by_side(mut self, side_by_side: bool) -> Self {
+        self.side_by_side = side_by_side;
+        self
+    }
+
+    pub fn layout(mut self, layout: OutputLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+}
+
+/// Everything a comparison found. Fields of steps that did not run are None.
+#[derive(Debug)]
+pub struct DiffOutcome {
+    /// Scale of image2 relative to image1 with `scale_invariant`
+    pub scale: Option<f64>,
+    /// Translation of image2 with `align`
+    pub offset: Option<(i32, i32)>,
+    /// Both images after the prefilters
+    pub prefiltered: Option<(DynamicImage, DynamicImage)>,
+    /// Red, green and blue mappings applied to image1 and image2
+    pub normalization: Option<([ChannelMapping; 3], [ChannelMapping; 3])>,
+    /// Score in `metric`, None when the compared images differ in size and
+    /// `metric` is not the diff ratio
+    pub score: Option<f64>,
+    /// Per channel errors when `metric` is one of the error metrics
+    pub errors: Option<ErrorMetrics>,
+    pub ssim_map: Option<DynamicImage>,
+    /// Diff image of the modes drawing one, laid out as `layout`
+    pub image: Option<DynamicImage>,
+    /// LCS: image1 and image2 with their removed and added rows marked
+    pub marked: Option<(DynamicImage, DynamicImage)>,
+    /// LCS with `side_by_side`: the marked images next to each other with
+    /// common rows lined up
+    pub side_by_side: Option<DynamicImage>,
+    pub moves: Option<MoveDetection>,
+    pub hashes: Option<(ImageHash, ImageHash)>,
+}
+
+/// Compares pairs of images with one set of options
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Differ {
+    options: DiffOptions,
+}
+
+fn apply_masks(image: &DynamicImage, masks: &[Region]) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    for mask in masks {
+        for y in mask.y.min(height)..(mask.y + mask.height).min(height) {
+            for x in mask.x.min(width)..(mask.x + mask.width).min(width) {
+                rgba.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+            }
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+// Like `highlight_changes_with_color`, ignoring changes of at most `tolerance`
+// in every channel
+fn mark_changes(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    color: Rgba<u8>,
+    tolerance: u8,
+) -> Result<DynamicImage, String> {
+    if image1.dimensions() != image2.dimensions() {
+        return Err(format!(
+            "Images must have the same dimensions, got {:?} and {:?}",
+            image1.dimensions(),
+            image2.dimensions()
+        ));
+    }
+    if tolerance == 0 {
+        return highlight_changes_with_color(image1.clone(), image2.clone(), color);
+    }
+    let mut result = RgbImage::new(image1.width(), image1.height());
+    for ((x, y, a), (_, _, b)) in image1.pixels().zip(image2.pixels()) {
+        let changed = (0..4).any(|c| a[c].abs_diff(b[c]) > tolerance);
+        let pixel = if changed { color } else { a };
+        result.put_pixel(x, y, Rgb([pixel[0], pixel[1], pixel[2]]));
+    }
+    Ok(DynamicImage::ImageRgb8(result))
+}
+
+fn side_by_side(images: &[&DynamicImage]) -> DynamicImage {
+    let width = images.iter().map(|image| image.width()).sum();
+    let height = images.iter().map(|image| image.height()).max().unwrap_or(0);
+    let mut output = RgbaImage::new(width, height);
+    let mut x = 0;
+    for image in images {
+        imageops::replace(&mut output, &image.to_rgba8(), x as i64, 0);
+        x += image.width();
+    }
+    DynamicImage::ImageRgba8(output)
+}
+
+impl Differ {
+    pub fn new(options: DiffOptions) -> Self {
+        Differ { options }
+    }
+
+    pub fn options(&self) -> &DiffOptions {
+        &self.options
+    }
+
+    /// Compare two images: mask, scale, align, prefilter, select channels and
+    /// normalize them as configured, score them with the metric and draw the
+    /// diff of the mode.
+    pub fn compare(
+        &self,
+        image1: &DynamicImage,
+        image2: &DynamicImage,
+    ) -> Result<DiffOutcome, String> {
+        let options = &self.options;
+        let mut outcome = DiffOutcome {
+            scale: None,
+            offset: None,
+            prefiltered: None,
+            normalization: None,
+            score: None,
+            errors: None,
+            ssim_map: None,
+            image: None,
+            marked: None,
+            side_by_side: None,
+            moves: None,
+            hashes: None,
+        };
+
+        let (mut image1, mut image2) = if options.masks.is_empty() {
+            (image1.clone(), image2.clone())
+        } else {
+            (
+                apply_masks(image1, &options.masks),
+                apply_masks(image2, &options.masks),
+            )
+        };
+
+        if options.scale_invariant {
+            let filter = options.resize_filter.unwrap_or(FilterType::Triangle);
+            let scaled = match_scale(&image1, &image2, filter)?;
+            outcome.scale = Some(scaled.scale);
+            (image1, image2) = (scaled.image1, scaled.image2);
+        } else if let Some(filter) = options.resize_filter {
+            image2 = resample_to_match(&image1, &image2, filter);
+        }
+
+        if let Some(max_offset) = options.align {
+            let aligned = align_images(&image1, &image2, max_offset)?;
+            outcome.offset = Some(aligned.offset);
+            (image1, image2) = (aligned.image1, aligned.image2);
+        }
+
+        if !options.prefilters.is_empty() {
+            (image1, image2) = prefilter_images(&image1, &image2, &options.prefilters);
+            outcome.prefiltered = Some((image1.clone(), image2.clone()));
+        }
+
+        if options.channels != Channels::Rgb {
+            image1 = select_channels(&image1, options.channels);
+            image2 = select_channels(&image2, options.channels);
+        }
+
+        if let Some(normalization) = options.normalization {
+            let normalized = normalize_images(&image1, &image2, normalization)?;
+            outcome.normalization = Some((normalized.image1_mapping, normalized.image2_mapping));
+            (image1, image2) = (normalized.image1, normalized.image2);
+        }
+
+        // the diff ratio has always been taken of images of different sizes,
+        // the other metrics need the same size
+        let same_size = image1.dimensions() == image2.dimensions();
+        if same_size || options.metric == Metric::DiffRatio {
+            self.score(&image1, &image2, &mut outcome)?;
+        }
+        if same_size && options.ssim_map {
+            outcome.ssim_map = Some(create_ssim_map(&image1, &image2)?);
+        }
+
+        let Some(mode) = options.mode else {
+            return Ok(outcome);
+        };
+        let diff = match mode {
+            DiffMode::MarkWithColor => {
+                mark_changes(&image1, &image2, options.color, options.tolerance)?
+            }
+            DiffMode::Blend => blend_images_in_color_space(
+                image1.clone(),
+                image2.clone(),
+                options.blend_mode,
+                options.onion_skin,
+                options.color_space,
+            )?,
+            DiffMode::Moves => {
+                let detection = detect_moves(&image1, &image2, options.max_move)?;
+                let image = draw_moves(&image2, &detection, options.color);
+                outcome.moves = Some(detection);
+                image
+            }
+            DiffMode::Hash => {
+                outcome.hashes = Some((
+                    compute_hash(&image1, options.hash_algorithm),
+                    compute_hash(&image2, options.hash_algorithm),
+                ));
+                return Ok(outcome);
+            }
+            DiffMode::LCS => {
+                let output = lcs_diff(
+                    &mut image1,
+                    &mut image2,
+                    options.rate,
+                    options.added_color,
+                    options.removed_color,
+                    options.row_tolerance,
+                    options.row_algorithm,
+                )?;
+                let side_by_side = (options.side_by_side
+                    || options.layout == OutputLayout::SideBySide)
+                    .then(|| output.side_by_side(&image1, &image2));
+                outcome.image = match options.layout {
+                    OutputLayout::Diff => Some(output.diff_image),
+                    OutputLayout::SideBySide => side_by_side.clone(),
+                };
+                if options.side_by_side {
+                    outcome.side_by_side = side_by_side;
+                }
+                outcome.marked = Some((image1, image2));
+                return Ok(outcome);
+            }
+        };
+
+        outcome.image = Some(match options.layout {
+            OutputLayout::Diff => diff,
+            OutputLayout::SideBySide => side_by_side(&[&image1, &diff, &image2]),
+        });
+        Ok(outcome)
+    }
+
+    fn score(
+        &self,
+        image1: &DynamicImage,
+        image2: &DynamicImage,
+        outcome: &mut DiffOutcome,
+    ) -> Result<(), String> {
+        let metric = self.options.metric;
+        outcome.score = Some(match metric {
+            Metric::Mse | Metric::Rmse | Metric::Psnr | Metric::MaxAbsError => {
+                let errors = calculate_error_metrics(image1, image2)?;
+                outcome.errors = Some(errors);
+                errors.overall.value(metric).unwrap()
+            }
+            // Weights apply to the differences of the sRGB channels
+            Metric::DiffRatio
+                if matches!(self.options.channels, Channels::Weighted(_))
+                    && self.options.color_space == ColorSpace::Srgb =>
+            {
+                calculate_diff_ratio_with_channels(image1, image2, self.options.channels)
+            }
+            Metric::DiffRatio => {
+                calculate_diff_ratio_in_color_space(image1, image2, self.options.color_space)
+            }
+            _ => calculate_score(image1, image2, metric)?,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageBuffer;
+
+    fn image_with_square(value: u8) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(8, 8, |x, y| {
+            if (2..4).contains(&x) && (2..4).contains(&y) {
+                Rgba([value, value, value, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_score_only() {
+        let outcome = Differ::new(DiffOptions::new().metric(Metric::Psnr))
+            .compare(&image_with_square(255), &image_with_square(255))
+            .unwrap();
+
+        assert_eq!(outcome.score, Some(f64::INFINITY));
+        assert!(outcome.errors.is_some());
+        assert!(outcome.image.is_none());
+    }
+
+    #[test]
+    fn test_images_of_different_sizes() {
+        let smaller = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 8, Rgba([0, 0, 0, 255])));
+        let compare =
+            |options: DiffOptions| Differ::new(options).compare(&image_with_square(255), &smaller);
+
+        let ratio = compare(DiffOptions::new()).unwrap();
+        let psnr = compare(DiffOptions::new().metric(Metric::Psnr)).unwrap();
+
+        assert!(ratio.score.unwrap() > 0.0);
+        assert_eq!(psnr.score, None);
+        assert!(compare(DiffOptions::new().mode(DiffMode::MarkWithColor)).is_err());
+        assert!(compare(
+            DiffOptions::new()
+                .mode(DiffMode::MarkWithColor)
+                .tolerance(8)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_mark_with_tolerance_and_masks() {
+        let (image1, image2) = (image_with_square(100), image_with_square(104));
+        let red = Rgba([255, 0, 0, 255]);
+        let differ = |options: DiffOptions| {
+            Differ::new(options.mode(DiffMode::MarkWithColor).color(red))
+                .compare(&image1, &image2)
+                .unwrap()
+        };
+
+        let exact = differ(DiffOptions::new());
+        let tolerant = differ(DiffOptions::new().tolerance(4));
+        let masked = differ(DiffOptions::new().mask(Region {
+            x: 2,
+            y: 2,
+            width: 2,
+            height: 2,
+        }));
+
+        assert_eq!(exact.image.unwrap().get_pixel(2, 2), red);
+        assert_eq!(
+            tolerant.image.unwrap().get_pixel(2, 2),
+            Rgba([100, 100, 100, 255])
+        );
+        assert_eq!(masked.score, Some(0.0));
+        assert!(exact.score.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_side_by_side_layout() {
+        let outcome = Differ::new(
+            DiffOptions::new()
+                .mode(DiffMode::Blend)
+                .layout(OutputLayout::SideBySide),
+        )
+        .compare(&image_with_square(10), &image_with_square(20))
+        .unwrap();
+
+        assert_eq!(outcome.image.unwrap().dimensions(), (24, 8));
+    }
+
+    #[test]
+    fn test_lcs_and_hash_outcomes() {
+        let image1 = image_with_square(255);
+        let image2 = DynamicImage::ImageRgba8(image1.to_rgba8().view(0, 0, 8, 6).to_image());
+
+        let lcs = Differ::new(DiffOptions::new().mode(DiffMode::LCS))
+            .compare(&image1, &image2)
+            .unwrap();
+        let lcs_side_by_side =
+            Differ::new(DiffOptions::new().mode(DiffMode::LCS).side_by_side(true))
+                .compare(&image1, &image2)
+                .unwrap();
+        let hash = Differ::new(DiffOptions::new().mode(DiffMode::Hash))
+            .compare(&image1, &image1)
+            .unwrap();
+
+        // the diff ratio is taken of images of different sizes too
+        assert!(lcs.score.is_some());
+        assert!(lcs.image.is_some() && lcs.marked.is_some() && lcs.side_by_side.is_none());
+        assert!(lcs_side_by_side.side_by_side.is_some());
+        let (hash1, hash2) = hash.hashes.unwrap();
+        assert_eq!(hash1.distance(&hash2), 0);
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs;
+use std::io::{Cursor, Write};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::{ColorType, DynamicImage, ImageFormat};
+
+/// How images are encoded when they are saved. The defaults are the
+/// encoder defaults of the `image` crate. WebP is always saved lossless,
+/// the `image` crate has no lossy WebP encoder.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EncodeOptions {
+    /// Format to save in, None for the format of the file extension
+    pub format: Option<ImageFormat>,
+    pub png_compression: CompressionType,
+    pub png_filter: PngFilterType,
+    /// JPEG quality from 1 to 100
+    pub jpeg_quality: u8,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            format: None,
+            png_compression: CompressionType::default(),
+            png_filter: PngFilterType::default(),
+            jpeg_quality: 75,
+        }
+    }
+}
+
+/// Encode an image as `format` into `writer`
+pub fn encode_image<W: Write>(
+    image: &DynamicImage,
+    format: ImageFormat,
+    options: &EncodeOptions,
+    mut writer: W,
+) -> Result<(), String> {
+    let encoded = match format {
+        ImageFormat::Png => image.write_with_encoder(PngEncoder::new_with_quality(
+            writer,
+            options.png_compression,
+            options.png_filter,
+        )),
+        ImageFormat::Jpeg => {
+            if !(1..=100).contains(&options.jpeg_quality) {
+                return Err(format!(
+                    "JPEG quality must be from 1 to 100, not {}",
+                    options.jpeg_quality
+                ));
+            }
+            let encoder = JpegEncoder::new_with_quality(writer, options.jpeg_quality);
+            // JPEG has no alpha channel
+            match image.color() {
+                ColorType::L8 | ColorType::Rgb8 => image.write_with_encoder(encoder),
+                _ => DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder),
+            }
+        }
+        ImageFormat::WebP => {
+            let encoder = WebPEncoder::new_lossless(writer);
+            match image.color() {
+                ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8 => {
+                    image.write_with_encoder(encoder)
+                }
+                _ => DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(encoder),
+            }
+        }
+        // Some encoders need to seek, so encode into memory first
+        format => {
+            let mut bytes = Cursor::new(Vec::new());
+            image
+                .write_to(&mut bytes, format)
+                .map_err(|err| err.to_string())?;
+            return writer
+                .write_all(bytes.get_ref())
+                .map_err(|err| err.to_string());
+        }
+    };
+    encoded.map_err(|err| err.to_string())
+}
+
+/// Save an image with the given encoder options, in the format of the file
+/// extension unless `options.format` is set
+pub fn save_image(
+    image: &DynamicImage,
+    filename: &str,
+    options: &EncodeOptions,
+) -> Result<(), String> {
+    let error = |msg: String| format!("Error saving image {}: {}", filename, msg);
+    let format = match options.format {
+        Some(format) => format,
+        None => ImageFormat::from_path(filename).map_err(|err| error(err.to_string()))?,
+    };
+
+    // encode before creating the file, so a failed encode leaves no file
+    let mut bytes = Vec::new();
+    encode_image(image, format, options, &mut bytes).map_err(error)?;
+    fs::write(filename, bytes).map_err(|err| error(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn noise() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 64, |x, y| {
+            let v = ((x * 7919 + y * 104729) % 251) as u8;
+            Rgba([v, v / 2, 255 - v, 128])
+        }))
+    }
+
+    fn encoded_size(format: ImageFormat, options: EncodeOptions) -> usize {
+        let mut bytes = Vec::new();
+        encode_image(&noise(), format, &options, &mut bytes).unwrap();
+        bytes.len()
+    }
+
+    #[test]
+    fn test_jpeg_quality() {
+        let low = encoded_size(
+            ImageFormat::Jpeg,
+            EncodeOptions {
+                jpeg_quality: 10,
+                ..Default::default()
+            },
+        );
+        let high = encoded_size(
+            ImageFormat::Jpeg,
+            EncodeOptions {
+                jpeg_quality: 95,
+                ..Default::default()
+            },
+        );
+
+        assert!(low < high);
+        let mut bytes = Vec::new();
+        let zero = EncodeOptions {
+            jpeg_quality: 0,
+            ..Default::default()
+        };
+        assert!(encode_image(&noise(), ImageFormat::Jpeg, &zero, &mut bytes).is_err());
+    }
+
+    #[test]
+    fn test_png_options_round_trip() {
+        let options = EncodeOptions {
+            png_compression: CompressionType::Best,
+            png_filter: PngFilterType::Paeth,
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+
+        encode_image(&noise(), ImageFormat::Png, &options, &mut bytes).unwrap();
+
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(decoded.to_rgba8(), noise().to_rgba8());
+    }
+
+    #[test]
+    fn test_webp_lossless() {
+        let mut bytes = Vec::new();
+        encode_image(
+            &noise(),
+            ImageFormat::WebP,
+            &EncodeOptions::default(),
+            &mut bytes,
+        )
+        .unwrap();
+
+        assert_eq!(
+            image::load_from_memory(&bytes).unwrap().to_rgba8(),
+            noise().to_rgba8()
+        );
+    }
+
+    #[test]
+    fn test_save_image_with_format() {
+        let path = std::env::temp_dir()
+            .join(format!("diff_img_{}_encoded.out", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let options = EncodeOptions {
+            format: Some(ImageFormat::Bmp),
+            ..Default::default()
+        };
+
+        save_image(&noise(), &path, &options).unwrap();
+        let format = image::ImageReader::open(&path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map(|reader| reader.format());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(format.unwrap(), Some(ImageFormat::Bmp));
+        assert!(save_image(&noise(), &path, &EncodeOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_failed_save_leaves_no_file() {
+        let path = std::env::temp_dir()
+            .join(format!("diff_img_{}_failed.jpg", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let options = EncodeOptions {
+            jpeg_quality: 0,
+            ..Default::default()
+        };
+
+        assert!(save_image(&noise(), &path, &options).is_err());
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
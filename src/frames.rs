@@ -0,0 +1,379 @@
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Read, Seek};
+use std::time::Duration;
+
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{
+    AnimationDecoder, Delay, DynamicImage, GenericImageView, GrayAlphaImage, GrayImage,
+    ImageFormat, ImageReader, RgbImage, RgbaImage,
+};
+use tiff::decoder::{Decoder as TiffDecoder, DecodingResult};
+use tiff::ColorType as TiffColorType;
+
+use crate::{DiffOptions, Differ, Metric};
+
+/// One frame of an animation or one page of a multi-page image
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub image: DynamicImage,
+    /// How long the frame is shown, zero for still images and pages
+    pub delay: Duration,
+}
+
+impl Frame {
+    /// A still image or a page, shown for no particular time
+    pub fn still(image: DynamicImage) -> Frame {
+        Frame {
+            image,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+/// Score of one pair of frames
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameScore {
+    pub index: usize,
+    pub score: f64,
+    pub delay1: Duration,
+    pub delay2: Duration,
+}
+
+/// Frame by frame comparison of two animations or multi-page images
+#[derive(Clone, Debug, PartialEq)]
+pub struct FramesComparison {
+    pub frame_count1: usize,
+    pub frame_count2: usize,
+    /// Scores of the frames both inputs have
+    pub frames: Vec<FrameScore>,
+}
+
+impl FramesComparison {
+    /// Indexes of the compared frames that are shown for a different time
+    pub fn timing_mismatches(&self) -> Vec<usize> {
+        self.frames
+            .iter()
+            .filter(|frame| frame.delay1 != frame.delay2)
+            .map(|frame| frame.index)
+            .collect()
+    }
+
+    /// Mean score of the compared frames, None if no frames were compared
+    pub fn mean_score(&self) -> Option<f64> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        Some(self.frames.iter().map(|frame| frame.score).sum::<f64>() / self.frames.len() as f64)
+    }
+
+    /// The compared frame with the worst score for `metric`
+    pub fn worst_frame(&self, metric: Metric) -> Option<&FrameScore> {
+        let worse = |a: &&FrameScore, b: &&FrameScore| {
+            if metric.higher_is_better() {
+                b.score.total_cmp(&a.score)
+            } else {
+                a.score.total_cmp(&b.score)
+            }
+        };
+        self.frames.iter().max_by(worse)
+    }
+}
+
+fn animation_frames<'a>(decoder: impl AnimationDecoder<'a>) -> Result<Vec<Frame>, String> {
+    decoder
+        .into_frames()
+        .map(|frame| {
+            let frame = frame.map_err(|err| err.to_string())?;
+            Ok(Frame {
+                delay: Duration::from(frame.delay()),
+                image: DynamicImage::ImageRgba8(frame.into_buffer()),
+            })
+        })
+        .collect()
+}
+
+fn tiff_page<R: Read + Seek>(decoder: &mut TiffDecoder<R>) -> Result<DynamicImage, String> {
+    let (width, height) = decoder.dimensions().map_err(|err| err.to_string())?;
+    let color_type = decoder.colortype().map_err(|err| err.to_string())?;
+    // 16 bit samples are reduced to their high byte
+    let samples: Vec<u8> = match decoder.read_image().map_err(|err| err.to_string())? {
+        DecodingResult::U8(samples) => samples,
+        DecodingResult::U16(samples) => samples.into_iter().map(|s| (s >> 8) as u8).collect(),
+        _ => return Err("Unsupported TIFF sample format".to_string()),
+    };
+    let invalid = || "Invalid TIFF page size".to_string();
+    match color_type {
+        TiffColorType::Gray(_) => GrayImage::from_raw(width, height, samples)
+            .map(DynamicImage::ImageLuma8)
+            .ok_or_else(invalid),
+        TiffColorType::GrayA(_) => GrayAlphaImage::from_raw(width, height, samples)
+            .map(DynamicImage::ImageLumaA8)
+            .ok_or_else(invalid),
+        TiffColorType::RGB(_) => RgbImage::from_raw(width, height, samples)
+            .map(DynamicImage::ImageRgb8)
+            .ok_or_else(invalid),
+        TiffColorType::RGBA(_) => RgbaImage::from_raw(width, height, samples)
+            .map(DynamicImage::ImageRgba8)
+            .ok_or_else(invalid),
+        other => Err(format!("Unsupported TIFF color type {:?}", other)),
+    }
+}
+
+fn tiff_pages<R: Read + Seek>(reader: R) -> Result<Vec<Frame>, String> {
+    let mut decoder = TiffDecoder::new(reader).map_err(|err| err.to_string())?;
+    let mut frames = Vec::new();
+    loop {
+        frames.push(Frame {
+            image: tiff_page(&mut decoder)?,
+            delay: Duration::ZERO,
+        });
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image().map_err(|err| err.to_string())?;
+    }
+    Ok(frames)
+}
+
+/// Decode every frame of an animated GIF, APNG or WebP, or every page of a
+/// multi-page TIFF, detecting the format from the first bytes. None for
+/// other formats and for inputs with a single frame or page, which decode
+/// as a single image.
+pub fn decode_animation(data: &[u8]) -> Result<Option<Vec<Frame>>, String> {
+    let format = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|err| err.to_string())?
+        .format();
+
+    let frames = match format {
+        Some(ImageFormat::Gif) => GifDecoder::new(Cursor::new(data))
+            .map_err(|err| err.to_string())
+            .and_then(animation_frames)?,
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(Cursor::new(data)).map_err(|err| err.to_string())?;
+            if !decoder.is_apng().map_err(|err| err.to_string())? {
+                return Ok(None);
+            }
+            decoder
+                .apng()
+                .map_err(|err| err.to_string())
+                .and_then(animation_frames)?
+        }
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(Cursor::new(data)).map_err(|err| err.to_string())?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            animation_frames(decoder)?
+        }
+        Some(ImageFormat::Tiff) => tiff_pages(Cursor::new(data))?,
+        _ => return Ok(None),
+    };
+    Ok(Some(frames).filter(|frames| frames.len() > 1))
+}
+
+/// Decode every frame of an animated GIF, APNG or WebP, or every page of a
+/// multi-page TIFF. Other images give a single frame.
+pub fn load_frames(filename: &str) -> Result<Vec<Frame>, String> {
+    let error = |msg: String| format!("Error loading frames of {}: {}", filename, msg);
+    let data = std::fs::read(filename).map_err(|err| error(err.to_string()))?;
+
+    match decode_animation(&data).map_err(error)? {
+        Some(frames) => Ok(frames),
+        None => image::open(filename)
+            .map(|image| vec![Frame::still(image)])
+            .map_err(|err| error(err.to_string())),
+    }
+}
+
+/// Score every pair of frames with `metric`. Frames only one of the inputs
+/// has are left out, compare `frame_count1` and `frame_count2` to find them.
+pub fn compare_frames(
+    frames1: &[Frame],
+    frames2: &[Frame],
+    metric: Metric,
+) -> Result<FramesComparison, String> {
+    let differ = Differ::new(DiffOptions::new().metric(metric));
+    diff_frames(frames1, frames2, &differ).map(|(comparison, _)| comparison)
+}
+
+/// Compare every pair of frames with `differ`, like `compare_frames`. Also
+/// returns the diff image of each pair, shown as long as the frame of the
+/// first input, when the mode of the differ draws one.
+pub fn diff_frames(
+    frames1: &[Frame],
+    frames2: &[Frame],
+    differ: &Differ,
+) -> Result<(FramesComparison, Vec<Frame>), String> {
+    let mut frames = Vec::new();
+    let mut diffs = Vec::new();
+    for (index, (frame1, frame2)) in frames1.iter().zip(frames2).enumerate() {
+        let outcome = differ
+            .compare(&frame1.image, &frame2.image)
+            .map_err(|err| format!("Frame {}: {}", index, err))?;
+        let Some(score) = outcome.score else {
+            return Err(format!(
+                "Frame {} has different dimensions, {:?} and {:?}",
+                index,
+                frame1.image.dimensions(),
+                frame2.image.dimensions()
+            ));
+        };
+        frames.push(FrameScore {
+            index,
+            score,
+            delay1: frame1.delay,
+            delay2: frame2.delay,
+        });
+        if let Some(image) = outcome.image {
+            diffs.push(Frame {
+                image,
+                delay: frame1.delay,
+            });
+        }
+    }
+
+    let comparison = FramesComparison {
+        frame_count1: frames1.len(),
+        frame_count2: frames2.len(),
+        frames,
+    };
+    Ok((comparison, diffs))
+}
+
+/// Save frames as an animated GIF that loops forever
+pub fn save_animation(frames: &[Frame], filename: &str) -> Result<(), String> {
+    let error = |msg: String| format!("Error saving animation {}: {}", filename, msg);
+    let file = File::create(filename).map_err(|err| error(err.to_string()))?;
+
+    let mut encoder = GifEncoder::new(BufWriter::new(file));
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(|err| error(err.to_string()))?;
+    encoder
+        .encode_frames(frames.iter().map(|frame| {
+            image::Frame::from_parts(
+                frame.image.to_rgba8(),
+                0,
+                0,
+                Delay::from_saturating_duration(frame.delay),
+            )
+        }))
+        .map_err(|err| error(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffMode;
+    use image::Rgba;
+
+    fn frame(value: u8, delay_ms: u64) -> Frame {
+        Frame {
+            image: DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 6, Rgba([value, 0, 0, 255]))),
+            delay: Duration::from_millis(delay_ms),
+        }
+    }
+
+    fn temp_file(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("diff_img_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn test_compare_frames() {
+        let frames1 = [frame(0, 100), frame(100, 100), frame(200, 100)];
+        let frames2 = [frame(0, 100), frame(50, 200)];
+
+        let comparison = compare_frames(&frames1, &frames2, Metric::DiffRatio).unwrap();
+
+        assert_eq!((comparison.frame_count1, comparison.frame_count2), (3, 2));
+        assert_eq!(comparison.frames.len(), 2);
+        assert_eq!(comparison.frames[0].score, 0.0);
+        assert!(comparison.frames[1].score > 0.0);
+        assert_eq!(comparison.timing_mismatches(), vec![1]);
+        assert_eq!(comparison.worst_frame(Metric::DiffRatio).unwrap().index, 1);
+        assert_eq!(
+            comparison.mean_score(),
+            Some(comparison.frames[1].score / 2.0)
+        );
+    }
+
+    #[test]
+    fn test_diff_frames() {
+        let frames1 = [frame(0, 100), frame(100, 40)];
+        let frames2 = [frame(0, 100), frame(50, 40)];
+        let differ = Differ::new(
+            DiffOptions::new()
+                .mode(DiffMode::MarkWithColor)
+                .color(Rgba([0, 255, 0, 255])),
+        );
+
+        let (comparison, diffs) = diff_frames(&frames1, &frames2, &differ).unwrap();
+
+        assert_eq!(comparison.frames.len(), 2);
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[1].delay, Duration::from_millis(40));
+        assert_eq!(diffs[0].image.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(diffs[1].image.get_pixel(0, 0), Rgba([0, 255, 0, 255]));
+    }
+
+    #[test]
+    fn test_gif_round_trip() {
+        let path = temp_file("frames.gif");
+        let frames = [frame(0, 100), frame(255, 250)];
+
+        save_animation(&frames, &path).unwrap();
+        let loaded = load_frames(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[1].delay, Duration::from_millis(250));
+        assert_eq!(loaded[1].image.get_pixel(3, 3), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_load_tiff_pages() {
+        let path = temp_file("pages.tiff");
+        {
+            let file = File::create(&path).unwrap();
+            let mut encoder = tiff::encoder::TiffEncoder::new(BufWriter::new(file)).unwrap();
+            for value in [10u8, 20, 30] {
+                encoder
+                    .write_image::<tiff::encoder::colortype::Gray8>(4, 2, &[value; 8])
+                    .unwrap();
+            }
+        }
+
+        let pages = load_frames(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[2].image.get_pixel(0, 0), Rgba([30, 30, 30, 255]));
+    }
+
+    #[test]
+    fn test_decode_animation_from_memory() {
+        let path = temp_file("memory.gif");
+        save_animation(&[frame(0, 100), frame(255, 100)], &path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let still = std::fs::read("tests/images/image2.png").unwrap();
+
+        assert_eq!(decode_animation(&data).unwrap().unwrap().len(), 2);
+        assert!(decode_animation(&still).unwrap().is_none());
+        assert!(decode_animation(b"not an image").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_load_still_image() {
+        let frames = load_frames("tests/images/image2.png").unwrap();
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].delay, Duration::ZERO);
+    }
+}
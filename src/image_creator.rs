@@ -1,13 +1,15 @@
 use std::cmp;
 
-use base64::decode;
-use image::{DynamicImage, GenericImage, ImageBuffer, Rgba};
+use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, Rgba};
+
+use crate::diff::CompareRow;
 
 pub static BLACK: (u8, u8, u8) = (0, 0, 0);
-pub static RED: (u8, u8, u8) = (255, 119, 119);
-pub static GREEN: (u8, u8, u8) = (99, 195, 99);
 
-fn compute_range(r: &Vec<usize>) -> Vec<(usize, usize)> {
+// Transparent columns between the two halves of a side by side image
+const SIDE_BY_SIDE_GAP: u32 = 8;
+
+fn compute_range(r: &[usize]) -> Vec<(usize, usize)> {
     let mut i = 0;
     let mut j = 0;
     let mut acc: usize;
@@ -52,41 +54,33 @@ where
 }
 
 fn blend(base: Rgba<u8>, rgb: (u8, u8, u8), rate: f32) -> Rgba<u8> {
-    return Rgba([
+    Rgba([
         (base.0[0] as f32 * (1.0 - rate) + rgb.0 as f32 * (rate)) as u8,
         (base.0[1] as f32 * (1.0 - rate) + rgb.1 as f32 * (rate)) as u8,
         (base.0[2] as f32 * (1.0 - rate) + rgb.2 as f32 * (rate)) as u8,
         base.0[3],
-    ]);
+    ])
 }
 
 fn put_diff_pixels(
     y: usize,
     img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
-    row_width: u32,
-    data: &String,
+    row: &[u8],
     rgb: (u8, u8, u8),
     rate: f32,
-) -> Result<(), base64::DecodeError> {
-    let row = decode(data)?;
+) {
     for x in 0..img.dimensions().0 {
         let index = x as usize * 4;
-        let pixel: Rgba<u8> = if row_width > x {
+        let pixel: Rgba<u8> = if index < row.len() {
             Rgba([row[index], row[index + 1], row[index + 2], row[index + 3]])
         } else {
             Rgba([0, 0, 0, 0])
         };
-        img.put_pixel(x as u32, y as u32, blend(pixel, rgb, rate));
+        img.put_pixel(x, y as u32, blend(pixel, rgb, rate));
     }
-    Ok(())
 }
 
-pub fn mark_org_image(
-    base: &mut DynamicImage,
-    color: (u8, u8, u8),
-    rate: f32,
-    indexes: &Vec<usize>,
-) {
+pub fn mark_org_image(base: &mut DynamicImage, color: (u8, u8, u8), rate: f32, indexes: &[usize]) {
     let range = compute_range(indexes);
     blend_diff_area(base, range, color, rate);
 }
@@ -94,24 +88,62 @@ pub fn mark_org_image(
 pub fn get_diff_image(
     before_width: u32,
     after_width: u32,
-    result: &Vec<lcs_diff::DiffResult<String>>,
+    result: &[lcs_diff::DiffResult<CompareRow>],
+    added_color: (u8, u8, u8),
+    removed_color: (u8, u8, u8),
     rate: f32,
-) -> Result<DynamicImage, base64::DecodeError> {
+) -> DynamicImage {
     let height = result.len() as u32;
     let width = cmp::max(before_width, after_width);
     let mut img: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::new(width, height);
     for (y, d) in result.iter().enumerate() {
         match d {
-            &lcs_diff::DiffResult::Added(ref a) => {
-                put_diff_pixels(y, &mut img, after_width, &a.data, GREEN, rate)?
+            lcs_diff::DiffResult::Added(a) => {
+                put_diff_pixels(y, &mut img, a.data.pixels, added_color, rate)
             }
-            &lcs_diff::DiffResult::Removed(ref r) => {
-                put_diff_pixels(y, &mut img, before_width, &r.data, RED, rate)?
+            lcs_diff::DiffResult::Removed(r) => {
+                put_diff_pixels(y, &mut img, r.data.pixels, removed_color, rate)
             }
-            &lcs_diff::DiffResult::Common(ref c) => {
-                put_diff_pixels(y, &mut img, width, &c.data, BLACK, 0.0)?
+            lcs_diff::DiffResult::Common(c) => {
+                put_diff_pixels(y, &mut img, c.data.pixels, BLACK, 0.0)
             }
         }
     }
-    Ok(DynamicImage::ImageRgba8(img))
+    DynamicImage::ImageRgba8(img)
+}
+
+fn copy_row(
+    src: &DynamicImage,
+    src_y: usize,
+    dest: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    dest_x: u32,
+    dest_y: usize,
+) {
+    for x in 0..src.dimensions().0 {
+        dest.put_pixel(dest_x + x, dest_y as u32, src.get_pixel(x, src_y as u32));
+    }
+}
+
+// Place the (marked) before and after images next to each other, padding
+// with empty rows wherever the other side has an added or removed row so
+// that common rows line up. `rows` holds the before and after row index of
+// every row of the diff.
+pub fn get_side_by_side_image(
+    before: &DynamicImage,
+    after: &DynamicImage,
+    rows: &[(Option<usize>, Option<usize>)],
+) -> DynamicImage {
+    let height = rows.len() as u32;
+    let after_x = before.dimensions().0 + SIDE_BY_SIDE_GAP;
+    let width = after_x + after.dimensions().0;
+    let mut img: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    for (y, &(old_index, new_index)) in rows.iter().enumerate() {
+        if let Some(old_index) = old_index {
+            copy_row(before, old_index, &mut img, 0, y);
+        }
+        if let Some(new_index) = new_index {
+            copy_row(after, new_index, &mut img, after_x, y);
+        }
+    }
+    DynamicImage::ImageRgba8(img)
 }
@@ -1,11 +1,37 @@
-use clap::{Arg, Command};
-use config::{DiffMode, BLEND_MODES, DIFF_MODES};
-use diff_img::{calculate_diff_ratio, highlight_changes_with_color, lcs_diff};
+use clap::{Arg, ArgAction, Command};
+use config::{
+    DiffMode, BLEND_MODES, CHANNELS, COLOR_SPACES, DIFF_MODES, HASH_ALGORITHMS, LAYOUTS, METRICS,
+    NORMALIZATIONS, OUTPUT_FORMATS, PNG_COMPRESSIONS, PNG_FILTERS, RAW_FORMATS, RESIZE_FILTERS,
+    ROW_DIFF_ALGORITHMS,
+};
+use diff_img::{
+    diff_frames, diff_sequences, save_animation, ChannelErrors, ChannelMapping, Differ, Metric,
+    SequenceFrame,
+};
+use image::DynamicImage;
+use std::path::Path;
+use std::process::exit;
 
 pub mod config;
 pub mod utils;
 
-static RATE: f32 = 100.0 / 256.0;
+// Text output goes to stderr while an image is written to stdout, so it does
+// not end up in the image
+macro_rules! report {
+    ($to_stderr:expr, $($arg:tt)*) => {
+        if $to_stderr {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+// Report an error the way load errors are reported and stop
+fn exit_with_error(to_stderr: bool, msg: &str) -> ! {
+    report!(to_stderr, "Error: {}", msg);
+    exit(1);
+}
 
 fn main() {
     let matches = Command::new("diffimg")
@@ -13,19 +39,77 @@ fn main() {
         .about("Diff images")
         .arg(
             Arg::new("image1")
-                .help("First image to diff")
+                .help("First image to diff, - reads it from stdin")
                 .required(true),
         )
         .arg(
             Arg::new("image2")
-                .help("Second image to diff")
+                .help("Second image to diff, - reads it from stdin")
                 .required(true),
         )
         .arg(
             Arg::new("filename")
                 .short('f')
                 .long("filename")
-                .help("If present, save a diff image to this filename."),
+                .help("If present, save a diff image to this filename. - writes it to stdout"),
+        )
+        .arg(
+            Arg::new("raw-size")
+                .long("raw-size")
+                .help("Read inputs of no known image format as raw pixels of this size, e.g. 1920x1080"),
+        )
+        .arg(
+            Arg::new("raw-format")
+                .long("raw-format")
+                .value_parser(RAW_FORMATS)
+                .default_value(RAW_FORMATS[0])
+                .help("Pixel format of raw inputs"),
+        )
+        .arg(
+            Arg::new("raw-stride")
+                .long("raw-stride")
+                .help("Bytes per row of raw inputs, if the rows are padded"),
+        )
+        .arg(
+            Arg::new("svg-scale")
+                .long("svg-scale")
+                .default_value("1")
+                .help("Rasterize SVG inputs at this multiple of their declared size (needs the svg feature)"),
+        )
+        .arg(
+            Arg::new("svg-size")
+                .long("svg-size")
+                .help("Rasterize SVG inputs at exactly this size, e.g. 64x64, instead of --svg-scale"),
+        )
+        .arg(
+            Arg::new("pdf-dpi")
+                .long("pdf-dpi")
+                .default_value("72")
+                .help("Rasterize PDF pages at this many pixels per inch (needs the pdf feature)"),
+        )
+        .arg(
+            Arg::new("output-format")
+                .long("output-format")
+                .value_parser(OUTPUT_FORMATS)
+                .help("Format of the saved images instead of the one of the file extension, needed when writing to stdout"),
+        )
+        .arg(
+            Arg::new("png-compression")
+                .long("png-compression")
+                .value_parser(PNG_COMPRESSIONS)
+                .help("Compression level of saved PNG images (default: fast)"),
+        )
+        .arg(
+            Arg::new("png-filter")
+                .long("png-filter")
+                .value_parser(PNG_FILTERS)
+                .help("Row filter of saved PNG images (default: adaptive)"),
+        )
+        .arg(
+            Arg::new("jpeg-quality")
+                .long("jpeg-quality")
+                .default_value("75")
+                .help("Quality of saved JPEG images, from 1 to 100"),
         )
         .arg(
             Arg::new("mode")
@@ -35,12 +119,129 @@ fn main() {
                 .help("diff mode")
                 .required(false),
         )
+        .arg(
+            Arg::new("frames")
+                .long("frames")
+                .help("Compare every frame of animated GIF/APNG/WebP images or every page of multi-page TIFFs. With solid-color or blend mode, save a diff per frame, or an animated diff if the filename ends in .gif")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sequence")
+                .long("sequence")
+                .help("Treat image1 and image2 as glob patterns of numbered image sequences (e.g. \"out/frame_*.png\") and compare them frame by frame")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("timeline")
+                .long("timeline")
+                .help("sequence mode: if present, save the per-frame scores to this .csv or .json file"),
+        )
+        .arg(
+            Arg::new("threshold")
+                .long("threshold")
+                .help("sequence mode: flag frames whose --metric score is worse than this, by default any frame that differs"),
+        )
+        .arg(
+            Arg::new("align")
+                .long("align")
+                .help("If present, detect a translation of up to this many pixels between the images and compare only their aligned overlap"),
+        )
+        .arg(
+            Arg::new("resize")
+                .long("resize")
+                .help("If present, resample image2 to the size of image1 with this filter before comparing")
+                .value_parser(RESIZE_FILTERS),
+        )
+        .arg(
+            Arg::new("scale-invariant")
+                .long("scale-invariant")
+                .help("Detect the scale between the images (e.g. a 2x retina render), resample image2 to the resolution of image1 and compare their common area")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("prefilter")
+                .long("prefilter")
+                .help("If present, run both images through these filters before comparing, in order, e.g. median:1,blur:2,downsample:2"),
+        )
+        .arg(
+            Arg::new("prefiltered-before")
+                .long("prefiltered-before")
+                .help("If present, save image1 after the prefilters to this filename"),
+        )
+        .arg(
+            Arg::new("prefiltered-after")
+                .long("prefiltered-after")
+                .help("If present, save image2 after the prefilters to this filename"),
+        )
+        .arg(
+            Arg::new("channels")
+                .long("channels")
+                .help(format!("Compare only these channels in every mode: one of {} or red, green, blue and alpha weights like [1,1,0,0]", CHANNELS.join(", ")))
+                .default_value(CHANNELS[0]),
+        )
+        .arg(
+            Arg::new("color-space")
+                .long("color-space")
+                .help("Color space the diff ratio and blend mode work in, linear blends light instead of encoded values")
+                .default_value(COLOR_SPACES[0])
+                .value_parser(COLOR_SPACES),
+        )
+        .arg(
+            Arg::new("normalize")
+                .long("normalize")
+                .help("If present, normalize the colors of both images this way before comparing, so global color or brightness shifts are ignored")
+                .value_parser(NORMALIZATIONS),
+        )
+        .arg(
+            Arg::new("metric")
+                .long("metric")
+                .help("Score printed when no diff mode is given")
+                .default_value(METRICS[0])
+                .value_parser(METRICS),
+        )
+        .arg(
+            Arg::new("ssim-map")
+                .long("ssim-map")
+                .help("If present and no diff mode is given, save the per-pixel SSIM map to this filename"),
+        )
+        .arg(
+            Arg::new("max-move")
+                .long("max-move")
+                .help("moves mode: how far in pixels a changed block is searched for in image2")
+                .default_value("64"),
+        )
+        .arg(
+            Arg::new("hash")
+                .long("hash")
+                .help("hash mode: perceptual hash algorithm")
+                .default_value(HASH_ALGORITHMS[2])
+                .value_parser(HASH_ALGORITHMS),
+        )
         .arg(
             Arg::new("color")
                 .long("color")
                 .short('c')
                 .default_value("[0,255,0,0]"),
         )
+        .arg(
+            Arg::new("tolerance")
+                .long("tolerance")
+                .default_value("0")
+                .help("solid-color mode: leave pixels unmarked when no channel differs by more than this"),
+        )
+        .arg(
+            Arg::new("mask")
+                .long("mask")
+                .action(ArgAction::Append)
+                .help("Leave a region out of the comparison, as <x>,<y>,<width>x<height>. Can be given more than once"),
+        )
+        .arg(
+            Arg::new("layout")
+                .long("layout")
+                .value_parser(LAYOUTS)
+                .default_value(LAYOUTS[0])
+                .help("Save the diff image on its own or next to both images"),
+        )
         .arg(
             Arg::new("blend")
                 .long("blend")
@@ -48,39 +249,396 @@ fn main() {
                 .default_value(BLEND_MODES[1])
                 .value_parser(BLEND_MODES),
         )
+        .arg(
+            Arg::new("before-opacity")
+                .long("before-opacity")
+                .help("onion-skin blend: opacity of image1, from 0 to 1")
+                .default_value("1.0"),
+        )
+        .arg(
+            Arg::new("after-opacity")
+                .long("after-opacity")
+                .help("onion-skin blend: opacity of image2, from 0 to 1")
+                .default_value("1.0"),
+        )
+        .arg(
+            Arg::new("before-tint")
+                .long("before-tint")
+                .help("onion-skin blend: tint color of image1")
+                .default_value("[255,0,0]"),
+        )
+        .arg(
+            Arg::new("after-tint")
+                .long("after-tint")
+                .help("onion-skin blend: tint color of image2")
+                .default_value("[0,255,255]"),
+        )
+        .arg(
+            Arg::new("added-color")
+                .long("added-color")
+                .help("LCS mode: color blended into rows only present in image2")
+                .default_value("[99,195,99,255]"),
+        )
+        .arg(
+            Arg::new("removed-color")
+                .long("removed-color")
+                .help("LCS mode: color blended into rows only present in image1")
+                .default_value("[255,119,119,255]"),
+        )
+        .arg(
+            Arg::new("rate")
+                .long("rate")
+                .help("LCS mode: how strongly the added/removed colors are blended in, from 0 to 1")
+                .default_value("0.390625"),
+        )
+        .arg(
+            Arg::new("row-tolerance")
+                .long("row-tolerance")
+                .help("LCS mode: rows still match when at most this many pixels (e.g. 3) or this percentage of the row (e.g. 2%) differ")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("row-algorithm")
+                .long("row-algorithm")
+                .help("LCS mode: sequence diff used to line up rows, myers or patience scale to very tall images")
+                .default_value(ROW_DIFF_ALGORITHMS[0])
+                .value_parser(ROW_DIFF_ALGORITHMS),
+        )
+        .arg(
+            Arg::new("before-output")
+                .long("before-output")
+                .help("LCS mode: if present, save image1 with its removed rows marked to this filename"),
+        )
+        .arg(
+            Arg::new("after-output")
+                .long("after-output")
+                .help("LCS mode: if present, save image2 with its added rows marked to this filename"),
+        )
+        .arg(
+            Arg::new("side-by-side")
+                .long("side-by-side")
+                .help("LCS mode: if present, save the marked images next to each other with common rows aligned to this filename"),
+        )
         .get_matches();
 
-    let mut config = config::Config::from_clap_matches(&matches);
+    let config = config::Config::from_clap_matches(&matches);
+    let to_stderr = config.writes_to_stdout();
 
-    let mode = config.mode;
-    let file_name: Option<&str> = config.filename.map(|s| s.as_str());
+    if config.sequence {
+        compare_sequence(&config);
+        return;
+    }
+    if config.frames {
+        compare_all_frames(&config);
+        return;
+    }
 
-    if mode.is_none() {
-        println!("Diff ratio {}", calculate_diff_ratio(config.image1.clone(), config.image2.clone()))
-    } else {
-        let _s: Result<String, _> = match mode.unwrap() {
-            DiffMode::MarkWithColor => {
-                match highlight_changes_with_color(config.image1, config.image2, config.color) {
-                    Ok(img) => utils::safe_save_image(img, file_name.unwrap()),
-                    Err(msg) => {
-                        panic!("{}", msg);
-                    }
-                }
+    // In frames and sequence mode the inputs are loaded frame by frame
+    let load = |path: &str| match config.load_image(path) {
+        Ok(image) => image,
+        Err(msg) => exit_with_error(to_stderr, &msg),
+    };
+    let (image1, image2) = (load(config.image1_path), load(config.image2_path));
+
+    let differ = Differ::new(config.diff_options());
+    let outcome = match differ.compare(&image1, &image2) {
+        Ok(outcome) => outcome,
+        Err(msg) => exit_with_error(to_stderr, &msg),
+    };
+    let save = |image: DynamicImage, path: &str| {
+        if let Err(msg) = utils::safe_save_image(image, path, &config.encode_options) {
+            report!(to_stderr, "Error: {}", msg);
+        }
+    };
+
+    if let Some(scale) = outcome.scale {
+        report!(to_stderr, "Scale {}", scale);
+    }
+    if let Some(offset) = outcome.offset {
+        report!(to_stderr, "Offset {} {}", offset.0, offset.1);
+    }
+    if let Some((image1, image2)) = outcome.prefiltered {
+        let prefiltered_outputs = [
+            (image1, config.prefiltered_before_output),
+            (image2, config.prefiltered_after_output),
+        ];
+        for (img, path) in prefiltered_outputs {
+            if let Some(path) = path {
+                save(img, path);
             }
-            DiffMode::LCS => match crate::lcs_diff(&mut config.image1, &mut config.image2, RATE) {
-                Ok(img) => utils::safe_save_image(img, file_name.unwrap()),
-                Err(msg) => {
-                    panic!("{}", msg);
+        }
+    }
+    if let Some((image1_mapping, image2_mapping)) = outcome.normalization {
+        let describe = |mapping: [ChannelMapping; 3]| {
+            ["R", "G", "B"]
+                .iter()
+                .zip(mapping)
+                .map(|(name, m)| format!("{} x{:.3} {:+.1}", name, m.gain, m.offset))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        report!(to_stderr, "Normalized image1 {}", describe(image1_mapping));
+        report!(to_stderr, "Normalized image2 {}", describe(image2_mapping));
+    }
+
+    let Some(mode) = config.mode else {
+        let label = metric_label(config.metric);
+        let Some(score) = outcome.score else {
+            let msg = format!(
+                "Images must have the same dimensions to be scored with {}",
+                label
+            );
+            exit_with_error(to_stderr, &msg);
+        };
+        match outcome.errors {
+            Some(errors) => {
+                let value = |channel: ChannelErrors| channel.value(config.metric).unwrap();
+                report!(
+                    to_stderr,
+                    "{} {} (R {}, G {}, B {})",
+                    label,
+                    score,
+                    value(errors.red),
+                    value(errors.green),
+                    value(errors.blue)
+                )
+            }
+            None => report!(to_stderr, "{} {}", label, score),
+        }
+        if let (Some(map), Some(path)) = (outcome.ssim_map, config.ssim_map_output) {
+            save(map, path);
+        }
+        return;
+    };
+
+    match mode {
+        DiffMode::Moves => {
+            let detection = outcome.moves.as_ref().unwrap();
+            for block in &detection.moves {
+                let from = block.from;
+                report!(
+                    to_stderr,
+                    "Moved {}x{} at {},{} by {},{}",
+                    from.width,
+                    from.height,
+                    from.x,
+                    from.y,
+                    block.vector.0,
+                    block.vector.1
+                );
+            }
+            for region in &detection.changed {
+                report!(
+                    to_stderr,
+                    "Changed {}x{} at {},{}",
+                    region.width,
+                    region.height,
+                    region.x,
+                    region.y
+                );
+            }
+        }
+        DiffMode::Hash => {
+            let (hash1, hash2) = outcome.hashes.unwrap();
+            report!(to_stderr, "image1 {}", hash1);
+            report!(to_stderr, "image2 {}", hash2);
+            report!(to_stderr, "Hamming distance {}", hash1.distance(&hash2));
+        }
+        DiffMode::LCS => {
+            let (before, after) = outcome.marked.unwrap();
+            let extra_outputs = [
+                (Some(before), config.before_output),
+                (Some(after), config.after_output),
+                (outcome.side_by_side, config.side_by_side_output),
+            ];
+            for (img, path) in extra_outputs {
+                if let (Some(img), Some(path)) = (img, path) {
+                    save(img, path);
                 }
-            },
-            DiffMode::Blend => {
-                let img =
-                    diff_img::blend_images(config.image1, config.image2, config.blend_mode).unwrap();
-    
-                utils::safe_save_image(img, file_name.unwrap())
             }
+        }
+        DiffMode::MarkWithColor | DiffMode::Blend => {}
+    }
+
+    if let Some(image) = outcome.image {
+        save(image, config.filename.unwrap());
+    }
+}
+
+fn metric_label(metric: Metric) -> &'static str {
+    match metric {
+        Metric::DiffRatio => "Diff ratio",
+        Metric::Ssim => "SSIM",
+        Metric::MsSsim => "MS-SSIM",
+        Metric::Mse => "MSE",
+        Metric::Rmse => "RMSE",
+        Metric::Psnr => "PSNR",
+        Metric::MaxAbsError => "Max abs error",
+    }
+}
+
+// Compare two numbered image sequences frame by frame
+fn compare_sequence(config: &config::Config) {
+    let to_stderr = config.writes_to_stdout();
+    // diffs are only drawn to be saved
+    let mut options = config.diff_options();
+    if config.filename.is_none() {
+        options.mode = None;
+    }
+    let differ = Differ::new(options);
+    let load = |path: &Path| config.load_image(&path.to_string_lossy());
+    // failed frames have no diff to save, save errors are reported with the
+    // frames
+    let mut save_errors = Vec::new();
+    let save = |frame: &SequenceFrame, image: DynamicImage| {
+        let Some(filename) = config.filename else {
+            return;
+        };
+        let saved = utils::safe_save_image(
+            image,
+            &utils::frame_filename(filename, frame.index as usize),
+            &config.encode_options,
+        );
+        if let Err(msg) = saved {
+            save_errors.push(msg);
+        }
+    };
+    let comparison = match diff_sequences(
+        config.image1_path,
+        config.image2_path,
+        &differ,
+        config.threshold,
+        load,
+        save,
+    ) {
+        Ok(comparison) => comparison,
+        Err(msg) => exit_with_error(to_stderr, &msg),
+    };
+
+    report!(
+        to_stderr,
+        "Frames {} {}",
+        comparison.frame_count1,
+        comparison.frame_count2
+    );
+    for index in &comparison.missing {
+        report!(to_stderr, "Frame {} missing", index);
+    }
+    for frame in &comparison.frames {
+        match (frame.score, &frame.error) {
+            (Some(score), _) => report!(
+                to_stderr,
+                "Frame {} {} {}{}",
+                frame.index,
+                metric_label(config.metric),
+                score,
+                if frame.flagged { " flagged" } else { "" }
+            ),
+            (None, error) => report!(
+                to_stderr,
+                "Frame {} failed: {}",
+                frame.index,
+                error.as_deref().unwrap_or("")
+            ),
+        }
+    }
+    for msg in &save_errors {
+        report!(to_stderr, "Error: {}", msg);
+    }
+
+    if let Some(path) = config.timeline_output {
+        let timeline = if path.to_lowercase().ends_with(".json") {
+            comparison.to_json()
+        } else {
+            comparison.to_csv()
         };
+        if let Err(err) = std::fs::write(path, timeline) {
+            exit_with_error(
+                to_stderr,
+                &format!("Error writing timeline {}: {}", path, err),
+            );
+        }
+    }
+}
+
+// Compare two animations or multi-page images frame by frame
+fn compare_all_frames(config: &config::Config) {
+    let to_stderr = config.writes_to_stdout();
+    let load = |path: &str| match config.load_pages(path) {
+        Ok(frames) => frames,
+        Err(msg) => exit_with_error(to_stderr, &msg),
+    };
+    let frames1 = load(config.image1_path);
+    let frames2 = load(config.image2_path);
+
+    let differ = Differ::new(config.diff_options());
+    let (comparison, diff_frames) = match diff_frames(&frames1, &frames2, &differ) {
+        Ok(compared) => compared,
+        Err(msg) => exit_with_error(to_stderr, &msg),
+    };
+
+    report!(
+        to_stderr,
+        "Frames {} {}",
+        comparison.frame_count1,
+        comparison.frame_count2
+    );
+    if comparison.frame_count1 != comparison.frame_count2 {
+        report!(
+            to_stderr,
+            "Frame count mismatch, only the first {} frames are compared",
+            comparison.frames.len()
+        );
+    }
+    for frame in &comparison.frames {
+        if frame.delay1 == frame.delay2 {
+            report!(
+                to_stderr,
+                "Frame {} {} {}",
+                frame.index,
+                metric_label(config.metric),
+                frame.score
+            );
+        } else {
+            report!(
+                to_stderr,
+                "Frame {} {} {} (timing {} ms vs {} ms)",
+                frame.index,
+                metric_label(config.metric),
+                frame.score,
+                frame.delay1.as_millis(),
+                frame.delay2.as_millis()
+            );
+        }
+    }
+    if let (Some(mean), Some(worst)) = (
+        comparison.mean_score(),
+        comparison.worst_frame(config.metric),
+    ) {
+        report!(
+            to_stderr,
+            "Overall {} mean {}, worst frame {} {}",
+            metric_label(config.metric),
+            mean,
+            worst.index,
+            worst.score
+        );
     }
 
- 
+    let (Some(_), Some(filename)) = (config.mode, config.filename) else {
+        return;
+    };
+
+    if filename.to_lowercase().ends_with(".gif") {
+        if let Err(msg) = save_animation(&diff_frames, filename) {
+            report!(to_stderr, "Error: {}", msg);
+        }
+    } else {
+        for (index, frame) in diff_frames.into_iter().enumerate() {
+            let path = utils::frame_filename(filename, index);
+            if let Err(msg) = utils::safe_save_image(frame.image, &path, &config.encode_options) {
+                report!(to_stderr, "Error: {}", msg);
+            }
+        }
+    }
 }
@@ -0,0 +1,143 @@
+use image::{DynamicImage, GenericImageView, Pixel};
+
+use crate::Metric;
+
+/// Full-reference error measures for one channel, or for all channels pooled
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChannelErrors {
+    /// Mean squared error
+    pub mse: f64,
+    /// Root mean squared error
+    pub rmse: f64,
+    /// Peak signal-to-noise ratio in dB, infinite for identical images
+    pub psnr: f64,
+    /// Largest absolute difference of any single value
+    pub max_abs_error: u8,
+}
+
+impl ChannelErrors {
+    fn from_sums(squared_error: u64, max_abs_error: u8, count: usize) -> Self {
+        let mse = squared_error as f64 / count as f64;
+        let psnr = if mse == 0.0 {
+            f64::INFINITY
+        } else {
+            10.0 * (255.0 * 255.0 / mse).log10()
+        };
+        ChannelErrors {
+            mse,
+            rmse: mse.sqrt(),
+            psnr,
+            max_abs_error,
+        }
+    }
+
+    /// The value of `metric`, or `None` if it is not one of the error metrics
+    pub fn value(&self, metric: Metric) -> Option<f64> {
+        match metric {
+            Metric::Mse => Some(self.mse),
+            Metric::Rmse => Some(self.rmse),
+            Metric::Psnr => Some(self.psnr),
+            Metric::MaxAbsError => Some(self.max_abs_error as f64),
+            _ => None,
+        }
+    }
+}
+
+/// Error measures of image2 against image1, per RGB channel and overall
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ErrorMetrics {
+    pub red: ChannelErrors,
+    pub green: ChannelErrors,
+    pub blue: ChannelErrors,
+    pub overall: ChannelErrors,
+}
+
+pub fn calculate_error_metrics(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+) -> Result<ErrorMetrics, String> {
+    if image1.dimensions() != image2.dimensions() {
+        return Err(format!(
+            "Images must have the same dimensions, got {:?} and {:?}",
+            image1.dimensions(),
+            image2.dimensions()
+        ));
+    }
+    if image1.width() == 0 || image1.height() == 0 {
+        return Err("Images must not be empty".to_string());
+    }
+
+    let mut squared_errors = [0u64; 3];
+    let mut max_errors = [0u8; 3];
+    for ((_, _, a), (_, _, b)) in image1.pixels().zip(image2.pixels()) {
+        let (a, b) = (a.to_rgb(), b.to_rgb());
+        for c in 0..3 {
+            let error = a[c].abs_diff(b[c]);
+            squared_errors[c] += error as u64 * error as u64;
+            max_errors[c] = max_errors[c].max(error);
+        }
+    }
+
+    let count = image1.width() as usize * image1.height() as usize;
+    let channel = |c: usize| ChannelErrors::from_sums(squared_errors[c], max_errors[c], count);
+
+    Ok(ErrorMetrics {
+        red: channel(0),
+        green: channel(1),
+        blue: channel(2),
+        overall: ChannelErrors::from_sums(
+            squared_errors.iter().sum(),
+            *max_errors.iter().max().unwrap(),
+            count * 3,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn solid(width: u32, height: u32, color: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(ImageBuffer::from_pixel(width, height, Rgb(color)))
+    }
+
+    #[test]
+    fn test_error_metrics_identical() {
+        let image = solid(4, 4, [10, 20, 30]);
+        let errors = calculate_error_metrics(&image, &image).unwrap();
+
+        assert_eq!(errors.overall.mse, 0.0);
+        assert_eq!(errors.overall.rmse, 0.0);
+        assert_eq!(errors.overall.psnr, f64::INFINITY);
+        assert_eq!(errors.overall.max_abs_error, 0);
+    }
+
+    #[test]
+    fn test_error_metrics_per_channel() {
+        let image1 = solid(4, 4, [10, 20, 30]);
+        let image2 = solid(4, 4, [20, 20, 0]);
+        let errors = calculate_error_metrics(&image1, &image2).unwrap();
+
+        assert_eq!(errors.red.mse, 100.0);
+        assert_eq!(errors.red.rmse, 10.0);
+        assert!((errors.red.psnr - 28.1308).abs() < 1e-4);
+        assert_eq!(errors.green.mse, 0.0);
+        assert_eq!(errors.blue.mse, 900.0);
+        assert_eq!(errors.blue.max_abs_error, 30);
+
+        // overall pools all channel values
+        assert!((errors.overall.mse - 1000.0 / 3.0).abs() < 1e-9);
+        assert_eq!(errors.overall.max_abs_error, 30);
+        assert_eq!(errors.overall.value(Metric::Mse), Some(errors.overall.mse));
+        assert_eq!(errors.overall.value(Metric::Ssim), None);
+    }
+
+    #[test]
+    fn test_error_metrics_dimension_mismatch() {
+        let image1 = solid(4, 4, [0, 0, 0]);
+        let image2 = solid(4, 5, [0, 0, 0]);
+
+        assert!(calculate_error_metrics(&image1, &image2).is_err());
+    }
+}
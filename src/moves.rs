@@ -0,0 +1,402 @@
+use std::collections::VecDeque;
+
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+// Changed pixels are grouped on a grid of cells this size, so parts of one
+// component a few pixels apart end up in the same region
+const CELL_SIZE: u32 = 4;
+// Largest mean absolute channel difference for a block to count as moved
+const MATCH_TOLERANCE: f64 = 4.0;
+// Patches whose channels vary less than this are flat and match anywhere
+const MIN_PATCH_CONTRAST: u8 = 16;
+
+// Colours cycled through so each move and its destination share a colour
+const MOVE_COLORS: [Rgba<u8>; 6] = [
+    Rgba([0, 114, 178, 255]),
+    Rgba([230, 159, 0, 255]),
+    Rgba([0, 158, 115, 255]),
+    Rgba([204, 121, 167, 255]),
+    Rgba([86, 180, 233, 255]),
+    Rgba([213, 94, 0, 255]),
+];
+
+/// Axis aligned rectangle in pixel coordinates
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    fn translate(&self, vector: (i32, i32)) -> Region {
+        Region {
+            x: (self.x as i32 + vector.0) as u32,
+            y: (self.y as i32 + vector.1) as u32,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn intersects(&self, other: &Region) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+
+    fn center(&self) -> (i32, i32) {
+        (
+            (self.x + self.width / 2) as i32,
+            (self.y + self.height / 2) as i32,
+        )
+    }
+}
+
+/// A block of image1 that was found again in image2, `vector` away
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MovedBlock {
+    pub from: Region,
+    pub vector: (i32, i32),
+}
+
+impl MovedBlock {
+    pub fn to(&self) -> Region {
+        self.from.translate(self.vector)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveDetection {
+    pub moves: Vec<MovedBlock>,
+    /// Changed regions that are not explained by a move
+    pub changed: Vec<Region>,
+}
+
+// Bounding boxes of the connected groups of changed cells
+fn changed_regions(image1: &RgbaImage, image2: &RgbaImage) -> Vec<Region> {
+    let (width, height) = image1.dimensions();
+    let cells_x = width.div_ceil(CELL_SIZE) as usize;
+    let cells_y = height.div_ceil(CELL_SIZE) as usize;
+
+    let mut changed = vec![false; cells_x * cells_y];
+    for (x, y, pixel) in image1.enumerate_pixels() {
+        if pixel != image2.get_pixel(x, y) {
+            changed[(y / CELL_SIZE) as usize * cells_x + (x / CELL_SIZE) as usize] = true;
+        }
+    }
+
+    let mut seen = vec![false; changed.len()];
+    let mut regions = Vec::new();
+    for start in 0..changed.len() {
+        if !changed[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut queue = VecDeque::from([start]);
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+        while let Some(cell) = queue.pop_front() {
+            let (cx, cy) = (cell % cells_x, cell / cells_x);
+            min_x = min_x.min(cx);
+            min_y = min_y.min(cy);
+            max_x = max_x.max(cx);
+            max_y = max_y.max(cy);
+            for ny in cy.saturating_sub(1)..=(cy + 1).min(cells_y - 1) {
+                for nx in cx.saturating_sub(1)..=(cx + 1).min(cells_x - 1) {
+                    let neighbour = ny * cells_x + nx;
+                    if changed[neighbour] && !seen[neighbour] {
+                        seen[neighbour] = true;
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+        }
+
+        let x = min_x as u32 * CELL_SIZE;
+        let y = min_y as u32 * CELL_SIZE;
+        regions.push(Region {
+            x,
+            y,
+            width: ((max_x as u32 + 1) * CELL_SIZE).min(width) - x,
+            height: ((max_y as u32 + 1) * CELL_SIZE).min(height) - y,
+        });
+    }
+    regions
+}
+
+fn is_flat(image: &RgbaImage, region: &Region) -> bool {
+    let first = image.get_pixel(region.x, region.y);
+    (region.y..region.y + region.height).all(|y| {
+        (region.x..region.x + region.width).all(|x| {
+            let pixel = image.get_pixel(x, y);
+            (0..3).all(|c| pixel[c].abs_diff(first[c]) < MIN_PATCH_CONTRAST)
+        })
+    })
+}
+
+// Mean absolute channel difference between `region` of image1 and the same
+// region moved by `vector` in image2, giving up once it exceeds `limit`
+fn match_cost(
+    image1: &RgbaImage,
+    image2: &RgbaImage,
+    region: &Region,
+    vector: (i32, i32),
+    limit: f64,
+) -> Option<f64> {
+    let values = region.width as f64 * region.height as f64 * 3.0;
+    let max_total = (limit * values) as u64;
+    let mut total = 0u64;
+    for y in region.y..region.y + region.height {
+        for x in region.x..region.x + region.width {
+            let a = image1.get_pixel(x, y);
+            let b = image2.get_pixel((x as i32 + vector.0) as u32, (y as i32 + vector.1) as u32);
+            total += (0..3).map(|c| a[c].abs_diff(b[c]) as u64).sum::<u64>();
+        }
+        if total > max_total {
+            return None;
+        }
+    }
+    Some(total as f64 / values)
+}
+
+// Best non-zero translation of `region` within `max_distance`, if the block
+// matches well enough there
+fn find_move(
+    image1: &RgbaImage,
+    image2: &RgbaImage,
+    region: &Region,
+    max_distance: u32,
+) -> Option<(i32, i32)> {
+    let (width, height) = image2.dimensions();
+    let max = max_distance as i32;
+    let min_dx = (-max).max(-(region.x as i32));
+    let min_dy = (-max).max(-(region.y as i32));
+    let max_dx = max.min(width as i32 - (region.x + region.width) as i32);
+    let max_dy = max.min(height as i32 - (region.y + region.height) as i32);
+
+    let mut best: Option<((i32, i32), f64)> = None;
+    for dy in min_dy..=max_dy {
+        for dx in min_dx..=max_dx {
+            if dx == 0 && dy == 0 {
+                continue;
+            }
+            let limit = best.map_or(MATCH_TOLERANCE, |(_, cost)| cost);
+            if let Some(cost) = match_cost(image1, image2, region, (dx, dy), limit) {
+                let better = best.is_none_or(|((bx, by), best_cost)| {
+                    cost < best_cost || dx.abs() + dy.abs() < bx.abs() + by.abs()
+                });
+                if better {
+                    best = Some(((dx, dy), cost));
+                }
+            }
+        }
+    }
+    best.map(|(vector, _)| vector)
+}
+
+/// Find changed regions and check whether the content of image1 there can
+/// be found in image2 within `max_distance` pixels. Blocks that were found
+/// are reported as moves instead of a removal plus an addition.
+pub fn detect_moves(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    max_distance: u32,
+) -> Result<MoveDetection, String> {
+    if image1.dimensions() != image2.dimensions() {
+        return Err(format!(
+            "Images must have the same dimensions, got {:?} and {:?}",
+            image1.dimensions(),
+            image2.dimensions()
+        ));
+    }
+    let image1 = image1.to_rgba8();
+    let image2 = image2.to_rgba8();
+
+    let regions = changed_regions(&image1, &image2);
+    let mut moves: Vec<MovedBlock> = Vec::new();
+    let mut changed = Vec::new();
+    for region in &regions {
+        if is_flat(&image1, region) {
+            continue;
+        }
+        match find_move(&image1, &image2, region, max_distance) {
+            Some(vector) => moves.push(MovedBlock {
+                from: *region,
+                vector,
+            }),
+            None => changed.push(*region),
+        }
+    }
+
+    // Flat regions are where a block was moved to or from, anything else is a change
+    for region in &regions {
+        let explained = moves
+            .iter()
+            .any(|m| m.from.intersects(region) || m.to().intersects(region));
+        if is_flat(&image1, region) && !explained {
+            changed.push(*region);
+        }
+    }
+
+    Ok(MoveDetection { moves, changed })
+}
+
+fn draw_rect(image: &mut RgbaImage, region: &Region, color: Rgba<u8>) {
+    if region.width == 0 || region.height == 0 {
+        return;
+    }
+    let (right, bottom) = (region.x + region.width - 1, region.y + region.height - 1);
+    for x in region.x..=right {
+        image.put_pixel(x, region.y, color);
+        image.put_pixel(x, bottom, color);
+    }
+    for y in region.y..=bottom {
+        image.put_pixel(region.x, y, color);
+        image.put_pixel(right, y, color);
+    }
+}
+
+fn draw_line(image: &mut RgbaImage, from: (i32, i32), to: (i32, i32), color: Rgba<u8>) {
+    // Bresenham
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let sx = if x < to.0 { 1 } else { -1 };
+    let sy = if y < to.1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    loop {
+        if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+            image.put_pixel(x as u32, y as u32, color);
+        }
+        if (x, y) == to {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Draw the detection on top of image2: each move gets its own colour for
+/// the source and destination outlines and the arrow between them, other
+/// changes are outlined in `color`.
+pub fn draw_moves(
+    image2: &DynamicImage,
+    detection: &MoveDetection,
+    color: Rgba<u8>,
+) -> DynamicImage {
+    let mut output = image2.to_rgba8();
+
+    for region in &detection.changed {
+        draw_rect(&mut output, region, color);
+    }
+    for (i, block) in detection.moves.iter().enumerate() {
+        let move_color = MOVE_COLORS[i % MOVE_COLORS.len()];
+        let to = block.to();
+        draw_rect(&mut output, &block.from, move_color);
+        draw_rect(&mut output, &to, move_color);
+
+        let (start, end) = (block.from.center(), to.center());
+        draw_line(&mut output, start, end, move_color);
+        // arrow head, two short strokes back from the end
+        let length = (((end.0 - start.0).pow(2) + (end.1 - start.1).pow(2)) as f64).sqrt();
+        if length > 0.0 {
+            let (ux, uy) = (
+                (end.0 - start.0) as f64 / length,
+                (end.1 - start.1) as f64 / length,
+            );
+            let head = 6.0_f64.min(length);
+            for side in [-1.0, 1.0] {
+                let hx = end.0 as f64 - head * (ux - side * uy * 0.5);
+                let hy = end.1 as f64 - head * (uy + side * ux * 0.5);
+                draw_line(
+                    &mut output,
+                    end,
+                    (hx.round() as i32, hy.round() as i32),
+                    move_color,
+                );
+            }
+        }
+    }
+
+    DynamicImage::ImageRgba8(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageBuffer;
+
+    // A white page with a checkered block at (x, y)
+    fn page_with_block(x: u32, y: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(120, 80, |px, py| {
+            if px >= x && px < x + 20 && py >= y && py < y + 12 {
+                let v = if (px - x + py - y).is_multiple_of(3) {
+                    0
+                } else {
+                    120
+                };
+                Rgba([v, v / 2, 200, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_detect_small_move() {
+        let image1 = page_with_block(30, 20);
+        let image2 = page_with_block(35, 18);
+
+        let detection = detect_moves(&image1, &image2, 16).unwrap();
+
+        assert_eq!(detection.moves.len(), 1);
+        assert_eq!(detection.moves[0].vector, (5, -2));
+        assert!(detection.changed.is_empty());
+    }
+
+    #[test]
+    fn test_detect_far_move() {
+        let image1 = page_with_block(4, 4);
+        let image2 = page_with_block(80, 50);
+
+        let detection = detect_moves(&image1, &image2, 100).unwrap();
+
+        assert_eq!(detection.moves.len(), 1);
+        assert_eq!(detection.moves[0].vector, (76, 46));
+        assert!(detection.changed.is_empty());
+
+        // out of reach, reported as plain changes
+        let detection = detect_moves(&image1, &image2, 10).unwrap();
+        assert!(detection.moves.is_empty());
+        assert_eq!(detection.changed.len(), 2);
+    }
+
+    #[test]
+    fn test_draw_moves() {
+        let image1 = page_with_block(30, 20);
+        let image2 = page_with_block(35, 18);
+        let detection = detect_moves(&image1, &image2, 16).unwrap();
+
+        let output = draw_moves(&image2, &detection, Rgba([255, 0, 0, 255]));
+        let from = detection.moves[0].from;
+
+        assert_eq!(output.dimensions(), image2.dimensions());
+        assert_eq!(output.get_pixel(from.x, from.y), MOVE_COLORS[0]);
+    }
+
+    #[test]
+    fn test_detect_moves_dimension_mismatch() {
+        let image1 = page_with_block(0, 0);
+        let image2 = image1.crop_imm(0, 0, 60, 40);
+
+        assert!(detect_moves(&image1, &image2, 8).is_err());
+    }
+}
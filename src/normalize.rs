@@ -0,0 +1,317 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+
+// Share of the darkest and brightest values auto-levels ignores, so a few
+// stray pixels do not decide the range
+const AUTO_LEVELS_CLIP: f64 = 0.005;
+
+/// How the images are normalized before diffing, so that a global colour or
+/// brightness shift is not reported as a change
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Normalization {
+    /// Remap the values of image2 so each channel has the histogram of image1
+    HistogramMatch,
+    /// Scale and shift each channel of image2 to the mean and standard
+    /// deviation of image1
+    MeanStd,
+    /// Stretch each channel of both images to the full range
+    AutoLevels,
+}
+
+/// Applied normalization of one channel: a value `v` became roughly
+/// `gain * v + offset`. Exact for the linear normalizations, a least
+/// squares fit for histogram matching.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChannelMapping {
+    pub gain: f64,
+    pub offset: f64,
+}
+
+impl ChannelMapping {
+    const IDENTITY: ChannelMapping = ChannelMapping {
+        gain: 1.0,
+        offset: 0.0,
+    };
+
+    fn lookup_table(&self) -> [u8; 256] {
+        std::array::from_fn(|v| {
+            (self.gain * v as f64 + self.offset)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        })
+    }
+}
+
+/// Both images after normalization, with the red, green and blue mappings
+/// that were applied to each
+#[derive(Debug)]
+pub struct NormalizedImages {
+    pub image1: DynamicImage,
+    pub image2: DynamicImage,
+    pub image1_mapping: [ChannelMapping; 3],
+    pub image2_mapping: [ChannelMapping; 3],
+}
+
+type Histograms = [[u64; 256]; 3];
+
+fn histograms(image: &RgbaImage) -> Histograms {
+    let mut histograms = [[0u64; 256]; 3];
+    for pixel in image.pixels() {
+        for (c, histogram) in histograms.iter_mut().enumerate() {
+            histogram[pixel[c] as usize] += 1;
+        }
+    }
+    histograms
+}
+
+fn mean_std(histogram: &[u64; 256]) -> (f64, f64) {
+    let count: u64 = histogram.iter().sum();
+    let mean = histogram
+        .iter()
+        .enumerate()
+        .map(|(v, &n)| v as f64 * n as f64)
+        .sum::<f64>()
+        / count as f64;
+    let variance = histogram
+        .iter()
+        .enumerate()
+        .map(|(v, &n)| (v as f64 - mean).powi(2) * n as f64)
+        .sum::<f64>()
+        / count as f64;
+    (mean, variance.sqrt())
+}
+
+// Smallest value with at least `share` of the pixels at or below it
+fn percentile(histogram: &[u64; 256], share: f64) -> u8 {
+    let count: u64 = histogram.iter().sum();
+    let target = (share * count as f64).ceil().max(1.0) as u64;
+    let mut seen = 0;
+    for (v, &n) in histogram.iter().enumerate() {
+        seen += n;
+        if seen >= target {
+            return v as u8;
+        }
+    }
+    255
+}
+
+fn match_mean_std(source: &[u64; 256], reference: &[u64; 256]) -> ChannelMapping {
+    let (source_mean, source_std) = mean_std(source);
+    let (reference_mean, reference_std) = mean_std(reference);
+    let gain = if source_std > 0.0 {
+        reference_std / source_std
+    } else {
+        1.0
+    };
+    ChannelMapping {
+        gain,
+        offset: reference_mean - gain * source_mean,
+    }
+}
+
+fn auto_levels(histogram: &[u64; 256]) -> ChannelMapping {
+    let low = percentile(histogram, AUTO_LEVELS_CLIP) as f64;
+    let high = percentile(histogram, 1.0 - AUTO_LEVELS_CLIP) as f64;
+    if high <= low {
+        return ChannelMapping::IDENTITY;
+    }
+    let gain = 255.0 / (high - low);
+    ChannelMapping {
+        gain,
+        offset: -gain * low,
+    }
+}
+
+// Lookup table giving each source value the reference value at the same
+// position of the cumulative histogram
+fn match_histogram(source: &[u64; 256], reference: &[u64; 256]) -> [u8; 256] {
+    let cumulative = |histogram: &[u64; 256]| {
+        let total: u64 = histogram.iter().sum();
+        let mut sum = 0;
+        histogram.map(|n| {
+            sum += n;
+            sum as f64 / total as f64
+        })
+    };
+    let source_cdf = cumulative(source);
+    let reference_cdf = cumulative(reference);
+
+    let mut table = [0u8; 256];
+    let mut r = 0;
+    for v in 0..256 {
+        while r < 255 && reference_cdf[r] < source_cdf[v] - 1e-12 {
+            r += 1;
+        }
+        table[v] = r as u8;
+    }
+    table
+}
+
+// Least squares line through the lookup table, weighted by how often each value occurs
+fn fit_mapping(histogram: &[u64; 256], table: &[u8; 256]) -> ChannelMapping {
+    let (mut n, mut sx, mut sy, mut sxx, mut sxy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for v in 0..256 {
+        let w = histogram[v] as f64;
+        let (x, y) = (v as f64, table[v] as f64);
+        n += w;
+        sx += w * x;
+        sy += w * y;
+        sxx += w * x * x;
+        sxy += w * x * y;
+    }
+    let variance = n * sxx - sx * sx;
+    if variance <= 0.0 {
+        // a single value, only the shift is known
+        return ChannelMapping {
+            gain: 1.0,
+            offset: (sy - sx) / n,
+        };
+    }
+    let gain = (n * sxy - sx * sy) / variance;
+    ChannelMapping {
+        gain,
+        offset: (sy - gain * sx) / n,
+    }
+}
+
+fn apply_tables(image: &RgbaImage, tables: &[[u8; 256]; 3]) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let pixel = image.get_pixel(x, y);
+        Rgba([
+            tables[0][pixel[0] as usize],
+            tables[1][pixel[1] as usize],
+            tables[2][pixel[2] as usize],
+            pixel[3],
+        ])
+    }))
+}
+
+/// Normalize the colour and brightness of both images so that only
+/// structural changes are left to diff. Alpha is left alone.
+pub fn normalize_images(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    normalization: Normalization,
+) -> Result<NormalizedImages, String> {
+    if image1.width() == 0 || image1.height() == 0 || image2.width() == 0 || image2.height() == 0 {
+        return Err("Images must not be empty".to_string());
+    }
+    let (rgba1, rgba2) = (image1.to_rgba8(), image2.to_rgba8());
+    let (histograms1, histograms2) = (histograms(&rgba1), histograms(&rgba2));
+
+    let identity = [ChannelMapping::IDENTITY; 3];
+    let (image1_mapping, image2_mapping, tables1, tables2) = match normalization {
+        Normalization::HistogramMatch => {
+            let tables: [[u8; 256]; 3] =
+                std::array::from_fn(|c| match_histogram(&histograms2[c], &histograms1[c]));
+            let mapping = std::array::from_fn(|c| fit_mapping(&histograms2[c], &tables[c]));
+            (
+                identity,
+                mapping,
+                identity.map(|m| m.lookup_table()),
+                tables,
+            )
+        }
+        Normalization::MeanStd => {
+            let mapping: [ChannelMapping; 3] =
+                std::array::from_fn(|c| match_mean_std(&histograms2[c], &histograms1[c]));
+            (
+                identity,
+                mapping,
+                identity.map(|m| m.lookup_table()),
+                mapping.map(|m| m.lookup_table()),
+            )
+        }
+        Normalization::AutoLevels => {
+            let mapping1 = histograms1.map(|h| auto_levels(&h));
+            let mapping2 = histograms2.map(|h| auto_levels(&h));
+            (
+                mapping1,
+                mapping2,
+                mapping1.map(|m| m.lookup_table()),
+                mapping2.map(|m| m.lookup_table()),
+            )
+        }
+    };
+
+    Ok(NormalizedImages {
+        image1: apply_tables(&rgba1, &tables1),
+        image2: apply_tables(&rgba2, &tables2),
+        image1_mapping,
+        image2_mapping,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageBuffer;
+
+    fn gradient(gain: f64, offset: f64) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(32, 16, |x, y| {
+            let v =
+                |base: u32| (gain * ((x * 5 + y * 3 + base) % 160) as f64 + offset).round() as u8;
+            Rgba([v(20), v(40), v(60), 255])
+        }))
+    }
+
+    #[test]
+    fn test_mean_std_removes_brightness_shift() {
+        let image1 = gradient(1.0, 40.0);
+        let image2 = gradient(1.0, 60.0);
+
+        let normalized = normalize_images(&image1, &image2, Normalization::MeanStd).unwrap();
+
+        assert_eq!(normalized.image1.to_rgba8(), image1.to_rgba8());
+        assert_eq!(normalized.image2.to_rgba8(), image1.to_rgba8());
+        for mapping in normalized.image2_mapping {
+            assert!((mapping.gain - 1.0).abs() < 1e-9);
+            assert!((mapping.offset + 20.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_histogram_match_removes_contrast_change() {
+        let image1 = gradient(1.0, 40.0);
+        let image2 = gradient(0.5, 10.0);
+
+        let normalized = normalize_images(&image1, &image2, Normalization::HistogramMatch).unwrap();
+
+        // halving the contrast merges neighbouring values, so allow one step
+        for (a, b) in normalized
+            .image2
+            .to_rgba8()
+            .pixels()
+            .zip(image1.to_rgba8().pixels())
+        {
+            assert!((0..3).all(|c| a[c].abs_diff(b[c]) <= 1), "{:?} {:?}", a, b);
+        }
+        let mapping = normalized.image2_mapping[0];
+        assert!((mapping.gain - 2.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_auto_levels_stretches_both_images() {
+        let image1 = gradient(1.0, 40.0);
+        let image2 = gradient(0.5, 100.0);
+
+        let normalized = normalize_images(&image1, &image2, Normalization::AutoLevels).unwrap();
+
+        for image in [&normalized.image1, &normalized.image2] {
+            let rgba = image.to_rgba8();
+            assert_eq!(rgba.pixels().map(|p| p[0]).min(), Some(0));
+            assert_eq!(rgba.pixels().map(|p| p[0]).max(), Some(255));
+        }
+        assert!(normalized.image2_mapping[0].gain > normalized.image1_mapping[0].gain);
+    }
+
+    #[test]
+    fn test_normalize_flat_and_empty_images() {
+        let flat = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([9, 9, 9, 255])));
+        let empty = DynamicImage::new_rgba8(0, 0);
+
+        let normalized = normalize_images(&flat, &flat, Normalization::AutoLevels).unwrap();
+
+        assert_eq!(normalized.image1.to_rgba8(), flat.to_rgba8());
+        assert!(normalize_images(&flat, &empty, Normalization::MeanStd).is_err());
+    }
+}
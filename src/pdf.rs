@@ -0,0 +1,298 @@
+#[cfg(feature = "pdf")]
+use image::DynamicImage;
+
+#[cfg(feature = "pdf")]
+use crate::InputDecoder;
+
+/// Whether the data looks like a PDF document: a `.pdf` name, or content
+/// starting with the `%PDF-` header
+pub fn is_pdf(data: &[u8], filename: &str) -> bool {
+    let extension = std::path::Path::new(filename)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    extension.as_deref() == Some("pdf") || data.starts_with(b"%PDF-")
+}
+
+/// Rasterize every page of a PDF document at `dpi` pixels per inch, on a
+/// white background. Paths, images and text in embedded Type1, TrueType,
+/// OpenType, CFF and Type3 fonts are drawn. Text in non-embedded fonts,
+/// shadings, patterns, soft mask groups and annotations are not.
+#[cfg(feature = "pdf")]
+pub fn rasterize_pdf(data: &[u8], dpi: f32) -> Result<Vec<DynamicImage>, String> {
+    let doc = lopdf::Document::load_mem(data).map_err(|err| format!("Invalid PDF: {}", err))?;
+    doc.get_pages()
+        .into_iter()
+        .map(|(number, page)| {
+            crate::pdf_render::render_page(&doc, page, dpi)
+                .map_err(|err| format!("Page {}: {}", number, err))
+        })
+        .collect()
+}
+
+/// Load and rasterize every page of a PDF file, see `rasterize_pdf`
+#[cfg(feature = "pdf")]
+pub fn load_pdf(filename: &str, dpi: f32) -> Result<Vec<DynamicImage>, String> {
+    let data = std::fs::read(filename)
+        .map_err(|err| format!("Error loading PDF {}: {}", filename, err))?;
+    rasterize_pdf(&data, dpi).map_err(|err| format!("Error loading PDF {}: {}", filename, err))
+}
+
+/// Input decoder rasterizing each page of a PDF document
+#[cfg(feature = "pdf")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PdfDecoder {
+    /// Pixels per inch, 72 renders one pixel per PDF unit
+    pub dpi: f32,
+}
+
+#[cfg(feature = "pdf")]
+impl InputDecoder for PdfDecoder {
+    fn name(&self) -> &str {
+        "PDF"
+    }
+
+    fn can_decode(&self, data: &[u8], filename: &str) -> bool {
+        is_pdf(data, filename)
+    }
+
+    fn decode_pages(&self, data: &[u8]) -> Result<Vec<DynamicImage>, String> {
+        rasterize_pdf(data, self.dpi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_pdf() {
+        assert!(is_pdf(b"", "report.PDF"));
+        assert!(is_pdf(b"%PDF-1.7\n", "-"));
+        assert!(!is_pdf(b"\x89PNG\r\n\x1a\n", "-"));
+    }
+
+    #[cfg(feature = "pdf")]
+    mod rasterize {
+        use super::super::*;
+        use image::{GenericImageView, Rgba};
+        use lopdf::{dictionary, Document, Object, Stream};
+
+        // A document with one 20x10 page per content stream, sharing the
+        // resources added by `resources`
+        fn document(
+            pages: &[&str],
+            resources: impl FnOnce(&mut Document) -> lopdf::Dictionary,
+        ) -> Vec<u8> {
+            let mut doc = Document::with_version("1.5");
+            let pages_id = doc.new_object_id();
+            let resources = resources(&mut doc);
+            let resources_id = doc.add_object(resources);
+            let kids: Vec<Object> = pages
+                .iter()
+                .map(|content| {
+                    let content_id =
+                        doc.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+                    doc.add_object(dictionary! {
+                        "Type" => "Page",
+                        "Parent" => pages_id,
+                        "Contents" => content_id,
+                    })
+                    .into()
+                })
+                .collect();
+            doc.objects.insert(
+                pages_id,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Pages",
+                    "Kids" => kids.clone(),
+                    "Count" => kids.len() as i64,
+                    "MediaBox" => vec![0.into(), 0.into(), 20.into(), 10.into()],
+                    "Resources" => resources_id,
+                }),
+            );
+            let catalog_id =
+                doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+            doc.trailer.set("Root", catalog_id);
+            let mut data = Vec::new();
+            doc.save_to(&mut data).unwrap();
+            data
+        }
+
+        #[test]
+        fn test_rasterize_pages() {
+            let data = document(
+                &["1 0 0 rg 0 0 10 10 re f", "0 0 1 RG 4 w 0 5 m 20 5 l S"],
+                |_| dictionary! {},
+            );
+
+            let pages = rasterize_pdf(&data, 144.0).unwrap();
+
+            assert_eq!(pages.len(), 2);
+            assert_eq!(pages[0].dimensions(), (40, 20));
+            assert_eq!(pages[0].get_pixel(5, 5), Rgba([255, 0, 0, 255]));
+            assert_eq!(pages[0].get_pixel(30, 5), Rgba([255, 255, 255, 255]));
+            assert_eq!(pages[1].get_pixel(20, 10), Rgba([0, 0, 255, 255]));
+            assert_eq!(pages[1].get_pixel(20, 1), Rgba([255, 255, 255, 255]));
+            assert!(rasterize_pdf(b"%PDF-1.7 truncated", 72.0).is_err());
+            // 80000x40000 pixels
+            assert!(rasterize_pdf(&data, 288000.0).is_err());
+        }
+
+        #[test]
+        fn test_rasterize_xobjects() {
+            // a 2x1 image of a green and a blue pixel, and a form drawing a
+            // black square clipped by its bounding box
+            let image = Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => 2,
+                    "Height" => 1,
+                    "ColorSpace" => "DeviceRGB",
+                    "BitsPerComponent" => 8,
+                },
+                vec![0, 255, 0, 0, 0, 255],
+            );
+            let form = Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Form",
+                    "BBox" => vec![0.into(), 0.into(), 5.into(), 5.into()],
+                    "Matrix" => vec![1.into(), 0.into(), 0.into(), 1.into(), 10.into(), 0.into()],
+                },
+                b"0 g 0 0 10 10 re f".to_vec(),
+            );
+            let data = document(&["q 10 0 0 10 0 0 cm /Im Do Q /Fm Do"], |doc| {
+                dictionary! {
+                    "XObject" => dictionary! {
+                        "Im" => doc.add_object(image),
+                        "Fm" => doc.add_object(form),
+                    },
+                }
+            });
+
+            let page = rasterize_pdf(&data, 72.0).unwrap().remove(0);
+
+            assert_eq!(page.get_pixel(1, 5), Rgba([0, 255, 0, 255]));
+            assert_eq!(page.get_pixel(8, 5), Rgba([0, 0, 255, 255]));
+            assert_eq!(page.get_pixel(12, 7), Rgba([0, 0, 0, 255]));
+            assert_eq!(page.get_pixel(17, 7), Rgba([255, 255, 255, 255]));
+            assert_eq!(page.get_pixel(12, 2), Rgba([255, 255, 255, 255]));
+        }
+
+        #[test]
+        fn test_rasterize_image_formats() {
+            // a red JPEG, and a 1 bit indexed image of a yellow and a magenta
+            // pixel whose soft mask hides the magenta one
+            let mut jpeg = Vec::new();
+            image::RgbImage::from_pixel(8, 8, image::Rgb([255, 0, 0]))
+                .write_to(
+                    &mut std::io::Cursor::new(&mut jpeg),
+                    image::ImageFormat::Jpeg,
+                )
+                .unwrap();
+            let jpeg = Stream::new(
+                dictionary! {
+                    "Subtype" => "Image",
+                    "Width" => 8,
+                    "Height" => 8,
+                    "ColorSpace" => "DeviceRGB",
+                    "BitsPerComponent" => 8,
+                    "Filter" => "DCTDecode",
+                },
+                jpeg,
+            );
+            let soft_mask = Stream::new(
+                dictionary! {
+                    "Subtype" => "Image",
+                    "Width" => 2,
+                    "Height" => 1,
+                    "ColorSpace" => "DeviceGray",
+                    "BitsPerComponent" => 8,
+                },
+                vec![255, 0],
+            );
+            let data = document(
+                &["q 10 0 0 10 0 0 cm /Jpeg Do Q q 10 0 0 10 10 0 cm /Indexed Do Q"],
+                |doc| {
+                    let indexed = Stream::new(
+                        dictionary! {
+                            "Subtype" => "Image",
+                            "Width" => 2,
+                            "Height" => 1,
+                            "ColorSpace" => vec![
+                                Object::Name(b"Indexed".to_vec()),
+                                Object::Name(b"DeviceRGB".to_vec()),
+                                1.into(),
+                                Object::string_literal(vec![255, 255, 0, 255, 0, 255]),
+                            ],
+                            "BitsPerComponent" => 1,
+                            "SMask" => doc.add_object(soft_mask),
+                        },
+                        vec![0b0100_0000],
+                    );
+                    dictionary! {
+                        "XObject" => dictionary! {
+                            "Jpeg" => doc.add_object(jpeg),
+                            "Indexed" => doc.add_object(indexed),
+                        },
+                    }
+                },
+            );
+
+            let page = rasterize_pdf(&data, 72.0).unwrap().remove(0);
+
+            let Rgba([red, green, blue, _]) = page.get_pixel(5, 5);
+            assert!(red > 240 && green < 16 && blue < 16);
+            assert_eq!(page.get_pixel(11, 5), Rgba([255, 255, 0, 255]));
+            assert_eq!(page.get_pixel(18, 5), Rgba([255, 255, 255, 255]));
+        }
+
+        #[test]
+        fn test_rasterize_text() {
+            // Type3 font whose glyph "a" is a square filling its 1000 unit em
+            let square = Stream::new(
+                dictionary! {},
+                b"1000 0 0 0 1000 1000 d1 0 0 1000 1000 re f".to_vec(),
+            );
+            let data = document(
+                &["BT /F1 4 Tf 1 0 0 rg 2 3 Td (aa) Tj 0 g 10 0 Td (a) Tj ET"],
+                |doc| {
+                    let font = dictionary! {
+                        "Type" => "Font",
+                        "Subtype" => "Type3",
+                        "FontBBox" => vec![0.into(), 0.into(), 1000.into(), 1000.into()],
+                        "FontMatrix" => vec![0.001.into(), 0.into(), 0.into(), 0.001.into(), 0.into(), 0.into()],
+                        "CharProcs" => dictionary! { "square" => doc.add_object(square) },
+                        "Encoding" => dictionary! { "Differences" => vec![97.into(), Object::Name(b"square".to_vec())] },
+                        "FirstChar" => 97,
+                        "LastChar" => 97,
+                        "Widths" => vec![1250.into()],
+                    };
+                    dictionary! { "Font" => dictionary! { "F1" => doc.add_object(font) } }
+                },
+            );
+
+            let page = rasterize_pdf(&data, 72.0).unwrap().remove(0);
+
+            // glyphs are 4 units wide and advance 5 units, the line starts at x 2
+            // and y 3, the next line 10 units to the right
+            assert_eq!(page.get_pixel(3, 5), Rgba([255, 0, 0, 255]));
+            assert_eq!(page.get_pixel(6, 5), Rgba([255, 255, 255, 255]));
+            assert_eq!(page.get_pixel(8, 5), Rgba([255, 0, 0, 255]));
+            assert_eq!(page.get_pixel(11, 5), Rgba([255, 255, 255, 255]));
+            assert_eq!(page.get_pixel(13, 5), Rgba([0, 0, 0, 255]));
+            assert_eq!(page.get_pixel(3, 1), Rgba([255, 255, 255, 255]));
+        }
+
+        #[test]
+        fn test_pdf_decoder() {
+            let decoder = PdfDecoder { dpi: 72.0 };
+            let data = document(&["0.5 g 0 0 20 10 re f"], |_| dictionary! {});
+
+            assert!(decoder.can_decode(&data, "-"));
+            let pages = decoder.decode_pages(&data).unwrap();
+            assert_eq!(pages[0].get_pixel(10, 5), Rgba([128, 128, 128, 255]));
+        }
+    }
+}
@@ -0,0 +1,769 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use lopdf::{Dictionary, Document, Object, Stream};
+use tiny_skia::{Path, PathBuilder, Transform};
+use ttf_parser::{cff, Face, GlyphId, OutlineBuilder, PlatformId};
+
+use crate::pdf_render::{entry, entry_dict, numbers, resolve, STREAM_LIMIT};
+use crate::pdf_type1::Type1;
+
+/// Font of PDF text. Glyphs are drawn from embedded Type1, TrueType, OpenType
+/// and CFF programs, or from the content streams of Type3 fonts; fonts without
+/// an embedded program still advance the text but draw nothing.
+pub(crate) struct Font<'a> {
+    program: Option<Program>,
+    type3: Option<Type3<'a>>,
+    /// Type0 fonts use two byte codes, read as CIDs
+    two_byte: bool,
+    /// Glyph of each code of a simple font
+    simple_glyphs: Vec<Option<u16>>,
+    cid_glyphs: CidGlyphs,
+    /// Advance of each code for a font size of 1
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+    /// Outlines for a font size of 1, by glyph
+    outlines: RefCell<HashMap<u16, Option<Path>>>,
+}
+
+// Glyphs of the CIDs of a Type0 font
+enum CidGlyphs {
+    Identity,
+    /// CIDToGIDMap stream of a CIDFontType2 font
+    Map(Vec<u16>),
+    /// Charset of a CID-keyed CFF program
+    Charset(HashMap<u16, u16>),
+}
+
+// Type3 glyphs are content streams in the glyph space of the font matrix
+struct Type3<'a> {
+    matrix: Transform,
+    procs: HashMap<u32, &'a Stream>,
+    resources: Option<&'a Dictionary>,
+}
+
+// Embedded font program
+enum Program {
+    /// TrueType or OpenType
+    Sfnt(Vec<u8>),
+    /// Bare CFF, as embedded for Type1C and CIDFontType0C fonts
+    Cff(Vec<u8>),
+    Type1(Type1),
+}
+
+impl<'a> Font<'a> {
+    pub(crate) fn load(doc: &'a Document, dict: &'a Dictionary) -> Font<'a> {
+        match dict.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Type0") => Self::load_type0(doc, dict),
+            Ok(b"Type3") => Self::load_type3(doc, dict),
+            _ => Self::load_simple(doc, dict),
+        }
+    }
+
+    fn empty() -> Font<'a> {
+        Font {
+            program: None,
+            type3: None,
+            two_byte: false,
+            simple_glyphs: vec![None; 256],
+            cid_glyphs: CidGlyphs::Identity,
+            widths: HashMap::new(),
+            default_width: 0.0,
+            outlines: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn load_simple(doc: &'a Document, dict: &'a Dictionary) -> Font<'a> {
+        let descriptor = entry_dict(doc, dict, b"FontDescriptor");
+        let program = descriptor.and_then(|descriptor| Program::load(doc, descriptor));
+        let symbolic = descriptor
+            .and_then(|descriptor| entry(doc, descriptor, b"Flags"))
+            .and_then(|flags| flags.as_i64().ok())
+            .is_some_and(|flags| flags & 4 != 0);
+        let names = glyph_names(doc, dict, symbolic);
+
+        let mut font = Font::empty();
+        if let Some(program) = &program {
+            font.simple_glyphs = program.simple_glyphs(&names);
+        }
+        font.default_width = descriptor
+            .and_then(|descriptor| entry(doc, descriptor, b"MissingWidth"))
+            .and_then(|width| width.as_float().ok())
+            .unwrap_or(0.0)
+            / 1000.0;
+        font.widths = simple_widths(doc, dict, 1.0 / 1000.0);
+        if let Some(program) = &program {
+            for (code, glyph) in font.simple_glyphs.iter().enumerate() {
+                let code = code as u32;
+                if let (false, Some(glyph)) = (font.widths.contains_key(&code), glyph) {
+                    if let Some(advance) = program.advance(*glyph) {
+                        font.widths.insert(code, advance);
+                    }
+                }
+            }
+        }
+        font.program = program;
+        font
+    }
+
+    fn load_type0(doc: &'a Document, dict: &'a Dictionary) -> Font<'a> {
+        // only the Identity-H and Identity-V encodings are read, other CMaps
+        // are taken as identity as well
+        let mut font = Font {
+            two_byte: true,
+            default_width: 1.0,
+            ..Font::empty()
+        };
+        let Some(descendant) = entry(doc, dict, b"DescendantFonts")
+            .and_then(|fonts| fonts.as_array().ok())
+            .and_then(|fonts| fonts.first())
+            .and_then(|font| resolve(doc, font))
+            .and_then(|font| font.as_dict().ok())
+        else {
+            return font;
+        };
+
+        if let Some(width) = entry(doc, descendant, b"DW").and_then(|width| width.as_float().ok()) {
+            font.default_width = width / 1000.0;
+        }
+        font.widths = cid_widths(doc, descendant);
+        font.program = entry_dict(doc, descendant, b"FontDescriptor")
+            .and_then(|descriptor| Program::load(doc, descriptor));
+        font.cid_glyphs = match (&font.program, entry(doc, descendant, b"CIDToGIDMap")) {
+            (Some(Program::Cff(data)), _) => cff_charset(data),
+            (_, Some(Object::Stream(stream))) => {
+                match stream.decompressed_content_with_limit(STREAM_LIMIT) {
+                    Ok(map) => CidGlyphs::Map(
+                        map.chunks_exact(2)
+                            .map(|gid| u16::from_be_bytes([gid[0], gid[1]]))
+                            .collect(),
+                    ),
+                    Err(_) => CidGlyphs::Identity,
+                }
+            }
+            _ => CidGlyphs::Identity,
+        };
+        font
+    }
+
+    fn load_type3(doc: &'a Document, dict: &'a Dictionary) -> Font<'a> {
+        let matrix = match numbers(doc, dict.get(b"FontMatrix").ok())[..] {
+            [a, b, c, d, e, f] => Transform::from_row(a, b, c, d, e, f),
+            _ => Transform::from_scale(0.001, 0.001),
+        };
+        let names = glyph_names(doc, dict, true);
+        let mut procs = HashMap::new();
+        if let Some(char_procs) = entry_dict(doc, dict, b"CharProcs") {
+            for (code, name) in names.iter().enumerate() {
+                let proc = name
+                    .and_then(|name| entry(doc, char_procs, name.as_bytes()))
+                    .and_then(|proc| proc.as_stream().ok());
+                if let Some(proc) = proc {
+                    procs.insert(code as u32, proc);
+                }
+            }
+        }
+
+        Font {
+            widths: simple_widths(doc, dict, matrix.sx),
+            type3: Some(Type3 {
+                matrix,
+                procs,
+                resources: entry_dict(doc, dict, b"Resources"),
+            }),
+            ..Font::empty()
+        }
+    }
+
+    /// Character codes of a string shown with this font
+    pub(crate) fn codes(&self, bytes: &[u8]) -> Vec<u32> {
+        if self.two_byte {
+            bytes
+                .chunks_exact(2)
+                .map(|code| u32::from(u16::from_be_bytes([code[0], code[1]])))
+                .collect()
+        } else {
+            bytes.iter().map(|&code| u32::from(code)).collect()
+        }
+    }
+
+    /// Whether word spacing applies to the code, which is only the case for
+    /// the single byte code 32
+    pub(crate) fn is_space(&self, code: u32) -> bool {
+        !self.two_byte && code == 32
+    }
+
+    /// Horizontal advance of the code for a font size of 1
+    pub(crate) fn width(&self, code: u32) -> f32 {
+        self.widths
+            .get(&code)
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+
+    /// Outline of the code for a font size of 1, None if nothing is drawn
+    pub(crate) fn outline(&self, code: u32) -> Option<Path> {
+        let program = self.program.as_ref()?;
+        let glyph = if self.two_byte {
+            let cid = code as u16;
+            match &self.cid_glyphs {
+                CidGlyphs::Identity => Some(cid),
+                CidGlyphs::Map(map) => map.get(usize::from(cid)).copied(),
+                CidGlyphs::Charset(charset) => charset.get(&cid).copied(),
+            }
+        } else {
+            self.simple_glyphs.get(code as usize).copied().flatten()
+        }?;
+        self.outlines
+            .borrow_mut()
+            .entry(glyph)
+            .or_insert_with(|| program.outline(glyph))
+            .clone()
+    }
+
+    /// Content stream, font matrix and resources of a Type3 glyph
+    pub(crate) fn type3_glyph(
+        &self,
+        code: u32,
+    ) -> Option<(&'a Stream, Transform, Option<&'a Dictionary>)> {
+        let type3 = self.type3.as_ref()?;
+        let proc = type3.procs.get(&code)?;
+        Some((proc, type3.matrix, type3.resources))
+    }
+
+    pub(crate) fn is_type3(&self) -> bool {
+        self.type3.is_some()
+    }
+}
+
+impl Program {
+    fn load(doc: &Document, descriptor: &Dictionary) -> Option<Program> {
+        let data = |stream: &Stream| stream.decompressed_content_with_limit(STREAM_LIMIT).ok();
+        let program = if let Some(Object::Stream(stream)) = entry(doc, descriptor, b"FontFile") {
+            let clear_length = entry(doc, &stream.dict, b"Length1")
+                .and_then(|length| length.as_i64().ok())
+                .and_then(|length| usize::try_from(length).ok());
+            return Type1::parse(&data(stream)?, clear_length).map(Program::Type1);
+        } else if let Some(Object::Stream(stream)) = entry(doc, descriptor, b"FontFile2") {
+            Program::Sfnt(data(stream)?)
+        } else if let Some(Object::Stream(stream)) = entry(doc, descriptor, b"FontFile3") {
+            match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+                Ok(b"OpenType") => Program::Sfnt(data(stream)?),
+                _ => Program::Cff(data(stream)?),
+            }
+        } else {
+            return None;
+        };
+
+        let valid = match &program {
+            Program::Sfnt(data) => Face::parse(data, 0).is_ok(),
+            Program::Cff(data) => cff::Table::parse(data).is_some(),
+            Program::Type1(_) => true,
+        };
+        valid.then_some(program)
+    }
+
+    // Glyph of each code of a simple font, given the glyph names of its encoding
+    fn simple_glyphs(&self, names: &[Option<&str>]) -> Vec<Option<u16>> {
+        match self {
+            Program::Sfnt(data) => {
+                let Ok(face) = Face::parse(data, 0) else {
+                    return vec![None; 256];
+                };
+                let subtables: Vec<_> = face
+                    .tables()
+                    .cmap
+                    .map(|cmap| cmap.subtables.into_iter().collect())
+                    .unwrap_or_default();
+                let lookup = |platform: PlatformId, encoding: u16, code: u32| {
+                    subtables
+                        .iter()
+                        .filter(|table| {
+                            table.platform_id == platform && table.encoding_id == encoding
+                        })
+                        .find_map(|table| table.glyph_index(code))
+                };
+                names
+                    .iter()
+                    .enumerate()
+                    .map(|(code, name)| {
+                        let code = code as u32;
+                        // symbol fonts map the codes themselves, often moved to 0xF000
+                        [code, 0xF000 + code, 0xF100 + code, 0xF200 + code]
+                            .into_iter()
+                            .find_map(|code| lookup(PlatformId::Windows, 0, code))
+                            .or_else(|| {
+                                let name = (*name)?;
+                                unicode_for_name(name)
+                                    .and_then(|unicode| face.glyph_index(unicode))
+                                    .or_else(|| face.glyph_index_by_name(name))
+                            })
+                            .or_else(|| lookup(PlatformId::Macintosh, 0, code))
+                            .map(|glyph| glyph.0)
+                    })
+                    .collect()
+            }
+            Program::Cff(data) => {
+                let Some(table) = cff::Table::parse(data) else {
+                    return vec![None; 256];
+                };
+                names
+                    .iter()
+                    .enumerate()
+                    .map(|(code, name)| {
+                        name.and_then(|name| table.glyph_index_by_name(name))
+                            .or_else(|| table.glyph_index(code as u8))
+                            .map(|glyph| glyph.0)
+                    })
+                    .collect()
+            }
+            Program::Type1(font) => names
+                .iter()
+                .enumerate()
+                .map(|(code, name)| {
+                    name.and_then(|name| font.glyph_index_by_name(name))
+                        .or_else(|| font.glyph_index(code as u8))
+                })
+                .collect(),
+        }
+    }
+
+    // Outline of a glyph, one unit per em
+    fn outline(&self, glyph: u16) -> Option<Path> {
+        let mut outline = Outline(PathBuilder::new());
+        let transform = match self {
+            Program::Sfnt(data) => {
+                let face = Face::parse(data, 0).ok()?;
+                face.outline_glyph(GlyphId(glyph), &mut outline)?;
+                let scale = 1.0 / f32::from(face.units_per_em());
+                Transform::from_scale(scale, scale)
+            }
+            Program::Cff(data) => {
+                let table = cff::Table::parse(data)?;
+                table.outline(GlyphId(glyph), &mut outline).ok()?;
+                let m = table.matrix();
+                Transform::from_row(m.sx, m.ky, m.kx, m.sy, m.tx, m.ty)
+            }
+            Program::Type1(font) => return font.outline(glyph),
+        };
+        outline.0.finish()?.transform(transform)
+    }
+
+    // Advance of a glyph, one unit per em
+    fn advance(&self, glyph: u16) -> Option<f32> {
+        match self {
+            Program::Sfnt(data) => {
+                let face = Face::parse(data, 0).ok()?;
+                let advance = face.glyph_hor_advance(GlyphId(glyph))?;
+                Some(f32::from(advance) / f32::from(face.units_per_em()))
+            }
+            Program::Cff(data) => {
+                let table = cff::Table::parse(data)?;
+                Some(f32::from(table.glyph_width(GlyphId(glyph))?) * table.matrix().sx)
+            }
+            Program::Type1(font) => font.advance(glyph),
+        }
+    }
+}
+
+// Map the CIDs of a CID-keyed CFF program to its glyphs, other programs are
+// indexed by CID directly
+fn cff_charset(data: &[u8]) -> CidGlyphs {
+    let Some(table) = cff::Table::parse(data) else {
+        return CidGlyphs::Identity;
+    };
+    let charset: HashMap<u16, u16> = (0..table.number_of_glyphs())
+        .filter_map(|glyph| Some((table.glyph_cid(GlyphId(glyph))?, glyph)))
+        .collect();
+    if charset.is_empty() {
+        CidGlyphs::Identity
+    } else {
+        CidGlyphs::Charset(charset)
+    }
+}
+
+struct Outline(PathBuilder);
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.quad_to(x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.cubic_to(x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        self.0.close();
+    }
+}
+
+// Glyph names of the 256 codes of a simple font: the differences of its
+// encoding over the base encoding. StandardEncoding and MacRomanEncoding are
+// read as WinAnsiEncoding, they agree on letters and digits. Symbolic fonts
+// without a base encoding keep the built-in encoding of their program.
+fn glyph_names<'a>(
+    doc: &'a Document,
+    dict: &'a Dictionary,
+    symbolic: bool,
+) -> Vec<Option<&'a str>> {
+    let encoding = entry(doc, dict, b"Encoding");
+    let base = match encoding {
+        Some(Object::Name(_)) => true,
+        Some(Object::Dictionary(encoding)) => encoding.has(b"BaseEncoding") || !symbolic,
+        _ => !symbolic,
+    };
+    let mut names: Vec<Option<&str>> = (0..256)
+        .map(|code| match base {
+            true => win_ansi_name(code).filter(|name| !name.is_empty()),
+            false => None,
+        })
+        .collect();
+
+    let differences = match encoding {
+        Some(Object::Dictionary(encoding)) => {
+            numbers_or_names(doc, encoding.get(b"Differences").ok())
+        }
+        _ => Vec::new(),
+    };
+    let mut code = 0usize;
+    for difference in differences {
+        match difference {
+            Object::Integer(start) => code = (*start).clamp(0, 256) as usize,
+            Object::Name(name) => {
+                if let Some(slot) = names.get_mut(code) {
+                    *slot = std::str::from_utf8(name).ok();
+                }
+                code += 1;
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+fn numbers_or_names<'a>(doc: &'a Document, array: Option<&'a Object>) -> Vec<&'a Object> {
+    array
+        .and_then(|array| resolve(doc, array))
+        .and_then(|array| array.as_array().ok())
+        .map(|array| array.iter().filter_map(|item| resolve(doc, item)).collect())
+        .unwrap_or_default()
+}
+
+// Widths array of a simple font starting at FirstChar, scaled to a font size of 1
+fn simple_widths(doc: &Document, dict: &Dictionary, scale: f32) -> HashMap<u32, f32> {
+    let first = entry(doc, dict, b"FirstChar")
+        .and_then(|first| first.as_i64().ok())
+        .unwrap_or(0)
+        .clamp(0, 255) as u32;
+    numbers(doc, dict.get(b"Widths").ok())
+        .into_iter()
+        .zip(first..256)
+        .map(|(width, code)| (code, width * scale))
+        .collect()
+}
+
+// W array of a CID font, with runs `c [w1 w2 ...]` and ranges `c1 c2 w`
+fn cid_widths(doc: &Document, descendant: &Dictionary) -> HashMap<u32, f32> {
+    let items = numbers_or_names(doc, descendant.get(b"W").ok());
+    let mut widths = HashMap::new();
+    let mut i = 0;
+    while i < items.len() {
+        let Ok(first) = items[i].as_i64() else {
+            break;
+        };
+        let first = first.clamp(0, 0xFFFF) as u32;
+        match items.get(i + 1) {
+            Some(Object::Array(run)) => {
+                for (cid, width) in
+                    (first..).zip(run.iter().filter_map(|width| resolve(doc, width)))
+                {
+                    if let Ok(width) = width.as_float() {
+                        widths.insert(cid, width / 1000.0);
+                    }
+                }
+                i += 2;
+            }
+            Some(last) => {
+                let last = last.as_i64().unwrap_or(0).clamp(0, 0xFFFF) as u32;
+                if let Some(width) = items.get(i + 2).and_then(|width| width.as_float().ok()) {
+                    for cid in first..=last {
+                        widths.insert(cid, width / 1000.0);
+                    }
+                }
+                i += 3;
+            }
+            None => break,
+        }
+    }
+    widths
+}
+
+// Unicode of a glyph name of WinAnsiEncoding or of the uniXXXX form
+fn unicode_for_name(name: &str) -> Option<char> {
+    if let Some(code) = (32..256).find(|&code| win_ansi_name(code) == Some(name)) {
+        return win_ansi_unicode(code);
+    }
+    let hex = name
+        .strip_prefix("uni")
+        .or_else(|| name.strip_prefix('u'))?;
+    u32::from_str_radix(hex.get(..4)?, 16)
+        .ok()
+        .and_then(char::from_u32)
+}
+
+pub(crate) fn win_ansi_name(code: usize) -> Option<&'static str> {
+    code.checked_sub(32)
+        .and_then(|index| WIN_ANSI_NAMES.get(index))
+        .copied()
+}
+
+fn win_ansi_unicode(code: usize) -> Option<char> {
+    match code {
+        128..=159 => char::from_u32(WIN_ANSI_HIGH[code - 128]).filter(|&unicode| unicode != '\0'),
+        _ => char::from_u32(code as u32),
+    }
+}
+
+// Glyph names of WinAnsiEncoding from code 32, empty for unused codes
+const WIN_ANSI_NAMES: [&str; 224] = [
+    "space",
+    "exclam",
+    "quotedbl",
+    "numbersign",
+    "dollar",
+    "percent",
+    "ampersand",
+    "quotesingle",
+    "parenleft",
+    "parenright",
+    "asterisk",
+    "plus",
+    "comma",
+    "hyphen",
+    "period",
+    "slash",
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "colon",
+    "semicolon",
+    "less",
+    "equal",
+    "greater",
+    "question",
+    "at",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "bracketleft",
+    "backslash",
+    "bracketright",
+    "asciicircum",
+    "underscore",
+    "grave",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "braceleft",
+    "bar",
+    "braceright",
+    "asciitilde",
+    "",
+    "Euro",
+    "",
+    "quotesinglbase",
+    "florin",
+    "quotedblbase",
+    "ellipsis",
+    "dagger",
+    "daggerdbl",
+    "circumflex",
+    "perthousand",
+    "Scaron",
+    "guilsinglleft",
+    "OE",
+    "",
+    "Zcaron",
+    "",
+    "",
+    "quoteleft",
+    "quoteright",
+    "quotedblleft",
+    "quotedblright",
+    "bullet",
+    "endash",
+    "emdash",
+    "tilde",
+    "trademark",
+    "scaron",
+    "guilsinglright",
+    "oe",
+    "",
+    "zcaron",
+    "Ydieresis",
+    "space",
+    "exclamdown",
+    "cent",
+    "sterling",
+    "currency",
+    "yen",
+    "brokenbar",
+    "section",
+    "dieresis",
+    "copyright",
+    "ordfeminine",
+    "guillemotleft",
+    "logicalnot",
+    "hyphen",
+    "registered",
+    "macron",
+    "degree",
+    "plusminus",
+    "twosuperior",
+    "threesuperior",
+    "acute",
+    "mu",
+    "paragraph",
+    "periodcentered",
+    "cedilla",
+    "onesuperior",
+    "ordmasculine",
+    "guillemotright",
+    "onequarter",
+    "onehalf",
+    "threequarters",
+    "questiondown",
+    "Agrave",
+    "Aacute",
+    "Acircumflex",
+    "Atilde",
+    "Adieresis",
+    "Aring",
+    "AE",
+    "Ccedilla",
+    "Egrave",
+    "Eacute",
+    "Ecircumflex",
+    "Edieresis",
+    "Igrave",
+    "Iacute",
+    "Icircumflex",
+    "Idieresis",
+    "Eth",
+    "Ntilde",
+    "Ograve",
+    "Oacute",
+    "Ocircumflex",
+    "Otilde",
+    "Odieresis",
+    "multiply",
+    "Oslash",
+    "Ugrave",
+    "Uacute",
+    "Ucircumflex",
+    "Udieresis",
+    "Yacute",
+    "Thorn",
+    "germandbls",
+    "agrave",
+    "aacute",
+    "acircumflex",
+    "atilde",
+    "adieresis",
+    "aring",
+    "ae",
+    "ccedilla",
+    "egrave",
+    "eacute",
+    "ecircumflex",
+    "edieresis",
+    "igrave",
+    "iacute",
+    "icircumflex",
+    "idieresis",
+    "eth",
+    "ntilde",
+    "ograve",
+    "oacute",
+    "ocircumflex",
+    "otilde",
+    "odieresis",
+    "divide",
+    "oslash",
+    "ugrave",
+    "uacute",
+    "ucircumflex",
+    "udieresis",
+    "yacute",
+    "thorn",
+    "ydieresis",
+];
+
+// Unicode of WinAnsiEncoding codes 128 to 159, 0 for unused codes
+const WIN_ANSI_HIGH: [u32; 32] = [
+    0x20AC, 0, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0, 0x017D, 0, 0, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014, 0x02DC,
+    0x2122, 0x0161, 0x203A, 0x0153, 0, 0x017E, 0x0178,
+];
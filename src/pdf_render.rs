@@ -0,0 +1,1205 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use tiny_skia::{
+    BlendMode, Color, ColorU8, FillRule, FilterQuality, LineCap, LineJoin, Mask, Paint, Path,
+    PathBuilder, Pixmap, PixmapPaint, Stroke, StrokeDash, Transform,
+};
+
+use crate::pdf_font::Font;
+
+/// Size decoded streams may have, guards against decompression bombs
+pub(crate) const STREAM_LIMIT: usize = 256 << 20;
+
+// Images larger than this are not drawn
+const MAX_IMAGE_PIXELS: usize = 1 << 26;
+
+// Pages rasterizing to more pixels than this are an error rather than an
+// allocation the process may not survive
+const MAX_PAGE_PIXELS: usize = 1 << 28;
+
+// Form XObjects and Type3 glyphs nested deeper than this are not drawn, which
+// also stops reference cycles
+const MAX_DEPTH: usize = 16;
+
+/// Follow a reference, None for null and dangling references
+pub(crate) fn resolve<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Object> {
+    match doc.dereference(object) {
+        Ok((_, Object::Null)) | Err(_) => None,
+        Ok((_, object)) => Some(object),
+    }
+}
+
+/// Entry of a dictionary, following references
+pub(crate) fn entry<'a>(doc: &'a Document, dict: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+    resolve(doc, dict.get(key).ok()?)
+}
+
+/// Dictionary entry of a dictionary, the dictionary of a stream entry
+pub(crate) fn entry_dict<'a>(
+    doc: &'a Document,
+    dict: &'a Dictionary,
+    key: &[u8],
+) -> Option<&'a Dictionary> {
+    match entry(doc, dict, key)? {
+        Object::Dictionary(dict) => Some(dict),
+        Object::Stream(stream) => Some(&stream.dict),
+        _ => None,
+    }
+}
+
+/// Numbers of an array, following references
+pub(crate) fn numbers(doc: &Document, array: Option<&Object>) -> Vec<f32> {
+    array
+        .and_then(|array| resolve(doc, array))
+        .and_then(|array| array.as_array().ok())
+        .map(|array| {
+            array
+                .iter()
+                .filter_map(|number| resolve(doc, number)?.as_float().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Entry of a page, looked up in its ancestors for inheritable keys
+fn page_entry<'a>(doc: &'a Document, page: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+    let mut node = page;
+    for _ in 0..64 {
+        if let Some(value) = entry(doc, node, key) {
+            return Some(value);
+        }
+        node = entry(doc, node, b"Parent")?.as_dict().ok()?;
+    }
+    None
+}
+
+/// Rasterize a page on a white background, `dpi` pixels per inch
+pub(crate) fn render_page(
+    doc: &Document,
+    page_id: ObjectId,
+    dpi: f32,
+) -> Result<DynamicImage, String> {
+    let page = doc
+        .get_dictionary(page_id)
+        .map_err(|err| format!("Invalid page: {}", err))?;
+    let media_box = match numbers(doc, page_entry(doc, page, b"MediaBox"))[..] {
+        [x0, y0, x1, y1] => [x0, y0, x1, y1],
+        // US Letter
+        _ => [0.0, 0.0, 612.0, 792.0],
+    };
+    let [x0, y0, x1, y1] = match numbers(doc, page_entry(doc, page, b"CropBox"))[..] {
+        [x0, y0, x1, y1] => [x0, y0, x1, y1],
+        _ => media_box,
+    };
+    let (x0, x1) = (x0.min(x1), x0.max(x1));
+    let (y0, y1) = (y0.min(y1), y0.max(y1));
+    let rotation = page_entry(doc, page, b"Rotate")
+        .and_then(|rotate| rotate.as_i64().ok())
+        .unwrap_or(0)
+        .rem_euclid(360)
+        / 90
+        * 90;
+
+    // PDF units are 1/72 inch with the y axis pointing up, pages are rotated
+    // clockwise
+    let scale = dpi / 72.0;
+    let page_width = ((x1 - x0) * scale).round().max(1.0) as u32;
+    let page_height = ((y1 - y0) * scale).round().max(1.0) as u32;
+    let (width, height, ctm) = match rotation {
+        90 => (
+            page_height,
+            page_width,
+            Transform::from_row(0.0, scale, scale, 0.0, -y0 * scale, -x0 * scale),
+        ),
+        180 => (
+            page_width,
+            page_height,
+            Transform::from_row(-scale, 0.0, 0.0, scale, x1 * scale, -y0 * scale),
+        ),
+        270 => (
+            page_height,
+            page_width,
+            Transform::from_row(0.0, -scale, -scale, 0.0, y1 * scale, x1 * scale),
+        ),
+        _ => (
+            page_width,
+            page_height,
+            Transform::from_row(scale, 0.0, 0.0, -scale, -x0 * scale, y1 * scale),
+        ),
+    };
+    if width as usize * height as usize > MAX_PAGE_PIXELS {
+        return Err(format!(
+            "Page size {}x{} exceeds {} pixels, use a lower resolution",
+            width, height, MAX_PAGE_PIXELS
+        ));
+    }
+    let mut pixmap = Pixmap::new(width, height)
+        .ok_or_else(|| format!("Cannot rasterize page at {}x{}", width, height))?;
+    pixmap.fill(Color::WHITE);
+
+    let content = doc
+        .get_page_content_with_limit(page_id, STREAM_LIMIT)
+        .map_err(|err| format!("Invalid page content: {}", err))?;
+    let resources =
+        page_entry(doc, page, b"Resources").and_then(|resources| resources.as_dict().ok());
+    let mut renderer = Renderer {
+        doc,
+        pixmap,
+        fonts: HashMap::new(),
+        depth: 0,
+    };
+    renderer.run(&content, resources, GraphicsState::new(ctm))?;
+
+    // tiny-skia keeps premultiplied alpha
+    let pixels = renderer.pixmap.pixels();
+    Ok(DynamicImage::ImageRgba8(RgbaImage::from_fn(
+        width,
+        height,
+        |x, y| {
+            let color = pixels[y as usize * width as usize + x as usize].demultiply();
+            Rgba([color.red(), color.green(), color.blue(), color.alpha()])
+        },
+    )))
+}
+
+// Color spaces, converted to RGB when painting
+#[derive(Clone, Debug, PartialEq)]
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    /// Base space and its palette, as bytes
+    Indexed(Box<ColorSpace>, Vec<u8>),
+    /// Separation and DeviceN colorants, drawn as gray levels of the first tint
+    Tint(usize),
+    /// Lab, drawn as gray levels of its lightness
+    Lab,
+    /// Patterns and shadings are not drawn
+    Pattern,
+}
+
+impl ColorSpace {
+    fn parse(
+        doc: &Document,
+        object: &Object,
+        resources: Option<&Dictionary>,
+        depth: usize,
+    ) -> ColorSpace {
+        let object = match resolve(doc, object) {
+            Some(object) if depth < 4 => object,
+            _ => return ColorSpace::Gray,
+        };
+        let (family, params) = match object {
+            Object::Name(name) => (name.as_slice(), &[][..]),
+            Object::Array(array) => match array.first().and_then(|family| family.as_name().ok()) {
+                Some(family) => (family, &array[1..]),
+                None => return ColorSpace::Gray,
+            },
+            _ => return ColorSpace::Gray,
+        };
+        let param = |index: usize| params.get(index).and_then(|param| resolve(doc, param));
+
+        match family {
+            b"DeviceGray" | b"G" | b"CalGray" => ColorSpace::Gray,
+            b"DeviceRGB" | b"RGB" | b"CalRGB" => ColorSpace::Rgb,
+            b"DeviceCMYK" | b"CMYK" => ColorSpace::Cmyk,
+            b"Lab" => ColorSpace::Lab,
+            b"Pattern" => ColorSpace::Pattern,
+            b"ICCBased" => {
+                let components = param(0)
+                    .and_then(|profile| profile.as_stream().ok())
+                    .and_then(|profile| entry(doc, &profile.dict, b"N"))
+                    .and_then(|n| n.as_i64().ok());
+                match components {
+                    Some(4) => ColorSpace::Cmyk,
+                    Some(3) => ColorSpace::Rgb,
+                    _ => ColorSpace::Gray,
+                }
+            }
+            b"Indexed" | b"I" => {
+                let base = params
+                    .first()
+                    .map(|base| ColorSpace::parse(doc, base, resources, depth + 1))
+                    .unwrap_or(ColorSpace::Gray);
+                let palette = match param(2) {
+                    Some(Object::String(palette, _)) => palette.clone(),
+                    Some(Object::Stream(palette)) => palette
+                        .decompressed_content_with_limit(STREAM_LIMIT)
+                        .unwrap_or_default(),
+                    _ => Vec::new(),
+                };
+                ColorSpace::Indexed(Box::new(base), palette)
+            }
+            b"Separation" => ColorSpace::Tint(1),
+            b"DeviceN" => ColorSpace::Tint(
+                param(0)
+                    .and_then(|names| names.as_array().ok())
+                    .map_or(1, |names| names.len().max(1)),
+            ),
+            name => resources
+                .and_then(|resources| entry_dict(doc, resources, b"ColorSpace"))
+                .and_then(|spaces| spaces.get(name).ok())
+                .map(|space| ColorSpace::parse(doc, space, resources, depth + 1))
+                .unwrap_or(ColorSpace::Gray),
+        }
+    }
+
+    fn components(&self) -> usize {
+        match self {
+            ColorSpace::Gray | ColorSpace::Indexed(..) => 1,
+            ColorSpace::Rgb | ColorSpace::Lab => 3,
+            ColorSpace::Cmyk => 4,
+            ColorSpace::Tint(colorants) => *colorants,
+            ColorSpace::Pattern => 0,
+        }
+    }
+
+    fn initial_color(&self) -> Vec<f32> {
+        match self {
+            ColorSpace::Cmyk => vec![0.0, 0.0, 0.0, 1.0],
+            ColorSpace::Tint(colorants) => vec![1.0; *colorants],
+            space => vec![0.0; space.components()],
+        }
+    }
+
+    fn to_rgb(&self, values: &[f32]) -> Option<[f32; 3]> {
+        let value = |index: usize| values.get(index).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+        match self {
+            ColorSpace::Gray => Some([value(0); 3]),
+            ColorSpace::Rgb => Some([value(0), value(1), value(2)]),
+            ColorSpace::Cmyk => {
+                let black = 1.0 - value(3);
+                Some([
+                    (1.0 - value(0)) * black,
+                    (1.0 - value(1)) * black,
+                    (1.0 - value(2)) * black,
+                ])
+            }
+            ColorSpace::Indexed(base, palette) => {
+                let count = base.components();
+                let index = values.first().copied().unwrap_or(0.0).round().max(0.0) as usize;
+                let entry = palette.get(index * count..(index + 1) * count)?;
+                let base_values: Vec<f32> =
+                    entry.iter().map(|&byte| f32::from(byte) / 255.0).collect();
+                base.to_rgb(&base_values)
+            }
+            ColorSpace::Tint(_) => Some([1.0 - value(0); 3]),
+            ColorSpace::Lab => {
+                let lightness = (values.first().copied().unwrap_or(0.0) / 100.0).clamp(0.0, 1.0);
+                Some([lightness; 3])
+            }
+            ColorSpace::Pattern => None,
+        }
+    }
+
+    // Range of the samples of an image in this space, before its Decode array
+    fn default_decode(&self, bits_per_component: u32) -> Vec<f32> {
+        match self {
+            ColorSpace::Indexed(..) => vec![0.0, ((1u32 << bits_per_component) - 1) as f32],
+            ColorSpace::Lab => vec![0.0, 100.0, -128.0, 127.0, -128.0, 127.0],
+            space => [0.0, 1.0].repeat(space.components()),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct GraphicsState<'a> {
+    ctm: Transform,
+    /// Device pixels painting reaches
+    clip: Option<Rc<Mask>>,
+    fill_space: ColorSpace,
+    /// None while a pattern is selected
+    fill: Option<[f32; 3]>,
+    stroke_space: ColorSpace,
+    stroke: Option<[f32; 3]>,
+    fill_alpha: f32,
+    stroke_alpha: f32,
+    line_width: f32,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    miter_limit: f32,
+    dash: Option<(Vec<f32>, f32)>,
+    font: Option<Rc<Font<'a>>>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+    leading: f32,
+    rise: f32,
+    render_mode: i64,
+}
+
+impl GraphicsState<'_> {
+    fn new(ctm: Transform) -> Self {
+        GraphicsState {
+            ctm,
+            clip: None,
+            fill_space: ColorSpace::Gray,
+            fill: Some([0.0; 3]),
+            stroke_space: ColorSpace::Gray,
+            stroke: Some([0.0; 3]),
+            fill_alpha: 1.0,
+            stroke_alpha: 1.0,
+            line_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 10.0,
+            dash: None,
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            render_mode: 0,
+        }
+    }
+
+    fn set_line_cap(&mut self, cap: i64) {
+        self.line_cap = match cap {
+            1 => LineCap::Round,
+            2 => LineCap::Square,
+            _ => LineCap::Butt,
+        };
+    }
+
+    fn set_line_join(&mut self, join: i64) {
+        self.line_join = match join {
+            1 => LineJoin::Round,
+            2 => LineJoin::Bevel,
+            _ => LineJoin::Miter,
+        };
+    }
+
+    fn set_dash(&mut self, doc: &Document, array: Option<&Object>, phase: f32) {
+        let array = numbers(doc, array);
+        self.dash = (!array.is_empty()).then_some((array, phase));
+    }
+
+    // Restrict the clip to a path, in user space
+    fn clip_path(&mut self, path: Option<&Path>, rule: FillRule, width: u32, height: u32) {
+        let mask = match (self.clip.take(), path) {
+            (Some(clip), Some(path)) => {
+                let mut mask = Rc::unwrap_or_clone(clip);
+                mask.intersect_path(path, rule, true, self.ctm);
+                Some(mask)
+            }
+            (None, Some(path)) => Mask::new(width, height).map(|mut mask| {
+                mask.fill_path(path, rule, true, self.ctm);
+                mask
+            }),
+            // clipping to an empty path hides everything
+            (_, None) => Mask::new(width, height),
+        };
+        self.clip = mask.map(Rc::new);
+    }
+}
+
+// Text and line matrices of a text object
+struct TextObject {
+    matrix: Transform,
+    line_matrix: Transform,
+}
+
+struct Renderer<'a> {
+    doc: &'a Document,
+    pixmap: Pixmap,
+    fonts: HashMap<ObjectId, Rc<Font<'a>>>,
+    depth: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn run(
+        &mut self,
+        content: &[u8],
+        resources: Option<&'a Dictionary>,
+        state: GraphicsState<'a>,
+    ) -> Result<(), String> {
+        let doc = self.doc;
+        let content =
+            Content::decode(content).map_err(|err| format!("Invalid content stream: {}", err))?;
+        let (width, height) = (self.pixmap.width(), self.pixmap.height());
+        let mut state = state;
+        let mut saved = Vec::new();
+        let mut path = PathBuilder::new();
+        let mut current = (0.0, 0.0);
+        let mut pending_clip = None;
+        let mut text = TextObject {
+            matrix: Transform::identity(),
+            line_matrix: Transform::identity(),
+        };
+
+        for operation in &content.operations {
+            let operands = &operation.operands;
+            let n: Vec<f32> = operands
+                .iter()
+                .filter_map(|operand| operand.as_float().ok())
+                .collect();
+            let name = || operands.first().and_then(|operand| operand.as_name().ok());
+            match (operation.operator.as_str(), &n[..]) {
+                // graphics state
+                ("q", _) => saved.push(state.clone()),
+                ("Q", _) => {
+                    if let Some(restored) = saved.pop() {
+                        state = restored;
+                    }
+                }
+                ("cm", &[a, b, c, d, e, f]) => {
+                    state.ctm = state.ctm.pre_concat(Transform::from_row(a, b, c, d, e, f))
+                }
+                ("w", &[line_width]) => state.line_width = line_width,
+                ("J", &[cap]) => state.set_line_cap(cap as i64),
+                ("j", &[join]) => state.set_line_join(join as i64),
+                ("M", &[miter_limit]) => state.miter_limit = miter_limit,
+                ("d", &[phase]) => state.set_dash(doc, operands.first(), phase),
+                ("gs", _) => {
+                    let params = resources
+                        .and_then(|resources| entry_dict(doc, resources, b"ExtGState"))
+                        .zip(name())
+                        .and_then(|(states, name)| entry_dict(doc, states, name));
+                    if let Some(params) = params {
+                        self.set_params(params, &mut state);
+                    }
+                }
+
+                // paths
+                ("m", &[x, y]) => {
+                    path.move_to(x, y);
+                    current = (x, y);
+                }
+                ("l", &[x, y]) => {
+                    path.line_to(x, y);
+                    current = (x, y);
+                }
+                ("c", &[x1, y1, x2, y2, x, y]) => {
+                    path.cubic_to(x1, y1, x2, y2, x, y);
+                    current = (x, y);
+                }
+                ("v", &[x2, y2, x, y]) => {
+                    path.cubic_to(current.0, current.1, x2, y2, x, y);
+                    current = (x, y);
+                }
+                ("y", &[x1, y1, x, y]) => {
+                    path.cubic_to(x1, y1, x, y, x, y);
+                    current = (x, y);
+                }
+                ("h", _) => path.close(),
+                ("re", &[x, y, w, h]) => {
+                    path.move_to(x, y);
+                    path.line_to(x + w, y);
+                    path.line_to(x + w, y + h);
+                    path.line_to(x, y + h);
+                    path.close();
+                    current = (x, y);
+                }
+                ("W", _) => pending_clip = Some(FillRule::Winding),
+                ("W*", _) => pending_clip = Some(FillRule::EvenOdd),
+                (operator @ ("S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "n"), _) => {
+                    if matches!(operator, "s" | "b" | "b*") {
+                        path.close();
+                    }
+                    let finished = std::mem::take(&mut path).finish();
+                    if let Some(finished) = &finished {
+                        let rule = match operator {
+                            "f*" | "B*" | "b*" => FillRule::EvenOdd,
+                            _ => FillRule::Winding,
+                        };
+                        if matches!(operator, "f" | "F" | "f*" | "B" | "B*" | "b" | "b*") {
+                            self.fill(finished, rule, &state);
+                        }
+                        if matches!(operator, "S" | "s" | "B" | "B*" | "b" | "b*") {
+                            self.stroke(finished, &state);
+                        }
+                    }
+                    if let Some(rule) = pending_clip.take() {
+                        state.clip_path(finished.as_ref(), rule, width, height);
+                    }
+                }
+
+                // colors
+                ("CS", _) => {
+                    if let Some(space) = operands.first() {
+                        state.stroke_space = ColorSpace::parse(doc, space, resources, 0);
+                        state.stroke = state
+                            .stroke_space
+                            .to_rgb(&state.stroke_space.initial_color());
+                    }
+                }
+                ("cs", _) => {
+                    if let Some(space) = operands.first() {
+                        state.fill_space = ColorSpace::parse(doc, space, resources, 0);
+                        state.fill = state.fill_space.to_rgb(&state.fill_space.initial_color());
+                    }
+                }
+                ("SC" | "SCN", values) => state.stroke = state.stroke_space.to_rgb(values),
+                ("sc" | "scn", values) => state.fill = state.fill_space.to_rgb(values),
+                ("G", values) => {
+                    state.stroke_space = ColorSpace::Gray;
+                    state.stroke = ColorSpace::Gray.to_rgb(values);
+                }
+                ("g", values) => {
+                    state.fill_space = ColorSpace::Gray;
+                    state.fill = ColorSpace::Gray.to_rgb(values);
+                }
+                ("RG", values) => {
+                    state.stroke_space = ColorSpace::Rgb;
+                    state.stroke = ColorSpace::Rgb.to_rgb(values);
+                }
+                ("rg", values) => {
+                    state.fill_space = ColorSpace::Rgb;
+                    state.fill = ColorSpace::Rgb.to_rgb(values);
+                }
+                ("K", values) => {
+                    state.stroke_space = ColorSpace::Cmyk;
+                    state.stroke = ColorSpace::Cmyk.to_rgb(values);
+                }
+                ("k", values) => {
+                    state.fill_space = ColorSpace::Cmyk;
+                    state.fill = ColorSpace::Cmyk.to_rgb(values);
+                }
+
+                // external objects and inline images
+                ("Do", _) => {
+                    let object = resources
+                        .and_then(|resources| entry_dict(doc, resources, b"XObject"))
+                        .zip(name())
+                        .and_then(|(objects, name)| entry(doc, objects, name))
+                        .and_then(|object| object.as_stream().ok());
+                    if let Some(object) = object {
+                        match object.dict.get(b"Subtype").and_then(Object::as_name) {
+                            Ok(b"Image") => self.draw_image(object, resources, &state),
+                            Ok(b"Form") => self.draw_form(object, resources, &state)?,
+                            _ => {}
+                        }
+                    }
+                }
+                ("BI", _) => {
+                    if let Some(Object::Stream(image)) = operands.first() {
+                        self.draw_image(image, resources, &state);
+                    }
+                }
+
+                // text
+                ("BT", _) => {
+                    text.matrix = Transform::identity();
+                    text.line_matrix = Transform::identity();
+                }
+                ("Tc", &[spacing]) => state.char_spacing = spacing,
+                ("Tw", &[spacing]) => state.word_spacing = spacing,
+                ("Tz", &[scale]) => state.horizontal_scale = scale / 100.0,
+                ("TL", &[leading]) => state.leading = leading,
+                ("Ts", &[rise]) => state.rise = rise,
+                ("Tr", &[mode]) => state.render_mode = mode as i64,
+                ("Tf", &[size]) => {
+                    state.font_size = size;
+                    state.font = name().and_then(|name| self.font(resources, name));
+                }
+                ("Td", &[x, y]) => text.next_line(x, y),
+                ("TD", &[x, y]) => {
+                    state.leading = -y;
+                    text.next_line(x, y);
+                }
+                ("Tm", &[a, b, c, d, e, f]) => {
+                    text.matrix = Transform::from_row(a, b, c, d, e, f);
+                    text.line_matrix = text.matrix;
+                }
+                ("T*", _) => text.next_line(0.0, -state.leading),
+                ("Tj", _) => {
+                    if let Some(Object::String(bytes, _)) = operands.first() {
+                        self.show_text(bytes, resources, &state, &mut text)?;
+                    }
+                }
+                ("'", _) => {
+                    text.next_line(0.0, -state.leading);
+                    if let Some(Object::String(bytes, _)) = operands.first() {
+                        self.show_text(bytes, resources, &state, &mut text)?;
+                    }
+                }
+                ("\"", &[word_spacing, char_spacing]) => {
+                    state.word_spacing = word_spacing;
+                    state.char_spacing = char_spacing;
+                    text.next_line(0.0, -state.leading);
+                    if let Some(Object::String(bytes, _)) = operands.get(2) {
+                        self.show_text(bytes, resources, &state, &mut text)?;
+                    }
+                }
+                ("TJ", _) => {
+                    let items = operands.first().and_then(|items| items.as_array().ok());
+                    for item in items.into_iter().flatten() {
+                        match item {
+                            Object::String(bytes, _) => {
+                                self.show_text(bytes, resources, &state, &mut text)?
+                            }
+                            item => {
+                                if let Ok(adjustment) = item.as_float() {
+                                    let shift = -adjustment / 1000.0
+                                        * state.font_size
+                                        * state.horizontal_scale;
+                                    text.matrix = text
+                                        .matrix
+                                        .pre_concat(Transform::from_translate(shift, 0.0));
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // shadings, marked content, compatibility sections and
+                // operators with missing operands
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Parameters of an ExtGState dictionary
+    fn set_params(&mut self, params: &'a Dictionary, state: &mut GraphicsState<'a>) {
+        let doc = self.doc;
+        let number = |key: &[u8]| entry(doc, params, key).and_then(|value| value.as_float().ok());
+        if let Some(line_width) = number(b"LW") {
+            state.line_width = line_width;
+        }
+        if let Some(cap) = number(b"LC") {
+            state.set_line_cap(cap as i64);
+        }
+        if let Some(join) = number(b"LJ") {
+            state.set_line_join(join as i64);
+        }
+        if let Some(miter_limit) = number(b"ML") {
+            state.miter_limit = miter_limit;
+        }
+        if let Some(Object::Array(dash)) = entry(doc, params, b"D") {
+            let phase = dash
+                .get(1)
+                .and_then(|phase| phase.as_float().ok())
+                .unwrap_or(0.0);
+            state.set_dash(doc, dash.first(), phase);
+        }
+        if let Some(alpha) = number(b"CA") {
+            state.stroke_alpha = alpha.clamp(0.0, 1.0);
+        }
+        if let Some(alpha) = number(b"ca") {
+            state.fill_alpha = alpha.clamp(0.0, 1.0);
+        }
+        if let Some(Object::Array(font)) = entry(doc, params, b"Font") {
+            state.font = font
+                .first()
+                .and_then(|font| font.as_reference().ok())
+                .and_then(|id| self.load_font(id));
+            if let Some(size) = font.get(1).and_then(|size| size.as_float().ok()) {
+                state.font_size = size;
+            }
+        }
+    }
+
+    fn fill(&mut self, path: &Path, rule: FillRule, state: &GraphicsState) {
+        if let Some(color) = state.fill {
+            let paint = paint(color, state.fill_alpha);
+            self.pixmap
+                .fill_path(path, &paint, rule, state.ctm, state.clip.as_deref());
+        }
+    }
+
+    fn stroke(&mut self, path: &Path, state: &GraphicsState) {
+        if let Some(color) = state.stroke {
+            let paint = paint(color, state.stroke_alpha);
+            // a width of 0 is the thinnest line the device draws, as in PDF
+            let stroke = Stroke {
+                width: state.line_width.max(0.0),
+                miter_limit: state.miter_limit,
+                line_cap: state.line_cap,
+                line_join: state.line_join,
+                dash: state
+                    .dash
+                    .clone()
+                    .and_then(|(array, phase)| StrokeDash::new(array, phase)),
+            };
+            self.pixmap
+                .stroke_path(path, &paint, &stroke, state.ctm, state.clip.as_deref());
+        }
+    }
+
+    fn font(&mut self, resources: Option<&'a Dictionary>, name: &[u8]) -> Option<Rc<Font<'a>>> {
+        let fonts = entry_dict(self.doc, resources?, b"Font")?;
+        match fonts.get(name).ok()? {
+            Object::Reference(id) => self.load_font(*id),
+            Object::Dictionary(font) => Some(Rc::new(Font::load(self.doc, font))),
+            _ => None,
+        }
+    }
+
+    fn load_font(&mut self, id: ObjectId) -> Option<Rc<Font<'a>>> {
+        if let Some(font) = self.fonts.get(&id) {
+            return Some(font.clone());
+        }
+        let font = Rc::new(Font::load(self.doc, self.doc.get_dictionary(id).ok()?));
+        self.fonts.insert(id, font.clone());
+        Some(font)
+    }
+
+    fn show_text(
+        &mut self,
+        bytes: &[u8],
+        resources: Option<&'a Dictionary>,
+        state: &GraphicsState<'a>,
+        text: &mut TextObject,
+    ) -> Result<(), String> {
+        let Some(font) = state.font.clone() else {
+            return Ok(());
+        };
+        let mode = state.render_mode;
+        // modes 4 to 7 add the glyphs to the clip, which is not done
+        let fills = matches!(mode, 0 | 2 | 4 | 6);
+        let strokes = matches!(mode, 1 | 2 | 5 | 6);
+
+        for code in font.codes(bytes) {
+            let glyph_space = text.matrix.pre_concat(Transform::from_row(
+                state.font_size * state.horizontal_scale,
+                0.0,
+                0.0,
+                state.font_size,
+                0.0,
+                state.rise,
+            ));
+            if font.is_type3() {
+                if let Some((proc, matrix, glyph_resources)) = font.type3_glyph(code) {
+                    self.draw_type3_glyph(
+                        proc,
+                        glyph_resources.or(resources),
+                        state,
+                        glyph_space.pre_concat(matrix),
+                    )?;
+                }
+            } else if fills || strokes {
+                let outline = font
+                    .outline(code)
+                    .and_then(|outline| outline.transform(glyph_space));
+                if let Some(outline) = outline {
+                    if fills {
+                        self.fill(&outline, FillRule::Winding, state);
+                    }
+                    if strokes {
+                        self.stroke(&outline, state);
+                    }
+                }
+            }
+
+            let word_spacing = match font.is_space(code) {
+                true => state.word_spacing,
+                false => 0.0,
+            };
+            let advance = (font.width(code) * state.font_size + state.char_spacing + word_spacing)
+                * state.horizontal_scale;
+            text.matrix = text
+                .matrix
+                .pre_concat(Transform::from_translate(advance, 0.0));
+        }
+        Ok(())
+    }
+
+    fn draw_type3_glyph(
+        &mut self,
+        proc: &'a Stream,
+        resources: Option<&'a Dictionary>,
+        state: &GraphicsState<'a>,
+        glyph_space: Transform,
+    ) -> Result<(), String> {
+        if self.depth >= MAX_DEPTH {
+            return Ok(());
+        }
+        let Ok(content) = proc.decompressed_content_with_limit(STREAM_LIMIT) else {
+            return Ok(());
+        };
+        let content = skip_glyph_metrics(&content);
+        let mut glyph_state = state.clone();
+        glyph_state.ctm = state.ctm.pre_concat(glyph_space);
+        self.depth += 1;
+        let result = self.run(content, resources, glyph_state);
+        self.depth -= 1;
+        result
+    }
+
+    fn draw_form(
+        &mut self,
+        form: &'a Stream,
+        resources: Option<&'a Dictionary>,
+        state: &GraphicsState<'a>,
+    ) -> Result<(), String> {
+        if self.depth >= MAX_DEPTH {
+            return Ok(());
+        }
+        let Ok(content) = form.decompressed_content_with_limit(STREAM_LIMIT) else {
+            return Ok(());
+        };
+        let doc = self.doc;
+        let mut form_state = state.clone();
+        if let [a, b, c, d, e, f] = numbers(doc, form.dict.get(b"Matrix").ok())[..] {
+            form_state.ctm = state.ctm.pre_concat(Transform::from_row(a, b, c, d, e, f));
+        }
+        if let [x0, y0, x1, y1] = numbers(doc, form.dict.get(b"BBox").ok())[..] {
+            let mut bbox = PathBuilder::new();
+            bbox.move_to(x0, y0);
+            bbox.line_to(x1, y0);
+            bbox.line_to(x1, y1);
+            bbox.line_to(x0, y1);
+            bbox.close();
+            form_state.clip_path(
+                bbox.finish().as_ref(),
+                FillRule::Winding,
+                self.pixmap.width(),
+                self.pixmap.height(),
+            );
+        }
+        let form_resources = entry_dict(doc, &form.dict, b"Resources").or(resources);
+
+        self.depth += 1;
+        let result = self.run(&content, form_resources, form_state);
+        self.depth -= 1;
+        result
+    }
+
+    // Images fill the unit square of user space
+    fn draw_image(
+        &mut self,
+        image: &Stream,
+        resources: Option<&Dictionary>,
+        state: &GraphicsState,
+    ) {
+        let Some(pixmap) = decode_image(self.doc, image, resources, state.fill) else {
+            return;
+        };
+        let (width, height) = (pixmap.width() as f32, pixmap.height() as f32);
+        let transform = state.ctm.pre_concat(Transform::from_row(
+            1.0 / width,
+            0.0,
+            0.0,
+            -1.0 / height,
+            0.0,
+            1.0,
+        ));
+        let paint = PixmapPaint {
+            opacity: state.fill_alpha,
+            blend_mode: BlendMode::SourceOver,
+            quality: FilterQuality::Bilinear,
+        };
+        self.pixmap.draw_pixmap(
+            0,
+            0,
+            pixmap.as_ref(),
+            &paint,
+            transform,
+            state.clip.as_deref(),
+        );
+    }
+}
+
+// Type3 glyphs start with the d0 or d1 operator, which lopdf reads as d
+// followed by a number, so it is skipped with its operands
+fn skip_glyph_metrics(content: &[u8]) -> &[u8] {
+    let boundary = |byte: Option<&u8>| byte.is_none_or(|byte| byte.is_ascii_whitespace());
+    (0..content.len().saturating_sub(1))
+        .find(|&index| {
+            content[index] == b'd'
+                && matches!(content[index + 1], b'0' | b'1')
+                && boundary(index.checked_sub(1).and_then(|before| content.get(before)))
+                && boundary(content.get(index + 2))
+        })
+        .map_or(content, |index| &content[index + 2..])
+}
+
+impl TextObject {
+    fn next_line(&mut self, x: f32, y: f32) {
+        self.line_matrix = self.line_matrix.pre_concat(Transform::from_translate(x, y));
+        self.matrix = self.line_matrix;
+    }
+}
+
+fn paint(color: [f32; 3], alpha: f32) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(Color::from_rgba(color[0], color[1], color[2], alpha).unwrap_or(Color::BLACK));
+    paint.anti_alias = true;
+    paint
+}
+
+// Entry of an image dictionary, inline images abbreviate the keys
+fn image_entry<'b>(
+    doc: &'b Document,
+    dict: &'b Dictionary,
+    key: &[u8],
+    short: &[u8],
+) -> Option<&'b Object> {
+    entry(doc, dict, key).or_else(|| entry(doc, dict, short))
+}
+
+// Samples of an image, each row starts on a byte boundary
+struct Samples {
+    data: Vec<u8>,
+    bits: u32,
+    row_length: usize,
+}
+
+impl Samples {
+    fn new(
+        mut data: Vec<u8>,
+        bits: u32,
+        width: usize,
+        height: usize,
+        components: usize,
+    ) -> Option<Samples> {
+        if !matches!(bits, 1 | 2 | 4 | 8 | 16) {
+            return None;
+        }
+        let row_length = (width * components * bits as usize).div_ceil(8);
+        // short data is padded, as PDF readers do
+        data.resize(row_length * height, 0);
+        Some(Samples {
+            data,
+            bits,
+            row_length,
+        })
+    }
+
+    fn get(&self, row: usize, index: usize) -> u32 {
+        let start = row * self.row_length;
+        match self.bits {
+            8 => u32::from(self.data[start + index]),
+            16 => u32::from(u16::from_be_bytes([
+                self.data[start + 2 * index],
+                self.data[start + 2 * index + 1],
+            ])),
+            bits => {
+                let bit = index * bits as usize;
+                let byte = self.data[start + bit / 8];
+                u32::from(byte >> (8 - bits as usize - bit % 8)) & ((1 << bits) - 1)
+            }
+        }
+    }
+
+    fn max(&self) -> f32 {
+        ((1u32 << self.bits) - 1) as f32
+    }
+}
+
+// Image data before the DCT filter, which the image crate decodes, and
+// whether that filter is applied. CCITT, JBIG2 and JPEG 2000 images are not
+// decoded.
+fn image_data(doc: &Document, image: &Stream) -> Option<(Vec<u8>, bool)> {
+    let mut filters: Vec<Vec<u8>> = match image_entry(doc, &image.dict, b"Filter", b"F") {
+        Some(Object::Name(filter)) => vec![filter.clone()],
+        Some(Object::Array(filters)) => filters
+            .iter()
+            .filter_map(|filter| Some(filter.as_name().ok()?.to_vec()))
+            .collect(),
+        _ => Vec::new(),
+    };
+    for filter in &mut filters {
+        let full: &[u8] = match filter.as_slice() {
+            b"AHx" => b"ASCIIHexDecode",
+            b"A85" => b"ASCII85Decode",
+            b"LZW" => b"LZWDecode",
+            b"Fl" => b"FlateDecode",
+            b"RL" => b"RunLengthDecode",
+            b"DCT" => b"DCTDecode",
+            _ => continue,
+        };
+        *filter = full.to_vec();
+    }
+    let jpeg = filters.last().is_some_and(|filter| filter == b"DCTDecode");
+    if jpeg {
+        filters.pop();
+    }
+    if filters.is_empty() {
+        return Some((image.content.clone(), jpeg));
+    }
+
+    let mut dict = Dictionary::new();
+    dict.set(
+        "Filter",
+        Object::Array(filters.into_iter().map(Object::Name).collect()),
+    );
+    let params = match image_entry(doc, &image.dict, b"DecodeParms", b"DP") {
+        Some(Object::Array(params)) => params.iter().find(|params| params.as_dict().is_ok()),
+        params => params,
+    };
+    if let Some(params) = params {
+        dict.set("DecodeParms", params.clone());
+    }
+    let data = Stream::new(dict, image.content.clone())
+        .decompressed_content_with_limit(STREAM_LIMIT)
+        .ok()?;
+    Some((data, jpeg))
+}
+
+// Decode an image or inline image into RGBA pixels. Stencil masks are painted
+// with the fill color, soft masks and stencil masks of other images become
+// their alpha.
+fn decode_image(
+    doc: &Document,
+    image: &Stream,
+    resources: Option<&Dictionary>,
+    fill: Option<[f32; 3]>,
+) -> Option<Pixmap> {
+    let dict = &image.dict;
+    let dimension = |key: &[u8], short: &[u8]| -> Option<usize> {
+        let value = image_entry(doc, dict, key, short)?.as_i64().ok()?;
+        usize::try_from(value).ok().filter(|&value| value > 0)
+    };
+    let width = dimension(b"Width", b"W")?;
+    let height = dimension(b"Height", b"H")?;
+    if width.checked_mul(height)? > MAX_IMAGE_PIXELS {
+        return None;
+    }
+    let stencil = image_entry(doc, dict, b"ImageMask", b"IM")
+        .and_then(|mask| mask.as_bool().ok())
+        .unwrap_or(false);
+    let (data, jpeg) = image_data(doc, image)?;
+    let decode = numbers(doc, image_entry(doc, dict, b"Decode", b"D"));
+
+    let mut rgba = vec![0u8; width * height * 4];
+    if stencil {
+        let color = fill?;
+        let samples = Samples::new(data, 1, width, height, 1)?;
+        // samples of 0 are painted, unless the Decode array is [1 0]
+        let painted = u32::from(decode.first().is_some_and(|&first| first > 0.5));
+        let color = color.map(|value| (value * 255.0).round() as u8);
+        for (index, pixel) in rgba.chunks_exact_mut(4).enumerate() {
+            if samples.get(index / width, index % width) == painted {
+                pixel.copy_from_slice(&[color[0], color[1], color[2], 255]);
+            }
+        }
+    } else if jpeg {
+        let decoded = image::load_from_memory_with_format(&data, image::ImageFormat::Jpeg)
+            .ok()?
+            .to_rgba8();
+        if decoded.dimensions() != (width as u32, height as u32) {
+            return None;
+        }
+        rgba = decoded.into_raw();
+    } else {
+        let space = image_entry(doc, dict, b"ColorSpace", b"CS")
+            .map(|space| ColorSpace::parse(doc, space, resources, 0))
+            .unwrap_or(ColorSpace::Gray);
+        let components = space.components();
+        let bits = image_entry(doc, dict, b"BitsPerComponent", b"BPC")
+            .and_then(|bits| bits.as_i64().ok())
+            .unwrap_or(8) as u32;
+        if components == 0 || components > 32 {
+            return None;
+        }
+        let samples = Samples::new(data, bits, width, height, components)?;
+        let decode = match decode.len() >= 2 * components {
+            true => decode,
+            false => space.default_decode(bits),
+        };
+        let color_key = numbers(doc, entry(doc, dict, b"Mask"));
+
+        let mut values = [0.0f32; 32];
+        let mut raw = [0u32; 32];
+        for (index, pixel) in rgba.chunks_exact_mut(4).enumerate() {
+            let (row, column) = (index / width, index % width);
+            for component in 0..components {
+                let sample = samples.get(row, column * components + component);
+                raw[component] = sample;
+                let (low, high) = (decode[2 * component], decode[2 * component + 1]);
+                values[component] = low + sample as f32 * (high - low) / samples.max();
+            }
+            let rgb = space.to_rgb(&values[..components]).unwrap_or([0.0; 3]);
+            // color key masking hides samples inside the ranges of the Mask array
+            let masked = color_key.len() >= 2 * components
+                && (0..components).all(|component| {
+                    let sample = raw[component] as f32;
+                    color_key[2 * component] <= sample && sample <= color_key[2 * component + 1]
+                });
+            let rgb = rgb.map(|value| (value * 255.0).round() as u8);
+            pixel.copy_from_slice(&[rgb[0], rgb[1], rgb[2], if masked { 0 } else { 255 }]);
+        }
+    }
+
+    if !stencil {
+        let soft_mask = entry(doc, dict, b"SMask").and_then(|mask| mask.as_stream().ok());
+        let stencil_mask = entry(doc, dict, b"Mask").and_then(|mask| mask.as_stream().ok());
+        let alpha = match (soft_mask, stencil_mask) {
+            (Some(mask), _) => mask_alpha(doc, mask, width, height, false),
+            (None, Some(mask)) => mask_alpha(doc, mask, width, height, true),
+            (None, None) => None,
+        };
+        for (pixel, alpha) in rgba.chunks_exact_mut(4).zip(alpha.into_iter().flatten()) {
+            pixel[3] = (u16::from(pixel[3]) * u16::from(alpha) / 255) as u8;
+        }
+    }
+
+    let mut pixmap = Pixmap::new(width as u32, height as u32)?;
+    for (pixel, rgba) in pixmap.pixels_mut().iter_mut().zip(rgba.chunks_exact(4)) {
+        *pixel = ColorU8::from_rgba(rgba[0], rgba[1], rgba[2], rgba[3]).premultiply();
+    }
+    Some(pixmap)
+}
+
+// Alpha of each pixel of an image from its soft mask, or from its stencil
+// mask, where samples of 1 hide the image. Masks of another size are sampled
+// at the nearest pixel.
+fn mask_alpha(
+    doc: &Document,
+    mask: &Stream,
+    width: usize,
+    height: usize,
+    stencil: bool,
+) -> Option<Vec<u8>> {
+    let mask_width = usize::try_from(entry(doc, &mask.dict, b"Width")?.as_i64().ok()?).ok()?;
+    let mask_height = usize::try_from(entry(doc, &mask.dict, b"Height")?.as_i64().ok()?).ok()?;
+    if mask_width == 0
+        || mask_height == 0
+        || mask_width.checked_mul(mask_height)? > MAX_IMAGE_PIXELS
+    {
+        return None;
+    }
+    let (data, jpeg) = image_data(doc, mask)?;
+    let levels: Vec<u8> = if jpeg {
+        let decoded = image::load_from_memory_with_format(&data, image::ImageFormat::Jpeg)
+            .ok()?
+            .to_luma8();
+        if decoded.dimensions() != (mask_width as u32, mask_height as u32) {
+            return None;
+        }
+        decoded.into_raw()
+    } else {
+        let bits = match stencil {
+            true => 1,
+            false => entry(doc, &mask.dict, b"BitsPerComponent")
+                .and_then(|bits| bits.as_i64().ok())
+                .unwrap_or(8) as u32,
+        };
+        let samples = Samples::new(data, bits, mask_width, mask_height, 1)?;
+        let decode = numbers(doc, entry(doc, &mask.dict, b"Decode"));
+        let (low, high) = match decode[..] {
+            [low, high, ..] => (low, high),
+            _ => (0.0, 1.0),
+        };
+        (0..mask_width * mask_height)
+            .map(|index| {
+                let sample = samples.get(index / mask_width, index % mask_width) as f32;
+                let level = low + sample * (high - low) / samples.max();
+                (level.clamp(0.0, 1.0) * 255.0).round() as u8
+            })
+            .collect()
+    };
+
+    Some(
+        (0..width * height)
+            .map(|index| {
+                let x = index % width * mask_width / width;
+                let y = index / width * mask_height / height;
+                let level = levels[y * mask_width + x];
+                match stencil {
+                    true => 255 - level,
+                    false => level,
+                }
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,588 @@
+use std::collections::HashMap;
+
+use tiny_skia::{Path, PathBuilder, Transform};
+
+// Keys of the eexec and charstring encryption
+const EEXEC_KEY: u16 = 55665;
+const CHARSTRING_KEY: u16 = 4330;
+
+// Subroutines and seac accents nested deeper than this are not drawn
+const MAX_DEPTH: usize = 10;
+
+/// Type1 font program, as embedded with the FontFile key
+pub(crate) struct Type1 {
+    matrix: Transform,
+    /// Glyph name of the codes of the built-in encoding
+    encoding: HashMap<u8, String>,
+    /// Decrypted charstrings by glyph index
+    glyphs: Vec<Vec<u8>>,
+    indexes: HashMap<String, u16>,
+    subrs: Vec<Vec<u8>>,
+}
+
+impl Type1 {
+    /// Parse a program from its clear text part, followed by the eexec
+    /// encrypted part. `clear_length` is the Length1 of the font stream.
+    pub(crate) fn parse(data: &[u8], clear_length: Option<usize>) -> Option<Type1> {
+        let eexec = find(data, b"eexec", 0)?;
+        let clear = &data[..clear_length
+            .filter(|&length| length > eexec)
+            .unwrap_or(eexec)];
+        let mut start = eexec + b"eexec".len();
+        while data
+            .get(start)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            start += 1;
+        }
+        let mut encrypted = data.get(start..)?.to_vec();
+        // the encrypted part is binary or hexadecimal
+        if encrypted.len() >= 4 && encrypted[..4].iter().all(u8::is_ascii_hexdigit) {
+            encrypted = from_hex(&encrypted);
+        }
+        let private = decrypt(&encrypted, EEXEC_KEY, 4);
+
+        let matrix = match numbers_after(clear, b"/FontMatrix")[..] {
+            [a, b, c, d, e, f, ..] => Transform::from_row(a, b, c, d, e, f),
+            _ => Transform::from_scale(0.001, 0.001),
+        };
+        let mut font = Type1 {
+            matrix,
+            encoding: parse_encoding(clear),
+            glyphs: Vec::new(),
+            indexes: HashMap::new(),
+            subrs: Vec::new(),
+        };
+
+        let skip = match numbers_after(&private, b"/lenIV")[..] {
+            [skip, ..] if skip < 0.0 => None,
+            [skip, ..] => Some(skip as usize),
+            _ => Some(4),
+        };
+        let charstring = |bytes: &[u8]| match skip {
+            Some(skip) => decrypt(bytes, CHARSTRING_KEY, skip),
+            None => bytes.to_vec(),
+        };
+
+        let mut reader = Reader {
+            data: &private,
+            position: 0,
+        };
+        if let Some(subrs) = find(&private, b"/Subrs", 0) {
+            reader.position = subrs + b"/Subrs".len();
+            // the count and "array" precede the entries, which are
+            // `dup <index> <length> RD <bytes> NP`
+            while let Some(token) = reader.token() {
+                if token == b"dup" {
+                    let (Some(index), Some(bytes)) = (reader.number(), reader.binary()) else {
+                        break;
+                    };
+                    let index = index as usize;
+                    if index >= font.subrs.len() {
+                        font.subrs.resize(index + 1, Vec::new());
+                    }
+                    font.subrs[index] = charstring(bytes);
+                } else if token.starts_with(b"/") || token == b"ND" || token == b"|-" {
+                    break;
+                }
+            }
+        }
+        let char_strings = find(&private, b"/CharStrings", reader.position)?;
+        reader.position = char_strings + b"/CharStrings".len();
+        // `/<name> <length> RD <bytes> ND` entries, after the count and the
+        // dictionary setup
+        while let Some(token) = reader.token() {
+            if let Some(name) = token.strip_prefix(b"/") {
+                let Some(bytes) = reader.binary() else {
+                    break;
+                };
+                let name = String::from_utf8_lossy(name).into_owned();
+                font.indexes.insert(name, font.glyphs.len() as u16);
+                font.glyphs.push(charstring(bytes));
+            } else if token == b"end" {
+                break;
+            }
+        }
+        (!font.glyphs.is_empty()).then_some(font)
+    }
+
+    pub(crate) fn glyph_index_by_name(&self, name: &str) -> Option<u16> {
+        self.indexes.get(name).copied()
+    }
+
+    /// Glyph of a code of the built-in encoding
+    pub(crate) fn glyph_index(&self, code: u8) -> Option<u16> {
+        self.glyph_index_by_name(self.encoding.get(&code)?)
+    }
+
+    /// Outline of a glyph, one unit per em
+    pub(crate) fn outline(&self, glyph: u16) -> Option<Path> {
+        let mut interpreter = Interpreter::new(self);
+        interpreter.run(self.glyphs.get(usize::from(glyph))?, 0)?;
+        interpreter.path.finish()?.transform(self.matrix)
+    }
+
+    /// Advance of a glyph, one unit per em
+    pub(crate) fn advance(&self, glyph: u16) -> Option<f32> {
+        let mut interpreter = Interpreter::new(self);
+        interpreter.run(self.glyphs.get(usize::from(glyph))?, 0)?;
+        Some(interpreter.width * self.matrix.sx)
+    }
+}
+
+// Charstring interpreter, drawing in glyph space
+struct Interpreter<'a> {
+    font: &'a Type1,
+    path: PathBuilder,
+    stack: Vec<f32>,
+    /// Results of other subroutines, taken by pop
+    results: Vec<f32>,
+    x: f32,
+    y: f32,
+    /// Where the glyph of a seac accent is drawn
+    offset: (f32, f32),
+    side_bearing: f32,
+    width: f32,
+    /// Points of a flex, drawn as two curves at its end
+    flex: Option<Vec<(f32, f32)>>,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(font: &'a Type1) -> Self {
+        Interpreter {
+            font,
+            path: PathBuilder::new(),
+            stack: Vec::new(),
+            results: Vec::new(),
+            x: 0.0,
+            y: 0.0,
+            offset: (0.0, 0.0),
+            side_bearing: 0.0,
+            width: 0.0,
+            flex: None,
+        }
+    }
+
+    fn move_by(&mut self, dx: f32, dy: f32) {
+        self.x += dx;
+        self.y += dy;
+        match &mut self.flex {
+            Some(points) => points.push((self.x, self.y)),
+            None => self
+                .path
+                .move_to(self.x + self.offset.0, self.y + self.offset.1),
+        }
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        self.x += dx;
+        self.y += dy;
+        self.path
+            .line_to(self.x + self.offset.0, self.y + self.offset.1);
+    }
+
+    fn curve_by(&mut self, d: [f32; 6]) {
+        let (x1, y1) = (self.x + d[0], self.y + d[1]);
+        let (x2, y2) = (x1 + d[2], y1 + d[3]);
+        (self.x, self.y) = (x2 + d[4], y2 + d[5]);
+        let (dx, dy) = self.offset;
+        self.path
+            .cubic_to(x1 + dx, y1 + dy, x2 + dx, y2 + dy, self.x + dx, self.y + dy);
+    }
+
+    // Run a charstring, None for invalid ones. Returns true at endchar.
+    fn run(&mut self, charstring: &[u8], depth: usize) -> Option<bool> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let mut i = 0;
+        while i < charstring.len() {
+            let byte = charstring[i];
+            i += 1;
+            match byte {
+                32..=246 => self.stack.push(f32::from(byte) - 139.0),
+                247..=250 => {
+                    let next = *charstring.get(i)?;
+                    i += 1;
+                    self.stack
+                        .push((f32::from(byte) - 247.0) * 256.0 + f32::from(next) + 108.0);
+                }
+                251..=254 => {
+                    let next = *charstring.get(i)?;
+                    i += 1;
+                    self.stack
+                        .push(-(f32::from(byte) - 251.0) * 256.0 - f32::from(next) - 108.0);
+                }
+                255 => {
+                    let bytes = charstring.get(i..i + 4)?;
+                    i += 4;
+                    self.stack
+                        .push(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32);
+                }
+                // return
+                11 => return Some(false),
+                12 => {
+                    let escape = *charstring.get(i)?;
+                    i += 1;
+                    if self.escape(escape, depth)? {
+                        return Some(true);
+                    }
+                }
+                command => {
+                    if self.command(command, depth)? {
+                        return Some(true);
+                    }
+                }
+            }
+        }
+        Some(false)
+    }
+
+    fn command(&mut self, command: u8, depth: usize) -> Option<bool> {
+        let args = std::mem::take(&mut self.stack);
+        let arg = |index: usize| args.get(index).copied().unwrap_or(0.0);
+        match command {
+            // hstem, vstem
+            1 | 3 => {}
+            4 => self.move_by(0.0, arg(0)),
+            5 => self.line_by(arg(0), arg(1)),
+            6 => self.line_by(arg(0), 0.0),
+            7 => self.line_by(0.0, arg(0)),
+            8 => self.curve_by([arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)]),
+            9 => self.path.close(),
+            10 => {
+                // callsubr keeps the arguments below the subroutine number
+                let (number, rest) = args.split_last()?;
+                self.stack = rest.to_vec();
+                let subr = self.font.subrs.get(*number as usize)?;
+                return self.run(subr, depth + 1);
+            }
+            13 => {
+                self.side_bearing = arg(0);
+                self.width = arg(1);
+                self.x = arg(0);
+                self.y = 0.0;
+            }
+            14 => return Some(true),
+            21 => self.move_by(arg(0), arg(1)),
+            22 => self.move_by(arg(0), 0.0),
+            30 => self.curve_by([0.0, arg(0), arg(1), arg(2), arg(3), 0.0]),
+            31 => self.curve_by([arg(0), 0.0, arg(1), arg(2), 0.0, arg(3)]),
+            _ => {}
+        }
+        Some(false)
+    }
+
+    fn escape(&mut self, escape: u8, depth: usize) -> Option<bool> {
+        match escape {
+            // seac, an accent over a base glyph of the standard encoding
+            6 => {
+                let args = std::mem::take(&mut self.stack);
+                let [accent_bearing, dx, dy, base, accent] = args[..] else {
+                    return None;
+                };
+                let base = self.font.glyph_index_by_name(standard_name(base as u8)?)?;
+                let accent = self
+                    .font
+                    .glyph_index_by_name(standard_name(accent as u8)?)?;
+                let offset = (self.side_bearing + dx - accent_bearing, dy);
+                let (side_bearing, width) = (self.side_bearing, self.width);
+                for (glyph, offset) in [(base, (0.0, 0.0)), (accent, offset)] {
+                    self.offset = offset;
+                    self.run(self.font.glyphs.get(usize::from(glyph))?, depth + 1)?;
+                }
+                (self.side_bearing, self.width) = (side_bearing, width);
+                return Some(true);
+            }
+            // sbw
+            7 => {
+                let args = std::mem::take(&mut self.stack);
+                if let [x, y, width, ..] = args[..] {
+                    self.side_bearing = x;
+                    self.width = width;
+                    (self.x, self.y) = (x, y);
+                }
+            }
+            // div
+            12 => {
+                let divisor = self.stack.pop()?;
+                let dividend = self.stack.pop()?;
+                self.stack.push(dividend / divisor);
+            }
+            // callothersubr
+            16 => {
+                let number = self.stack.pop()? as i32;
+                let count = self.stack.pop()? as usize;
+                let args = self.stack.split_off(self.stack.len().checked_sub(count)?);
+                match number {
+                    // end of a flex, its first point is the reference point
+                    0 => {
+                        let points = self.flex.take().unwrap_or_default();
+                        if let [_, p1, p2, p3, p4, p5, p6, ..] = points[..] {
+                            let (dx, dy) = self.offset;
+                            self.path.cubic_to(
+                                p1.0 + dx,
+                                p1.1 + dy,
+                                p2.0 + dx,
+                                p2.1 + dy,
+                                p3.0 + dx,
+                                p3.1 + dy,
+                            );
+                            self.path.cubic_to(
+                                p4.0 + dx,
+                                p4.1 + dy,
+                                p5.0 + dx,
+                                p5.1 + dy,
+                                p6.0 + dx,
+                                p6.1 + dy,
+                            );
+                            (self.x, self.y) = p6;
+                        }
+                        self.results = vec![self.y, self.x];
+                    }
+                    1 => self.flex = Some(Vec::new()),
+                    2 => {}
+                    // hint replacement calls subroutine 3, which only returns
+                    3 => self.results = vec![3.0],
+                    _ => self.results = args.into_iter().rev().collect(),
+                }
+            }
+            // pop
+            17 => {
+                let result = self.results.pop().unwrap_or(0.0);
+                self.stack.push(result);
+            }
+            // setcurrentpoint
+            33 => {
+                let args = std::mem::take(&mut self.stack);
+                if let [x, y, ..] = args[..] {
+                    (self.x, self.y) = (x, y);
+                }
+            }
+            // dotsection, vstem3, hstem3
+            _ => self.stack.clear(),
+        }
+        Some(false)
+    }
+}
+
+// Tokens and binary entries of the decrypted private dictionary
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn token(&mut self) -> Option<&'a [u8]> {
+        while self.data.get(self.position)?.is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start = self.position;
+        self.position += 1;
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|&byte| !byte.is_ascii_whitespace() && !b"/[]{}()<>".contains(&byte))
+        {
+            self.position += 1;
+        }
+        Some(&self.data[start..self.position])
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        std::str::from_utf8(self.token()?).ok()?.parse().ok()
+    }
+
+    // `<length> RD <bytes>`, RD may be spelled -| and is followed by one space
+    fn binary(&mut self) -> Option<&'a [u8]> {
+        let length = usize::try_from(self.number()?).ok()?;
+        self.token()?;
+        let start = self.position + 1;
+        let bytes = self.data.get(start..start.checked_add(length)?)?;
+        self.position = start + length;
+        Some(bytes)
+    }
+}
+
+fn find(data: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| position + from)
+}
+
+// Numbers of the array after a key, such as /FontMatrix [0.001 0 0 0.001 0 0]
+fn numbers_after(data: &[u8], key: &[u8]) -> Vec<f32> {
+    let Some(start) = find(data, key, 0) else {
+        return Vec::new();
+    };
+    let start = start + key.len();
+    let end = (start..data.len())
+        .find(|&index| matches!(data[index], b']' | b'}' | b'd'))
+        .unwrap_or(data.len());
+    String::from_utf8_lossy(&data[start..end])
+        .split(|c: char| c.is_whitespace() || "[]{}".contains(c))
+        .filter_map(|number| number.parse().ok())
+        .collect()
+}
+
+// Codes of `dup <code> /<name> put` entries of the built-in encoding. The
+// standard encoding is read as its ASCII part.
+fn parse_encoding(clear: &[u8]) -> HashMap<u8, String> {
+    let Some(start) = find(clear, b"/Encoding", 0) else {
+        return HashMap::new();
+    };
+    let mut reader = Reader {
+        data: clear,
+        position: start + b"/Encoding".len(),
+    };
+    if reader.token() == Some(b"StandardEncoding") {
+        return (32..=126)
+            .filter_map(|code| Some((code, standard_name(code)?.to_string())))
+            .collect();
+    }
+    let mut encoding = HashMap::new();
+    while let Some(token) = reader.token() {
+        match token {
+            b"dup" => {
+                let code = reader.number();
+                let name = reader.token();
+                if let (Some(code), Some(name)) =
+                    (code, name.and_then(|name| name.strip_prefix(b"/")))
+                {
+                    if let Ok(code) = u8::try_from(code) {
+                        encoding.insert(code, String::from_utf8_lossy(name).into_owned());
+                    }
+                }
+            }
+            b"def" | b"readonly" => break,
+            _ => {}
+        }
+    }
+    encoding
+}
+
+// Glyph names of the standard encoding for the codes seac uses: ASCII, which
+// matches WinAnsiEncoding but for the quotes, and the accents
+fn standard_name(code: u8) -> Option<&'static str> {
+    Some(match code {
+        39 => "quoteright",
+        96 => "quoteleft",
+        32..=126 => crate::pdf_font::win_ansi_name(usize::from(code))?,
+        193 => "grave",
+        194 => "acute",
+        195 => "circumflex",
+        196 => "tilde",
+        197 => "macron",
+        198 => "breve",
+        199 => "dotaccent",
+        200 => "dieresis",
+        202 => "ring",
+        203 => "cedilla",
+        205 => "hungarumlaut",
+        206 => "ogonek",
+        207 => "caron",
+        245 => "dotlessi",
+        _ => return None,
+    })
+}
+
+fn decrypt(data: &[u8], key: u16, skip: usize) -> Vec<u8> {
+    let mut r = key;
+    let plain: Vec<u8> = data
+        .iter()
+        .map(|&cipher| {
+            let plain = cipher ^ (r >> 8) as u8;
+            r = (u16::from(cipher).wrapping_add(r))
+                .wrapping_mul(52845)
+                .wrapping_add(22719);
+            plain
+        })
+        .collect();
+    plain.get(skip..).unwrap_or_default().to_vec()
+}
+
+fn from_hex(data: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = data
+        .iter()
+        .filter_map(|&byte| (byte as char).to_digit(16).map(|digit| digit as u8))
+        .collect();
+    digits
+        .chunks_exact(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypt(plain: &[u8], key: u16) -> Vec<u8> {
+        let mut r = key;
+        plain
+            .iter()
+            .map(|&plain| {
+                let cipher = plain ^ (r >> 8) as u8;
+                r = (u16::from(cipher).wrapping_add(r))
+                    .wrapping_mul(52845)
+                    .wrapping_add(22719);
+                cipher
+            })
+            .collect()
+    }
+
+    // A font whose glyph "square", at code 65, is a 500 unit square at x 100
+    // drawn by a subroutine
+    fn font() -> Vec<u8> {
+        let number = |value: i32| (value + 139) as u8;
+        // 100 500 hsbw 0 0 rmoveto 0 callsubr closepath endchar
+        let square = [
+            number(100),
+            248,
+            136,
+            13,
+            number(0),
+            number(0),
+            21,
+            number(0),
+            10,
+            9,
+            14,
+        ];
+        // 500 hlineto 500 vlineto -500 hlineto return
+        let subr = [248, 136, 6, 248, 136, 7, 252, 136, 6, 11];
+        let charstring =
+            |bytes: &[u8]| encrypt(&[vec![0; 4], bytes.to_vec()].concat(), CHARSTRING_KEY);
+
+        let mut private =
+            b"dup /Private 8 dict dup begin /lenIV 4 def /Subrs 1 array\ndup 0 ".to_vec();
+        let subr = charstring(&subr);
+        private.extend(format!("{} RD ", subr.len()).bytes());
+        private.extend(&subr);
+        private.extend(b" NP\nND 2 index /CharStrings 1 dict dup begin\n/square ");
+        let square = charstring(&square);
+        private.extend(format!("{} RD ", square.len()).bytes());
+        private.extend(&square);
+        private.extend(b" ND\nend end");
+
+        let mut data =
+            b"%!PS-AdobeFont-1.0: Test\n/FontMatrix [0.001 0 0 0.001 0 0] readonly def\n\
+            /Encoding 256 array\ndup 65 /square put\nreadonly def\ncurrentfile eexec\n"
+                .to_vec();
+        data.extend(encrypt(&[vec![0; 4], private].concat(), EEXEC_KEY));
+        data
+    }
+
+    #[test]
+    fn test_type1_outline() {
+        let font = Type1::parse(&font(), None).unwrap();
+
+        let glyph = font.glyph_index(65).unwrap();
+        let bounds = font.outline(glyph).unwrap().bounds();
+        assert_eq!(glyph, font.glyph_index_by_name("square").unwrap());
+        assert!((bounds.left() - 0.1).abs() < 1e-4 && (bounds.right() - 0.6).abs() < 1e-4);
+        assert!((bounds.top() - 0.0).abs() < 1e-4 && (bounds.bottom() - 0.5).abs() < 1e-4);
+        assert!((font.advance(glyph).unwrap() - 0.5).abs() < 1e-4);
+        assert!(Type1::parse(b"%!PS-AdobeFont-1.0 without eexec", None).is_none());
+    }
+}
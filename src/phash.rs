@@ -0,0 +1,180 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+const HASH_SIZE: u32 = 8;
+// pHash works on a larger thumbnail and keeps the lowest 8x8 frequencies
+const PHASH_IMAGE_SIZE: u32 = 32;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HashAlgorithm {
+    /// aHash: thumbnail pixels compared against their mean
+    Average,
+    /// dHash: horizontal gradient direction of the thumbnail
+    Difference,
+    /// pHash: low DCT frequencies compared against their median
+    Perceptual,
+}
+
+/// 64 bit perceptual hash of an image
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImageHash(pub u64);
+
+impl ImageHash {
+    /// Number of differing bits, 0 means the images look the same
+    pub fn distance(&self, other: &ImageHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+impl fmt::Display for ImageHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+fn thumbnail(image: &DynamicImage, width: u32, height: u32) -> Vec<f64> {
+    image
+        .resize_exact(width, height, FilterType::Triangle)
+        .to_luma8()
+        .into_raw()
+        .into_iter()
+        .map(|v| v as f64)
+        .collect()
+}
+
+fn bits_to_hash(bits: impl Iterator<Item = bool>) -> ImageHash {
+    ImageHash(bits.fold(0u64, |hash, bit| (hash << 1) | bit as u64))
+}
+
+fn average_hash(image: &DynamicImage) -> ImageHash {
+    let pixels = thumbnail(image, HASH_SIZE, HASH_SIZE);
+    let mean = pixels.iter().sum::<f64>() / pixels.len() as f64;
+    bits_to_hash(pixels.iter().map(|&v| v > mean))
+}
+
+fn difference_hash(image: &DynamicImage) -> ImageHash {
+    let width = HASH_SIZE as usize + 1;
+    let pixels = thumbnail(image, HASH_SIZE + 1, HASH_SIZE);
+    bits_to_hash(
+        (0..HASH_SIZE as usize)
+            .flat_map(|y| (0..HASH_SIZE as usize).map(move |x| (x, y)))
+            .map(|(x, y)| pixels[y * width + x] < pixels[y * width + x + 1]),
+    )
+}
+
+// Lowest `HASH_SIZE` DCT-II coefficients of each row of `size` x `size` values
+fn dct_rows(values: &[f64], size: usize, rows: usize) -> Vec<f64> {
+    let mut out = Vec::with_capacity(rows * HASH_SIZE as usize);
+    for row in values.chunks_exact(size).take(rows) {
+        for k in 0..HASH_SIZE as usize {
+            out.push(
+                row.iter()
+                    .enumerate()
+                    .map(|(n, v)| v * (PI / size as f64 * (n as f64 + 0.5) * k as f64).cos())
+                    .sum(),
+            );
+        }
+    }
+    out
+}
+
+fn transpose(values: &[f64], width: usize, height: usize) -> Vec<f64> {
+    (0..width)
+        .flat_map(|x| (0..height).map(move |y| values[y * width + x]))
+        .collect()
+}
+
+fn perceptual_hash(image: &DynamicImage) -> ImageHash {
+    let size = PHASH_IMAGE_SIZE as usize;
+    let hash_size = HASH_SIZE as usize;
+    let pixels = thumbnail(image, PHASH_IMAGE_SIZE, PHASH_IMAGE_SIZE);
+
+    // 2D DCT as a row pass followed by a column pass, keeping only the low
+    // frequencies of each
+    let rows = dct_rows(&pixels, size, size);
+    let columns = transpose(&rows, hash_size, size);
+    let low = transpose(&dct_rows(&columns, size, hash_size), hash_size, hash_size);
+
+    // The DC term only carries the mean brightness, leave it out of the median
+    let mut sorted: Vec<f64> = low[1..].to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median = (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0;
+
+    bits_to_hash(low.iter().map(|&v| v > median))
+}
+
+pub fn compute_hash(image: &DynamicImage, algorithm: HashAlgorithm) -> ImageHash {
+    match algorithm {
+        HashAlgorithm::Average => average_hash(image),
+        HashAlgorithm::Difference => difference_hash(image),
+        HashAlgorithm::Perceptual => perceptual_hash(image),
+    }
+}
+
+/// Hamming distance between the hashes of both images, out of 64
+pub fn hash_distance(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    algorithm: HashAlgorithm,
+) -> u32 {
+    compute_hash(image1, algorithm).distance(&compute_hash(image2, algorithm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [HashAlgorithm; 3] = [
+        HashAlgorithm::Average,
+        HashAlgorithm::Difference,
+        HashAlgorithm::Perceptual,
+    ];
+
+    #[test]
+    fn test_image_hash_distance_and_display() {
+        let a = ImageHash(0b1011);
+        let b = ImageHash(0b0110);
+
+        assert_eq!(a.distance(&b), 3);
+        assert_eq!(a.distance(&a), 0);
+        assert_eq!(a.to_string(), "000000000000000b");
+    }
+
+    #[test]
+    fn test_hash_tolerates_resizing() {
+        let image =
+            image::open("tests/images/image1.png")
+                .unwrap()
+                .resize(1200, 600, FilterType::Triangle);
+        let smaller = image.resize(image.width() / 3, image.height() / 3, FilterType::Lanczos3);
+
+        for algorithm in ALGORITHMS {
+            assert_eq!(hash_distance(&image, &image, algorithm), 0);
+            assert!(
+                hash_distance(&image, &smaller, algorithm) <= 4,
+                "{:?}",
+                algorithm
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash_tells_different_images_apart() {
+        let image =
+            image::open("tests/images/image1.png")
+                .unwrap()
+                .resize(1200, 600, FilterType::Triangle);
+        let flipped = image.flipv();
+
+        for algorithm in ALGORITHMS {
+            assert!(
+                hash_distance(&image, &flipped, algorithm) > 10,
+                "{:?}",
+                algorithm
+            );
+        }
+    }
+}
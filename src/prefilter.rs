@@ -0,0 +1,175 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+
+/// Largest median radius, larger radii are clamped to it. The window of a
+/// pixel holds (2 * radius + 1)^2 values.
+pub const MAX_MEDIAN_RADIUS: u32 = 32;
+
+/// One step of the pre-processing applied to both images before diffing
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Prefilter {
+    /// Gaussian blur over this radius in pixels, sigma is half the radius
+    GaussianBlur(f32),
+    /// Average each block of this many by this many pixels into one pixel
+    Downsample(u32),
+    /// Per channel median over a square window of this radius, at most
+    /// `MAX_MEDIAN_RADIUS`
+    Median(u32),
+}
+
+fn downsample(image: &RgbaImage, factor: u32) -> RgbaImage {
+    let width = image.width().div_ceil(factor);
+    let height = image.height().div_ceil(factor);
+    RgbaImage::from_fn(width, height, |bx, by| {
+        let x_end = (bx + 1).saturating_mul(factor).min(image.width());
+        let y_end = (by + 1).saturating_mul(factor).min(image.height());
+        let mut total = [0u32; 4];
+        let mut count = 0u32;
+        for y in by * factor..y_end {
+            for x in bx * factor..x_end {
+                let pixel = image.get_pixel(x, y);
+                for c in 0..4 {
+                    total[c] += pixel[c] as u32;
+                }
+                count += 1;
+            }
+        }
+        Rgba(total.map(|t| ((t + count / 2) / count) as u8))
+    })
+}
+
+fn median(image: &RgbaImage, radius: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let radius = radius.min(MAX_MEDIAN_RADIUS) as i64;
+    let mut window: Vec<u8> = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+    RgbaImage::from_fn(width, height, |x, y| {
+        let mut output = [0u8; 4];
+        for (c, value) in output.iter_mut().enumerate() {
+            window.clear();
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    // edges are clamped
+                    let sx = (x as i64 + dx).clamp(0, width as i64 - 1) as u32;
+                    let sy = (y as i64 + dy).clamp(0, height as i64 - 1) as u32;
+                    window.push(image.get_pixel(sx, sy)[c]);
+                }
+            }
+            let middle = window.len() / 2;
+            *value = *window.select_nth_unstable(middle).1;
+        }
+        Rgba(output)
+    })
+}
+
+/// Run the filters over the image in order
+pub fn apply_prefilters(image: &DynamicImage, filters: &[Prefilter]) -> DynamicImage {
+    let mut output = image.clone();
+    for filter in filters {
+        if output.width() == 0 || output.height() == 0 {
+            break;
+        }
+        output = match *filter {
+            Prefilter::GaussianBlur(radius) if radius > 0.0 => output.blur(radius / 2.0),
+            Prefilter::GaussianBlur(_) => output,
+            Prefilter::Downsample(factor) if factor > 1 => {
+                DynamicImage::ImageRgba8(downsample(&output.to_rgba8(), factor))
+            }
+            Prefilter::Downsample(_) => output,
+            Prefilter::Median(radius) if radius > 0 => {
+                DynamicImage::ImageRgba8(median(&output.to_rgba8(), radius))
+            }
+            Prefilter::Median(_) => output,
+        };
+    }
+    output
+}
+
+/// Run the same filters over both images, so noise like JPEG artifacts
+/// does not show up as a difference
+pub fn prefilter_images(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    filters: &[Prefilter],
+) -> (DynamicImage, DynamicImage) {
+    (
+        apply_prefilters(image1, filters),
+        apply_prefilters(image2, filters),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, ImageBuffer};
+
+    fn grey(width: u32, height: u32, value: impl Fn(u32, u32) -> u8) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+            let v = value(x, y);
+            Rgba([v, v, v, 255])
+        }))
+    }
+
+    #[test]
+    fn test_median_removes_salt_noise() {
+        let clean = grey(16, 16, |_, _| 100);
+        let noisy = grey(
+            16,
+            16,
+            |x, y| if (x * 7 + y * 3) % 11 == 0 { 255 } else { 100 },
+        );
+
+        let filtered = apply_prefilters(&noisy, &[Prefilter::Median(1)]);
+
+        assert_eq!(filtered.to_rgba8(), clean.to_rgba8());
+    }
+
+    #[test]
+    fn test_huge_median_radius_and_downsample_factor() {
+        let image = grey(3, 2, |x, _| x as u8 * 10);
+
+        let median = apply_prefilters(&image, &[Prefilter::Median(u32::MAX)]);
+        let downsampled = apply_prefilters(&image, &[Prefilter::Downsample(u32::MAX)]);
+
+        assert_eq!(median.dimensions(), (3, 2));
+        assert_eq!(downsampled.get_pixel(0, 0), Rgba([10, 10, 10, 255]));
+    }
+
+    #[test]
+    fn test_downsample_averages_blocks() {
+        let image = grey(5, 4, |x, _| if x % 2 == 0 { 0 } else { 200 });
+
+        let filtered = apply_prefilters(&image, &[Prefilter::Downsample(2)]);
+
+        assert_eq!(filtered.dimensions(), (3, 2));
+        assert_eq!(filtered.get_pixel(0, 0), Rgba([100, 100, 100, 255]));
+        // the last column only has one source column left
+        assert_eq!(filtered.get_pixel(2, 1), Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_blur_smooths_edges() {
+        let image = grey(16, 16, |x, _| if x < 8 { 0 } else { 255 });
+
+        let filtered = apply_prefilters(&image, &[Prefilter::GaussianBlur(4.0)]);
+        let edge = filtered.get_pixel(8, 8)[0];
+
+        assert_eq!(filtered.dimensions(), (16, 16));
+        assert!(edge > 0 && edge < 255);
+    }
+
+    #[test]
+    fn test_prefilter_images_pipeline() {
+        let image1 = grey(8, 8, |x, y| ((x + y) * 10) as u8);
+        let image2 = grey(8, 8, |x, y| ((x + y) * 10) as u8 + 1);
+        let filters = [
+            Prefilter::Median(1),
+            Prefilter::GaussianBlur(0.0),
+            Prefilter::Downsample(4),
+        ];
+
+        let (filtered1, filtered2) = prefilter_images(&image1, &image2, &filters);
+
+        assert_eq!(filtered1.dimensions(), (2, 2));
+        assert_eq!(filtered2.dimensions(), (2, 2));
+        assert_eq!(apply_prefilters(&image1, &[]).to_rgba8(), image1.to_rgba8());
+    }
+}
@@ -0,0 +1,176 @@
+use image::{DynamicImage, GrayImage, RgbImage, RgbaImage};
+
+/// Byte order of the pixels of a raw buffer, 8 bits per channel
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PixelFormat {
+    Rgba8,
+    /// Common for GPU readbacks on little endian machines
+    Bgra8,
+    Rgb8,
+    Bgr8,
+    Gray8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 => 4,
+            PixelFormat::Rgb8 | PixelFormat::Bgr8 => 3,
+            PixelFormat::Gray8 => 1,
+        }
+    }
+}
+
+/// Layout of a raw pixel buffer without a header
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RawLayout {
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    /// Bytes from the start of one row to the start of the next, None for
+    /// rows without padding
+    pub stride: Option<usize>,
+}
+
+/// Build an image from a raw pixel buffer. Padding at the end of each row is
+/// skipped, BGR(A) buffers are reordered to RGB(A).
+pub fn image_from_raw(bytes: &[u8], layout: RawLayout) -> Result<DynamicImage, String> {
+    let too_large = || format!("Raw image {}x{} is too large", layout.width, layout.height);
+    let bytes_per_pixel = layout.format.bytes_per_pixel();
+    let row_length = (layout.width as usize)
+        .checked_mul(bytes_per_pixel)
+        .ok_or_else(too_large)?;
+    let stride = layout.stride.unwrap_or(row_length);
+    if stride < row_length {
+        return Err(format!(
+            "Stride {} is shorter than a row of {} bytes",
+            stride, row_length
+        ));
+    }
+    let needed = match layout.height as usize {
+        0 => 0,
+        rows => stride
+            .checked_mul(rows - 1)
+            .and_then(|offset| offset.checked_add(row_length))
+            .ok_or_else(too_large)?,
+    };
+    if bytes.len() < needed {
+        return Err(format!(
+            "Raw buffer of {} bytes is too small for {}x{} {:?} pixels, {} bytes needed",
+            bytes.len(),
+            layout.width,
+            layout.height,
+            layout.format,
+            needed
+        ));
+    }
+
+    // no larger than `needed`, which fits
+    let mut pixels = Vec::with_capacity(row_length * layout.height as usize);
+    for row in 0..layout.height as usize {
+        let start = row.checked_mul(stride).ok_or_else(too_large)?;
+        pixels.extend_from_slice(&bytes[start..start + row_length]);
+    }
+    if matches!(layout.format, PixelFormat::Bgra8 | PixelFormat::Bgr8) {
+        for pixel in pixels.chunks_exact_mut(bytes_per_pixel) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    let (width, height) = (layout.width, layout.height);
+    let image = match layout.format {
+        PixelFormat::Rgba8 | PixelFormat::Bgra8 => {
+            RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+        }
+        PixelFormat::Rgb8 | PixelFormat::Bgr8 => {
+            RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+        }
+        PixelFormat::Gray8 => {
+            GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+        }
+    };
+    image.ok_or_else(|| "Invalid raw buffer size".to_string())
+}
+
+/// Load a raw pixel file, see `image_from_raw`
+pub fn load_raw_image(filename: &str, layout: RawLayout) -> Result<DynamicImage, String> {
+    let bytes = std::fs::read(filename)
+        .map_err(|err| format!("Error loading raw image {}: {}", filename, err))?;
+    image_from_raw(&bytes, layout)
+        .map_err(|err| format!("Error loading raw image {}: {}", filename, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba};
+
+    #[test]
+    fn test_bgra_with_stride() {
+        // 2x2 BGRA pixels, rows padded to 12 bytes
+        let bytes = [
+            3, 2, 1, 255, 6, 5, 4, 255, 0, 0, 0, 0, //
+            9, 8, 7, 255, 12, 11, 10, 128,
+        ];
+        let layout = RawLayout {
+            width: 2,
+            height: 2,
+            format: PixelFormat::Bgra8,
+            stride: Some(12),
+        };
+
+        let image = image_from_raw(&bytes, layout).unwrap();
+
+        assert_eq!(image.dimensions(), (2, 2));
+        assert_eq!(image.get_pixel(0, 0), Rgba([1, 2, 3, 255]));
+        assert_eq!(image.get_pixel(1, 1), Rgba([10, 11, 12, 128]));
+    }
+
+    #[test]
+    fn test_raw_layout_errors() {
+        let layout = RawLayout {
+            width: 4,
+            height: 2,
+            format: PixelFormat::Rgb8,
+            stride: None,
+        };
+
+        assert!(image_from_raw(&[0; 23], layout).is_err());
+        assert!(image_from_raw(&[0; 24], layout).is_ok());
+        let short_stride = RawLayout {
+            stride: Some(8),
+            ..layout
+        };
+        assert!(image_from_raw(&[0; 64], short_stride).is_err());
+        let huge_stride = RawLayout {
+            stride: Some(usize::MAX / 2),
+            height: 3,
+            ..layout
+        };
+        assert!(image_from_raw(&[0; 64], huge_stride).is_err());
+        let huge_width = RawLayout {
+            width: u32::MAX,
+            height: 1,
+            ..layout
+        };
+        assert!(image_from_raw(&[0; 64], huge_width).is_err());
+    }
+
+    #[test]
+    fn test_gray_image_feeds_diff() {
+        let layout = RawLayout {
+            width: 3,
+            height: 1,
+            format: PixelFormat::Gray8,
+            stride: None,
+        };
+        let image1 = image_from_raw(&[0, 50, 100], layout).unwrap();
+        let image2 = image_from_raw(&[0, 50, 200], layout).unwrap();
+
+        assert_eq!(
+            crate::calculate_diff_ratio(image1.clone(), image1.clone()),
+            0.0
+        );
+        assert!(crate::calculate_diff_ratio(image1, image2) > 0.0);
+    }
+}
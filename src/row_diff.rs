@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::{Index, IndexMut, Range};
+
+use lcs_diff::{DiffElement, DiffResult};
+
+enum Edit {
+    Common(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+// Diagonal-indexed vector, k ranges over -max..=max
+struct V {
+    offset: isize,
+    values: Vec<usize>,
+}
+
+impl V {
+    fn new(max_d: usize) -> Self {
+        V {
+            offset: max_d as isize,
+            values: vec![0; 2 * max_d + 1],
+        }
+    }
+}
+
+impl Index<isize> for V {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.values[(k + self.offset) as usize]
+    }
+}
+
+impl IndexMut<isize> for V {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.values[(k + self.offset) as usize]
+    }
+}
+
+fn max_d(old_len: usize, new_len: usize) -> usize {
+    (old_len + new_len).div_ceil(2) + 1
+}
+
+fn common_prefix_len<T: PartialEq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+) -> usize {
+    old[old_range]
+        .iter()
+        .zip(&new[new_range])
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+fn common_suffix_len<T: PartialEq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+) -> usize {
+    old[old_range]
+        .iter()
+        .rev()
+        .zip(new[new_range].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+// Find the start of the middle snake of the optimal edit path, searching
+// forwards and backwards at the same time so only O(N + M) space is used.
+fn find_middle_snake<T: PartialEq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    vf: &mut V,
+    vb: &mut V,
+) -> Option<(usize, usize)> {
+    let n = old_range.len();
+    let m = new_range.len();
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+
+    vf[1] = 0;
+    vb[1] = 0;
+
+    for d in 0..max_d(n, m) as isize {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
+                vf[k + 1]
+            } else {
+                vf[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let (x0, y0) = (x, y);
+            if x < n && y < m {
+                x += common_prefix_len(
+                    old,
+                    old_range.start + x..old_range.end,
+                    new,
+                    new_range.start + y..new_range.end,
+                );
+            }
+            vf[k] = x;
+            if odd && (k - delta).abs() < d && vf[k] + vb[-(k - delta)] >= n {
+                return Some((x0 + old_range.start, y0 + new_range.start));
+            }
+        }
+
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vb[k - 1] < vb[k + 1]) {
+                vb[k + 1]
+            } else {
+                vb[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            if x < n && y < m {
+                let advance = common_suffix_len(
+                    old,
+                    old_range.start..old_range.start + n - x,
+                    new,
+                    new_range.start..new_range.start + m - y,
+                );
+                x += advance;
+                y += advance;
+            }
+            vb[k] = x;
+            if !odd && (k - delta).abs() <= d && vb[k] + vf[-(k - delta)] >= n {
+                return Some((n - x + old_range.start, m - y + new_range.start));
+            }
+        }
+    }
+
+    None
+}
+
+fn myers_conquer<T: PartialEq>(
+    old: &[T],
+    mut old_range: Range<usize>,
+    new: &[T],
+    mut new_range: Range<usize>,
+    vf: &mut V,
+    vb: &mut V,
+    edits: &mut Vec<Edit>,
+) {
+    let prefix = common_prefix_len(old, old_range.clone(), new, new_range.clone());
+    for i in 0..prefix {
+        edits.push(Edit::Common(old_range.start + i, new_range.start + i));
+    }
+    old_range.start += prefix;
+    new_range.start += prefix;
+
+    let suffix = common_suffix_len(old, old_range.clone(), new, new_range.clone());
+    old_range.end -= suffix;
+    new_range.end -= suffix;
+
+    if old_range.is_empty() {
+        edits.extend(new_range.clone().map(Edit::Added));
+    } else if new_range.is_empty() {
+        edits.extend(old_range.clone().map(Edit::Removed));
+    } else if let Some((x, y)) =
+        find_middle_snake(old, old_range.clone(), new, new_range.clone(), vf, vb)
+    {
+        myers_conquer(
+            old,
+            old_range.start..x,
+            new,
+            new_range.start..y,
+            vf,
+            vb,
+            edits,
+        );
+        myers_conquer(old, x..old_range.end, new, y..new_range.end, vf, vb, edits);
+    } else {
+        edits.extend(old_range.clone().map(Edit::Removed));
+        edits.extend(new_range.clone().map(Edit::Added));
+    }
+
+    for i in 0..suffix {
+        edits.push(Edit::Common(old_range.end + i, new_range.end + i));
+    }
+}
+
+fn myers_edits<T: PartialEq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    edits: &mut Vec<Edit>,
+) {
+    let max = max_d(old_range.len(), new_range.len());
+    let mut vf = V::new(max);
+    let mut vb = V::new(max);
+    myers_conquer(old, old_range, new, new_range, &mut vf, &mut vb, edits);
+}
+
+// Longest increasing subsequence of the new indexes, found with patience sorting
+fn longest_increasing_run(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut tops: Vec<usize> = Vec::new();
+    let mut back: Vec<Option<usize>> = vec![None; pairs.len()];
+
+    for (i, &(_, new_index)) in pairs.iter().enumerate() {
+        let pile = tops.partition_point(|&top| pairs[top].1 < new_index);
+        back[i] = if pile > 0 { Some(tops[pile - 1]) } else { None };
+        if pile == tops.len() {
+            tops.push(i);
+        } else {
+            tops[pile] = i;
+        }
+    }
+
+    let mut run = Vec::with_capacity(tops.len());
+    let mut current = tops.last().copied();
+    while let Some(i) = current {
+        run.push(pairs[i]);
+        current = back[i];
+    }
+    run.reverse();
+    run
+}
+
+fn unique_pairs<T, K, F>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    key: &F,
+) -> Vec<(usize, usize)>
+where
+    K: Hash + Eq,
+    F: Fn(&T) -> K,
+{
+    // (count in old, index in old, count in new, index in new)
+    let mut seen: HashMap<K, (usize, usize, usize, usize)> = HashMap::new();
+    for i in old_range {
+        let entry = seen.entry(key(&old[i])).or_insert((0, i, 0, 0));
+        entry.0 += 1;
+    }
+    for i in new_range {
+        if let Some(entry) = seen.get_mut(&key(&new[i])) {
+            entry.2 += 1;
+            entry.3 = i;
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = seen
+        .into_values()
+        .filter(|&(old_count, _, new_count, _)| old_count == 1 && new_count == 1)
+        .map(|(_, old_index, _, new_index)| (old_index, new_index))
+        .collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+fn patience_conquer<T, K, F>(
+    old: &[T],
+    mut old_range: Range<usize>,
+    new: &[T],
+    mut new_range: Range<usize>,
+    key: &F,
+    edits: &mut Vec<Edit>,
+) where
+    T: PartialEq,
+    K: Hash + Eq,
+    F: Fn(&T) -> K,
+{
+    let prefix = common_prefix_len(old, old_range.clone(), new, new_range.clone());
+    for i in 0..prefix {
+        edits.push(Edit::Common(old_range.start + i, new_range.start + i));
+    }
+    old_range.start += prefix;
+    new_range.start += prefix;
+
+    let suffix = common_suffix_len(old, old_range.clone(), new, new_range.clone());
+    old_range.end -= suffix;
+    new_range.end -= suffix;
+
+    let anchors = longest_increasing_run(&unique_pairs(
+        old,
+        old_range.clone(),
+        new,
+        new_range.clone(),
+        key,
+    ));
+
+    if anchors.is_empty() {
+        myers_edits(old, old_range.clone(), new, new_range.clone(), edits);
+    } else {
+        let (mut old_start, mut new_start) = (old_range.start, new_range.start);
+        for (old_index, new_index) in anchors {
+            patience_conquer(
+                old,
+                old_start..old_index,
+                new,
+                new_start..new_index,
+                key,
+                edits,
+            );
+            edits.push(Edit::Common(old_index, new_index));
+            old_start = old_index + 1;
+            new_start = new_index + 1;
+        }
+        patience_conquer(
+            old,
+            old_start..old_range.end,
+            new,
+            new_start..new_range.end,
+            key,
+            edits,
+        );
+    }
+
+    for i in 0..suffix {
+        edits.push(Edit::Common(old_range.end + i, new_range.end + i));
+    }
+}
+
+fn into_results<T: PartialEq + Clone>(
+    old: &[T],
+    new: &[T],
+    edits: Vec<Edit>,
+) -> Vec<DiffResult<T>> {
+    edits
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Common(o, n) => DiffResult::Common(DiffElement {
+                old_index: Some(o),
+                new_index: Some(n),
+                data: new[n].clone(),
+            }),
+            Edit::Removed(o) => DiffResult::Removed(DiffElement {
+                old_index: Some(o),
+                new_index: None,
+                data: old[o].clone(),
+            }),
+            Edit::Added(n) => DiffResult::Added(DiffElement {
+                old_index: None,
+                new_index: Some(n),
+                data: new[n].clone(),
+            }),
+        })
+        .collect()
+}
+
+/// Linear space O(ND) diff (Myers, 1986). Produces a minimal edit script in
+/// the same form as `lcs_diff::diff`.
+pub fn myers_diff<T: PartialEq + Clone>(old: &[T], new: &[T]) -> Vec<DiffResult<T>> {
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    myers_edits(old, 0..old.len(), new, 0..new.len(), &mut edits);
+    into_results(old, new, edits)
+}
+
+/// Patience diff. Rows that occur exactly once on both sides (by `key`) are
+/// used as anchors, the gaps between them are diffed with Myers.
+pub fn patience_diff<T, K, F>(old: &[T], new: &[T], key: F) -> Vec<DiffResult<T>>
+where
+    T: PartialEq + Clone,
+    K: Hash + Eq,
+    F: Fn(&T) -> K,
+{
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    patience_conquer(old, 0..old.len(), new, 0..new.len(), &key, &mut edits);
+    into_results(old, new, edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn common_count<T: PartialEq + Clone>(result: &[DiffResult<T>]) -> usize {
+        result
+            .iter()
+            .filter(|d| matches!(d, DiffResult::Common(_)))
+            .count()
+    }
+
+    // Rebuild both sequences from a diff to check it is a valid edit script
+    fn rebuild<T: PartialEq + Clone>(result: &[DiffResult<T>]) -> (Vec<T>, Vec<T>) {
+        let mut old = Vec::new();
+        let mut new = Vec::new();
+        let (mut next_old, mut next_new) = (0, 0);
+        for d in result {
+            match d {
+                DiffResult::Common(c) => {
+                    assert_eq!(c.old_index, Some(next_old));
+                    assert_eq!(c.new_index, Some(next_new));
+                    old.push(c.data.clone());
+                    new.push(c.data.clone());
+                    next_old += 1;
+                    next_new += 1;
+                }
+                DiffResult::Removed(r) => {
+                    assert_eq!(r.old_index, Some(next_old));
+                    old.push(r.data.clone());
+                    next_old += 1;
+                }
+                DiffResult::Added(a) => {
+                    assert_eq!(a.new_index, Some(next_new));
+                    new.push(a.data.clone());
+                    next_new += 1;
+                }
+            }
+        }
+        (old, new)
+    }
+
+    const CASES: [(&str, &str); 8] = [
+        ("", ""),
+        ("abc", ""),
+        ("", "abc"),
+        ("abc", "abc"),
+        ("abcabba", "cbabac"),
+        ("abgdef", "gh"),
+        ("xaxbxcx", "axbxcxd"),
+        ("the quick brown fox", "a quick brown cat jumps"),
+    ];
+
+    #[test]
+    fn test_myers_diff_matches_lcs() {
+        for (a, b) in CASES {
+            let old: Vec<char> = a.chars().collect();
+            let new: Vec<char> = b.chars().collect();
+            let result = myers_diff(&old, &new);
+
+            assert_eq!(rebuild(&result), (old.clone(), new.clone()));
+            assert_eq!(
+                common_count(&result),
+                common_count(&lcs_diff::diff(&old, &new))
+            );
+        }
+    }
+
+    #[test]
+    fn test_myers_diff_matches_lcs_random() {
+        // small LCG so the sequences are reproducible
+        let mut seed: u32 = 12345;
+        let mut next = move |max: u32| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % max
+        };
+
+        for _ in 0..200 {
+            let old: Vec<u32> = (0..next(40)).map(|_| next(4)).collect();
+            let new: Vec<u32> = (0..next(40)).map(|_| next(4)).collect();
+            let result = myers_diff(&old, &new);
+
+            assert_eq!(rebuild(&result), (old.clone(), new.clone()));
+            assert_eq!(
+                common_count(&result),
+                common_count(&lcs_diff::diff(&old, &new))
+            );
+            assert_eq!(rebuild(&patience_diff(&old, &new, |v| *v)), (old, new));
+        }
+    }
+
+    #[test]
+    fn test_patience_diff_is_valid() {
+        for (a, b) in CASES {
+            let old: Vec<char> = a.chars().collect();
+            let new: Vec<char> = b.chars().collect();
+            let result = patience_diff(&old, &new, |c| *c);
+
+            assert_eq!(rebuild(&result), (old, new));
+        }
+    }
+
+    #[test]
+    fn test_patience_diff_anchors_unique_rows() {
+        let old = vec!["{", "a", "}", "{", "b", "}"];
+        let new = vec!["{", "b", "}", "{", "a", "}"];
+        let result = patience_diff(&old, &new, |s| *s);
+
+        assert_eq!(rebuild(&result), (old, new));
+        // the braces are unique once the common prefix and suffix are gone,
+        // so they anchor the diff and the swapped rows are reported as changes
+        assert_eq!(common_count(&result), 4);
+        assert!(!result
+            .iter()
+            .any(|d| matches!(d, DiffResult::Common(c) if c.data == "a" || c.data == "b")));
+    }
+}
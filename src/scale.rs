@@ -0,0 +1,184 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, GrayImage};
+
+// Scale factors tried besides the ratio of the image sizes, the device
+// pixel ratios screenshots usually come in
+const COMMON_SCALES: [f64; 7] = [1.0, 1.25, 1.5, 2.0, 2.5, 3.0, 4.0];
+// Longest side the images are reduced to while estimating the scale
+const ESTIMATE_SIZE: u32 = 256;
+
+/// Both images at the resolution of image1, cropped to the area they share
+#[derive(Debug)]
+pub struct ScaledImages {
+    pub image1: DynamicImage,
+    pub image2: DynamicImage,
+    /// Size of image2 relative to image1, 2.0 when image2 is a 2x render
+    pub scale: f64,
+}
+
+/// Resample image2 to the dimensions of image1
+pub fn resample_to_match(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    filter: FilterType,
+) -> DynamicImage {
+    if image1.dimensions() == image2.dimensions() {
+        return image2.clone();
+    }
+    image2.resize_exact(image1.width(), image1.height(), filter)
+}
+
+fn scaled_size(size: (u32, u32), factor: f64) -> (u32, u32) {
+    (
+        ((size.0 as f64 * factor).round() as u32).max(1),
+        ((size.1 as f64 * factor).round() as u32).max(1),
+    )
+}
+
+// Mean absolute luma difference over the top left area both images cover,
+// None when that is less than half of `a`
+fn cost(a: &GrayImage, b: &GrayImage) -> Option<f64> {
+    let width = a.width().min(b.width());
+    let height = a.height().min(b.height());
+    if (width as u64 * height as u64) * 2 < a.width() as u64 * a.height() as u64 {
+        return None;
+    }
+
+    let mut total = 0u64;
+    for y in 0..height {
+        for x in 0..width {
+            total += a.get_pixel(x, y)[0].abs_diff(b.get_pixel(x, y)[0]) as u64;
+        }
+    }
+    Some(total as f64 / (width as u64 * height as u64) as f64)
+}
+
+/// Estimate how much larger image2 is rendered than image1. The ratios of
+/// the image sizes and the common device pixel ratios (and their inverses)
+/// are tried, the one where the content lines up best wins.
+pub fn estimate_scale(image1: &DynamicImage, image2: &DynamicImage) -> Result<f64, String> {
+    if image1.width() == 0 || image1.height() == 0 || image2.width() == 0 || image2.height() == 0 {
+        return Err("Images must not be empty".to_string());
+    }
+
+    let mut candidates: Vec<f64> = COMMON_SCALES
+        .iter()
+        .flat_map(|&scale| [scale, 1.0 / scale])
+        .collect();
+    candidates.push(image2.width() as f64 / image1.width() as f64);
+    candidates.push(image2.height() as f64 / image1.height() as f64);
+
+    let reduce = (ESTIMATE_SIZE as f64 / image1.width().max(image1.height()) as f64).min(1.0);
+    let (width, height) = scaled_size(image1.dimensions(), reduce);
+    let a = imageops::resize(&image1.to_luma8(), width, height, FilterType::Triangle);
+    // image2 is at most ESTIMATE_SIZE * 4 wide at any candidate, shrink it once up front
+    let shrink = (4.0 * ESTIMATE_SIZE as f64 / image2.width().max(image2.height()) as f64).min(1.0);
+    let (width, height) = scaled_size(image2.dimensions(), shrink);
+    let b = imageops::resize(&image2.to_luma8(), width, height, FilterType::Triangle);
+
+    let mut best: Option<(f64, f64)> = None;
+    for scale in candidates {
+        let (width, height) = scaled_size(image2.dimensions(), reduce / scale);
+        let resized = imageops::resize(&b, width, height, FilterType::Triangle);
+        if let Some(c) = cost(&a, &resized) {
+            // ties go to the scale closest to 1
+            let better = best.is_none_or(|(best_scale, best_cost)| {
+                c < best_cost || (c == best_cost && scale.ln().abs() < best_scale.ln().abs())
+            });
+            if better {
+                best = Some((scale, c));
+            }
+        }
+    }
+
+    best.map(|(scale, _)| scale)
+        .ok_or_else(|| "Images do not overlap at any scale".to_string())
+}
+
+/// Detect the scale between the images, resample image2 to the resolution
+/// of image1 with `filter` and crop both to their common area so they can be
+/// compared pixel for pixel
+pub fn match_scale(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+    filter: FilterType,
+) -> Result<ScaledImages, String> {
+    let scale = estimate_scale(image1, image2)?;
+    let (width, height) = scaled_size(image2.dimensions(), 1.0 / scale);
+    let resized = if (width, height) == image2.dimensions() {
+        image2.clone()
+    } else {
+        image2.resize_exact(width, height, filter)
+    };
+
+    let width = width.min(image1.width());
+    let height = height.min(image1.height());
+    Ok(ScaledImages {
+        image1: image1.crop_imm(0, 0, width, height),
+        image2: resized.crop_imm(0, 0, width, height),
+        scale,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_image() -> DynamicImage {
+        image::open("tests/images/image1.png")
+            .unwrap()
+            .resize_exact(300, 150, FilterType::Triangle)
+    }
+
+    #[test]
+    fn test_estimate_scale() {
+        let image = base_image();
+        let retina = image.resize_exact(600, 300, FilterType::Triangle);
+        let three_x = image.resize_exact(900, 450, FilterType::Triangle);
+        let small = image.resize_exact(200, 100, FilterType::Triangle);
+
+        assert_eq!(estimate_scale(&image, &image).unwrap(), 1.0);
+        assert_eq!(estimate_scale(&image, &retina).unwrap(), 2.0);
+        assert_eq!(estimate_scale(&retina, &image).unwrap(), 0.5);
+        assert_eq!(estimate_scale(&image, &three_x).unwrap(), 3.0);
+        assert!((estimate_scale(&small, &image).unwrap() - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_match_scale_crops_to_common_area() {
+        let image = base_image();
+        // a 2x render of a taller page
+        let retina = image.resize_exact(600, 300, FilterType::Triangle);
+        let taller = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(600, 360, |x, y| {
+            if y < 300 {
+                retina.get_pixel(x, y)
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        }));
+
+        let scaled = match_scale(&image, &taller, FilterType::Triangle).unwrap();
+
+        assert_eq!(scaled.scale, 2.0);
+        assert_eq!(scaled.image1.dimensions(), (300, 150));
+        assert_eq!(scaled.image2.dimensions(), (300, 150));
+    }
+
+    #[test]
+    fn test_resample_to_match() {
+        let image = base_image();
+        let retina = image.resize_exact(600, 300, FilterType::Triangle);
+
+        let resampled = resample_to_match(&image, &retina, FilterType::Nearest);
+
+        assert_eq!(resampled.dimensions(), image.dimensions());
+    }
+
+    #[test]
+    fn test_estimate_scale_empty_image() {
+        let empty = DynamicImage::new_rgb8(0, 0);
+        let image = DynamicImage::new_rgb8(10, 10);
+
+        assert!(estimate_scale(&empty, &image).is_err());
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, GenericImageView};
+
+use crate::{DiffOptions, Differ, Metric};
+
+/// One pair of frames of two image sequences
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceFrame {
+    /// Frame number taken from the file names, or the position in the
+    /// sorted file list if they are not numbered
+    pub index: u64,
+    pub path1: PathBuf,
+    pub path2: PathBuf,
+    /// None if the frame failed, see `error`
+    pub score: Option<f64>,
+    /// Why the frame could not be scored, e.g. a file that does not load or
+    /// images of different sizes
+    pub error: Option<String>,
+    /// Whether the score is worse than the threshold, failed frames are
+    /// always flagged
+    pub flagged: bool,
+}
+
+/// Per frame comparison of two image sequences
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceComparison {
+    pub metric: Metric,
+    pub frame_count1: usize,
+    pub frame_count2: usize,
+    /// Frame numbers only one of the sequences has
+    pub missing: Vec<u64>,
+    pub frames: Vec<SequenceFrame>,
+}
+
+/// Files matching a glob pattern such as `renders/frame_*.png`, sorted by name
+pub fn expand_pattern(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let paths = glob::glob(pattern)
+        .map_err(|err| format!("Invalid pattern {}: {}", pattern, err))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+    let mut files: Vec<PathBuf> = paths.into_iter().filter(|path| path.is_file()).collect();
+    files.sort();
+    Ok(files)
+}
+
+// Last run of digits in the file name, "frame_0012.png" is frame 12
+fn frame_number(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = stem[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    stem[start..end].parse().ok()
+}
+
+// Frames keyed by their number, or by position if any file is not numbered
+fn number_frames(paths: Vec<PathBuf>) -> BTreeMap<u64, PathBuf> {
+    if paths.iter().all(|path| frame_number(path).is_some()) {
+        paths
+            .into_iter()
+            .map(|path| (frame_number(&path).unwrap(), path))
+            .collect()
+    } else {
+        paths
+            .into_iter()
+            .enumerate()
+            .map(|(i, path)| (i as u64, path))
+            .collect()
+    }
+}
+
+// Score of two identical images
+fn identical_score(metric: Metric) -> f64 {
+    match metric {
+        Metric::Ssim | Metric::MsSsim => 1.0,
+        Metric::Psnr => f64::INFINITY,
+        Metric::DiffRatio | Metric::Mse | Metric::Rmse | Metric::MaxAbsError => 0.0,
+    }
+}
+
+fn is_flagged(metric: Metric, score: f64, threshold: Option<f64>) -> bool {
+    match (threshold, metric.higher_is_better()) {
+        (None, _) => score != identical_score(metric),
+        (Some(threshold), true) => score < threshold,
+        (Some(threshold), false) => score > threshold,
+    }
+}
+
+/// Compare two image sequences frame by frame. Frames are matched by the
+/// number in their file names, frames are flagged when their score is worse
+/// than `threshold`, or when they differ at all if there is no threshold.
+pub fn compare_sequences(
+    pattern1: &str,
+    pattern2: &str,
+    metric: Metric,
+    threshold: Option<f64>,
+) -> Result<SequenceComparison, String> {
+    let differ = Differ::new(DiffOptions::new().metric(metric));
+    let load = |path: &Path| {
+        image::open(path).map_err(|err| format!("Error loading image {}: {}", path.display(), err))
+    };
+    diff_sequences(pattern1, pattern2, &differ, threshold, load, |_, _| {})
+}
+
+/// Compare two image sequences frame by frame with `differ`, like
+/// `compare_sequences`, loading each frame with `load`. The frames are
+/// scored in the metric of the differ. When its mode draws a diff image, the
+/// image of each flagged frame is passed to `on_flagged` as soon as the frame
+/// is compared, so a long sequence does not keep them all. A frame that does
+/// not load or cannot be scored is recorded as failed.
+pub fn diff_sequences(
+    pattern1: &str,
+    pattern2: &str,
+    differ: &Differ,
+    threshold: Option<f64>,
+    load: impl Fn(&Path) -> Result<DynamicImage, String>,
+    mut on_flagged: impl FnMut(&SequenceFrame, DynamicImage),
+) -> Result<SequenceComparison, String> {
+    let frames1 = number_frames(expand_pattern(pattern1)?);
+    let frames2 = number_frames(expand_pattern(pattern2)?);
+    if frames1.is_empty() || frames2.is_empty() {
+        return Err(format!(
+            "No files match {}",
+            if frames1.is_empty() {
+                pattern1
+            } else {
+                pattern2
+            }
+        ));
+    }
+
+    let missing = frames1
+        .keys()
+        .filter(|index| !frames2.contains_key(index))
+        .chain(frames2.keys().filter(|index| !frames1.contains_key(index)))
+        .copied()
+        .collect::<std::collections::BTreeSet<u64>>()
+        .into_iter()
+        .collect();
+
+    let metric = differ.options().metric;
+    let diff = |path1: &Path, path2: &Path| {
+        let (image1, image2) = (load(path1)?, load(path2)?);
+        let outcome = differ.compare(&image1, &image2)?;
+        match outcome.score {
+            Some(score) => Ok((score, outcome.image)),
+            None => Err(format!(
+                "Images must have the same dimensions, got {:?} and {:?}",
+                image1.dimensions(),
+                image2.dimensions()
+            )),
+        }
+    };
+    let mut frames = Vec::new();
+    for (&index, path1) in &frames1 {
+        let Some(path2) = frames2.get(&index) else {
+            continue;
+        };
+        let (score, image, error) = match diff(path1, path2) {
+            Ok((score, image)) => (Some(score), image, None),
+            Err(err) => (None, None, Some(err)),
+        };
+        let frame = SequenceFrame {
+            index,
+            path1: path1.clone(),
+            path2: path2.clone(),
+            score,
+            error,
+            flagged: score.is_none_or(|score| is_flagged(metric, score, threshold)),
+        };
+        if let Some(image) = image.filter(|_| frame.flagged) {
+            on_flagged(&frame, image);
+        }
+        frames.push(frame);
+    }
+
+    Ok(SequenceComparison {
+        metric,
+        frame_count1: frames1.len(),
+        frame_count2: frames2.len(),
+        missing,
+        frames,
+    })
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// JSON has no infinity, identical frames have a PSNR of null
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+impl SequenceComparison {
+    /// One line per compared frame: index, both paths, score, whether it was
+    /// flagged and why it failed. Failed frames have an empty score.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("index,image1,image2,score,flagged,error\n");
+        for frame in &self.frames {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                frame.index,
+                csv_field(&frame.path1.to_string_lossy()),
+                csv_field(&frame.path2.to_string_lossy()),
+                frame.score.map_or(String::new(), |score| score.to_string()),
+                frame.flagged,
+                csv_field(frame.error.as_deref().unwrap_or(""))
+            ));
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        let frames: Vec<String> = self
+            .frames
+            .iter()
+            .map(|frame| {
+                format!(
+                    "    {{\"index\": {}, \"image1\": {}, \"image2\": {}, \"score\": {}, \"flagged\": {}, \"error\": {}}}",
+                    frame.index,
+                    json_string(&frame.path1.to_string_lossy()),
+                    json_string(&frame.path2.to_string_lossy()),
+                    frame.score.map_or("null".to_string(), json_number),
+                    frame.flagged,
+                    frame.error.as_deref().map_or("null".to_string(), json_string)
+                )
+            })
+            .collect();
+        let missing: Vec<String> = self.missing.iter().map(u64::to_string).collect();
+        format!(
+            "{{\n  \"metric\": {},\n  \"frame_count1\": {},\n  \"frame_count2\": {},\n  \"missing\": [{}],\n  \"frames\": [\n{}\n  ]\n}}\n",
+            json_string(&format!("{:?}", self.metric)),
+            self.frame_count1,
+            self.frame_count2,
+            missing.join(", "),
+            frames.join(",\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffMode;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    fn write_sequence(dir: &Path, prefix: &str, values: &[(u64, u8)]) {
+        for &(index, value) in values {
+            let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([value, 0, 0])));
+            image
+                .save(dir.join(format!("{}_{:04}.png", prefix, index)))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_frame_number() {
+        assert_eq!(frame_number(Path::new("out/frame_0012.png")), Some(12));
+        assert_eq!(frame_number(Path::new("shot2_v3.png")), Some(3));
+        assert_eq!(frame_number(Path::new("title.png")), None);
+    }
+
+    #[test]
+    fn test_compare_sequences() {
+        let dir = std::env::temp_dir().join(format!("diff_img_sequence_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_sequence(&dir, "a", &[(1, 10), (2, 20), (3, 30)]);
+        write_sequence(&dir, "b", &[(1, 10), (2, 120), (4, 40)]);
+
+        let pattern = |prefix: &str| {
+            dir.join(format!("{}_*.png", prefix))
+                .to_string_lossy()
+                .to_string()
+        };
+        let comparison =
+            compare_sequences(&pattern("a"), &pattern("b"), Metric::DiffRatio, None).unwrap();
+        let with_threshold =
+            compare_sequences(&pattern("a"), &pattern("b"), Metric::DiffRatio, Some(0.5)).unwrap();
+        let differ = Differ::new(DiffOptions::new().mode(DiffMode::MarkWithColor));
+        let mut diffs = Vec::new();
+        diff_sequences(
+            &pattern("a"),
+            &pattern("b"),
+            &differ,
+            None,
+            |path| image::open(path).map_err(|err| err.to_string()),
+            |frame, image| diffs.push((frame.index, image.dimensions())),
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((comparison.frame_count1, comparison.frame_count2), (3, 3));
+        assert_eq!(comparison.missing, vec![3, 4]);
+        assert_eq!(
+            comparison
+                .frames
+                .iter()
+                .map(|f| (f.index, f.flagged))
+                .collect::<Vec<_>>(),
+            vec![(1, false), (2, true)]
+        );
+        assert!(!with_threshold.frames[1].flagged);
+        assert_eq!(diffs, vec![(2, (4, 4))]);
+
+        let csv = comparison.to_csv();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.lines().nth(2).unwrap().starts_with("2,"));
+        assert!(csv.ends_with(",true,\n"));
+        let json = comparison.to_json();
+        assert!(json.contains("\"missing\": [3, 4]"));
+        assert!(json.contains("\"metric\": \"DiffRatio\""));
+    }
+
+    #[test]
+    fn test_failed_frames() {
+        let dir = std::env::temp_dir().join(format!("diff_img_failed_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_sequence(&dir, "a", &[(1, 10), (2, 20), (3, 30)]);
+        write_sequence(&dir, "b", &[(1, 10), (3, 30)]);
+        DynamicImage::ImageRgb8(RgbImage::new(2, 2))
+            .save(dir.join("b_0002.png"))
+            .unwrap();
+        std::fs::write(dir.join("b_0003.png"), b"not a png").unwrap();
+
+        let pattern = |prefix: &str| {
+            dir.join(format!("{}_*.png", prefix))
+                .to_string_lossy()
+                .to_string()
+        };
+        let comparison =
+            compare_sequences(&pattern("a"), &pattern("b"), Metric::Psnr, None).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let frames = &comparison.frames;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            (frames[0].score, frames[0].flagged),
+            (Some(f64::INFINITY), false)
+        );
+        assert!(frames[1].score.is_none() && frames[1].flagged);
+        assert!(frames[1]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("same dimensions"));
+        assert!(frames[2].error.as_ref().unwrap().contains("b_0003.png"));
+        assert!(comparison
+            .to_csv()
+            .lines()
+            .nth(2)
+            .unwrap()
+            .contains(",,true,"));
+        assert!(comparison
+            .to_json()
+            .contains("\"score\": null, \"flagged\": true"));
+    }
+
+    #[test]
+    fn test_is_flagged() {
+        assert!(!is_flagged(Metric::Psnr, f64::INFINITY, None));
+        assert!(is_flagged(Metric::Psnr, 30.0, Some(40.0)));
+        assert!(!is_flagged(Metric::Ssim, 0.99, Some(0.95)));
+        assert!(is_flagged(Metric::Mse, 3.0, Some(2.0)));
+        assert_eq!(json_number(f64::INFINITY), "null");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+    }
+}
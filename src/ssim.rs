@@ -0,0 +1,306 @@
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Pixel};
+
+// Constants from Wang et al., "Image quality assessment: from error
+// visibility to structural similarity" (2004)
+const K1: f64 = 0.01;
+const K2: f64 = 0.03;
+const DYNAMIC_RANGE: f64 = 255.0;
+const WINDOW_RADIUS: usize = 5;
+const WINDOW_SIGMA: f64 = 1.5;
+
+// Per scale weights from Wang et al., "Multi-scale structural similarity
+// for image quality assessment" (2003)
+const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+struct Plane {
+    width: usize,
+    height: usize,
+    data: Vec<f64>,
+}
+
+impl Plane {
+    fn from_image(image: &DynamicImage) -> Self {
+        let (width, height) = image.dimensions();
+        let data = image
+            .pixels()
+            .map(|(_, _, pixel)| {
+                let rgb = pixel.to_rgb();
+                0.299 * rgb[0] as f64 + 0.587 * rgb[1] as f64 + 0.114 * rgb[2] as f64
+            })
+            .collect();
+        Plane {
+            width: width as usize,
+            height: height as usize,
+            data,
+        }
+    }
+
+    fn map(&self, other: &Plane, f: impl Fn(f64, f64) -> f64) -> Plane {
+        Plane {
+            width: self.width,
+            height: self.height,
+            data: self
+                .data
+                .iter()
+                .zip(&other.data)
+                .map(|(&a, &b)| f(a, b))
+                .collect(),
+        }
+    }
+
+    fn mean(&self) -> f64 {
+        self.data.iter().sum::<f64>() / self.data.len() as f64
+    }
+
+    // 2x2 box average, dropping an odd last row/column
+    fn downsample(&self) -> Plane {
+        let width = self.width / 2;
+        let height = self.height / 2;
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let i = 2 * y * self.width + 2 * x;
+                data.push(
+                    (self.data[i]
+                        + self.data[i + 1]
+                        + self.data[i + self.width]
+                        + self.data[i + self.width + 1])
+                        / 4.0,
+                );
+            }
+        }
+        Plane {
+            width,
+            height,
+            data,
+        }
+    }
+
+    // Separable gaussian blur, edges are clamped so the output keeps the input size
+    fn gaussian_blur(&self, kernel: &[f64]) -> Plane {
+        let radius = kernel.len() / 2;
+        let clamp = |v: isize, max: usize| v.clamp(0, max as isize - 1) as usize;
+
+        let mut horizontal = vec![0.0; self.data.len()];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                horizontal[y * self.width + x] = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, w)| {
+                        let sx = clamp(x as isize + k as isize - radius as isize, self.width);
+                        w * self.data[y * self.width + sx]
+                    })
+                    .sum();
+            }
+        }
+
+        let mut data = vec![0.0; self.data.len()];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                data[y * self.width + x] = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, w)| {
+                        let sy = clamp(y as isize + k as isize - radius as isize, self.height);
+                        w * horizontal[sy * self.width + x]
+                    })
+                    .sum();
+            }
+        }
+
+        Plane {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+}
+
+fn gaussian_kernel() -> Vec<f64> {
+    let kernel: Vec<f64> = (0..=2 * WINDOW_RADIUS)
+        .map(|i| {
+            let d = i as f64 - WINDOW_RADIUS as f64;
+            (-(d * d) / (2.0 * WINDOW_SIGMA * WINDOW_SIGMA)).exp()
+        })
+        .collect();
+    let total: f64 = kernel.iter().sum();
+    kernel.into_iter().map(|w| w / total).collect()
+}
+
+// Per pixel SSIM and contrast-structure maps
+fn ssim_maps(a: &Plane, b: &Plane) -> (Plane, Plane) {
+    let kernel = gaussian_kernel();
+    let c1 = (K1 * DYNAMIC_RANGE).powi(2);
+    let c2 = (K2 * DYNAMIC_RANGE).powi(2);
+
+    let mu_a = a.gaussian_blur(&kernel);
+    let mu_b = b.gaussian_blur(&kernel);
+    let a_sq = a.map(a, |x, y| x * y).gaussian_blur(&kernel);
+    let b_sq = b.map(b, |x, y| x * y).gaussian_blur(&kernel);
+    let ab = a.map(b, |x, y| x * y).gaussian_blur(&kernel);
+
+    let mut ssim = Vec::with_capacity(a.data.len());
+    let mut cs = Vec::with_capacity(a.data.len());
+    for i in 0..a.data.len() {
+        let (ma, mb) = (mu_a.data[i], mu_b.data[i]);
+        let var_a = a_sq.data[i] - ma * ma;
+        let var_b = b_sq.data[i] - mb * mb;
+        let cov = ab.data[i] - ma * mb;
+
+        let luminance = (2.0 * ma * mb + c1) / (ma * ma + mb * mb + c1);
+        let contrast_structure = (2.0 * cov + c2) / (var_a + var_b + c2);
+        ssim.push(luminance * contrast_structure);
+        cs.push(contrast_structure);
+    }
+
+    (
+        Plane {
+            width: a.width,
+            height: a.height,
+            data: ssim,
+        },
+        Plane {
+            width: a.width,
+            height: a.height,
+            data: cs,
+        },
+    )
+}
+
+fn planes(image1: &DynamicImage, image2: &DynamicImage) -> Result<(Plane, Plane), String> {
+    if image1.dimensions() != image2.dimensions() {
+        return Err(format!(
+            "Images must have the same dimensions, got {:?} and {:?}",
+            image1.dimensions(),
+            image2.dimensions()
+        ));
+    }
+    if image1.width() == 0 || image1.height() == 0 {
+        return Err("Images must not be empty".to_string());
+    }
+    Ok((Plane::from_image(image1), Plane::from_image(image2)))
+}
+
+/// Mean structural similarity of the luma of both images, 1.0 means identical
+pub fn calculate_ssim(image1: &DynamicImage, image2: &DynamicImage) -> Result<f64, String> {
+    let (a, b) = planes(image1, image2)?;
+    Ok(ssim_maps(&a, &b).0.mean())
+}
+
+/// Multi-scale SSIM over up to five scales. Small images use fewer scales,
+/// with the weights renormalized.
+pub fn calculate_ms_ssim(image1: &DynamicImage, image2: &DynamicImage) -> Result<f64, String> {
+    let (mut a, mut b) = planes(image1, image2)?;
+
+    let mut scales: Vec<(f64, f64)> = Vec::new();
+    loop {
+        let (ssim, cs) = ssim_maps(&a, &b);
+        scales.push((ssim.mean(), cs.mean()));
+        if scales.len() == MS_SSIM_WEIGHTS.len() || a.width < 2 || a.height < 2 {
+            break;
+        }
+        a = a.downsample();
+        b = b.downsample();
+    }
+
+    let weights = &MS_SSIM_WEIGHTS[..scales.len()];
+    let total: f64 = weights.iter().sum();
+    let last = scales.len() - 1;
+
+    Ok(scales
+        .iter()
+        .zip(weights)
+        .enumerate()
+        .map(|(i, (&(ssim, cs), weight))| {
+            let value = if i == last { ssim } else { cs };
+            value.max(0.0).powf(weight / total)
+        })
+        .product())
+}
+
+/// Per pixel SSIM as a greyscale image, white where the images are
+/// structurally identical and black where they are not similar at all
+pub fn create_ssim_map(
+    image1: &DynamicImage,
+    image2: &DynamicImage,
+) -> Result<DynamicImage, String> {
+    let (a, b) = planes(image1, image2)?;
+    let (ssim, _) = ssim_maps(&a, &b);
+
+    let map = GrayImage::from_fn(a.width as u32, a.height as u32, |x, y| {
+        let value = ssim.data[y as usize * a.width + x as usize];
+        Luma([(value.clamp(0.0, 1.0) * 255.0).round() as u8])
+    });
+    Ok(DynamicImage::ImageLuma8(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn gradient(width: u32, height: u32, offset: u8) -> DynamicImage {
+        DynamicImage::ImageRgb8(ImageBuffer::from_fn(width, height, |x, y| {
+            let v = ((x * 7 + y * 13) % 200) as u8 + offset;
+            Rgb([v, v, v])
+        }))
+    }
+
+    #[test]
+    fn test_ssim_identical_images() {
+        let image = gradient(32, 24, 0);
+
+        assert!((calculate_ssim(&image, &image).unwrap() - 1.0).abs() < 1e-9);
+        assert!((calculate_ms_ssim(&image, &image).unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ssim_different_images() {
+        let image1 = image::open("tests/images/image1.png").unwrap();
+        let image2 = image::open("tests/images/image2.png").unwrap();
+        let image1 = image1.resize_exact(200, 100, image::imageops::FilterType::Triangle);
+        let image2 = image2.resize_exact(200, 100, image::imageops::FilterType::Triangle);
+
+        let ssim = calculate_ssim(&image1, &image2).unwrap();
+        let ms_ssim = calculate_ms_ssim(&image1, &image2).unwrap();
+
+        assert!(ssim > 0.0 && ssim < 1.0);
+        assert!(ms_ssim > 0.0 && ms_ssim < 1.0);
+    }
+
+    #[test]
+    fn test_ssim_brightness_shift_keeps_structure() {
+        let image = gradient(32, 32, 0);
+        let brighter = gradient(32, 32, 10);
+        let noise = DynamicImage::ImageRgb8(ImageBuffer::from_fn(32, 32, |x, y| {
+            let v = ((x * 31 + y * 17) % 7 * 30) as u8;
+            Rgb([v, v, v])
+        }));
+
+        let shifted = calculate_ssim(&image, &brighter).unwrap();
+        let scrambled = calculate_ssim(&image, &noise).unwrap();
+
+        assert!(shifted > 0.9);
+        assert!(scrambled < shifted);
+    }
+
+    #[test]
+    fn test_ssim_map() {
+        let image = gradient(16, 8, 0);
+        let map = create_ssim_map(&image, &image).unwrap();
+
+        assert_eq!(map.dimensions(), (16, 8));
+        assert!(map.to_luma8().pixels().all(|p| p[0] == 255));
+    }
+
+    #[test]
+    fn test_ssim_dimension_mismatch() {
+        let image1 = gradient(16, 8, 0);
+        let image2 = gradient(8, 16, 0);
+
+        assert!(calculate_ssim(&image1, &image2).is_err());
+        assert!(calculate_ms_ssim(&image1, &image2).is_err());
+        assert!(create_ssim_map(&image1, &image2).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+#[cfg(feature = "svg")]
+use image::{DynamicImage, Rgba, RgbaImage};
+#[cfg(feature = "svg")]
+use resvg::{tiny_skia, usvg};
+
+#[cfg(feature = "svg")]
+use crate::InputDecoder;
+
+/// Size an SVG image is rasterized at
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SvgSize {
+    /// Multiple of the size the SVG declares, 2 for a retina render
+    Scale(f32),
+    /// Exact width and height in pixels, the drawing is stretched to fill them
+    Pixels(u32, u32),
+}
+
+/// Whether the data looks like an SVG document: XML whose root is an `svg`
+/// element, or gzip compressed SVGZ with a `.svgz` name
+pub fn is_svg(data: &[u8], filename: &str) -> bool {
+    let extension = std::path::Path::new(filename)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    if matches!(extension.as_deref(), Some("svg" | "svgz")) {
+        return true;
+    }
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    head.trim_start().starts_with('<') && head.contains("<svg")
+}
+
+/// Rasterize an SVG or SVGZ document. Text is not rendered, convert it to
+/// paths before comparing.
+#[cfg(feature = "svg")]
+pub fn rasterize_svg(data: &[u8], size: SvgSize) -> Result<DynamicImage, String> {
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())
+        .map_err(|err| format!("Invalid SVG: {}", err))?;
+    let declared = tree.size();
+    let (width, height) = match size {
+        SvgSize::Scale(scale) => (
+            (declared.width() * scale).round() as u32,
+            (declared.height() * scale).round() as u32,
+        ),
+        SvgSize::Pixels(width, height) => (width, height),
+    };
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| format!("Cannot rasterize SVG at {}x{}", width, height))?;
+
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / declared.width(),
+        height as f32 / declared.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    // tiny-skia keeps premultiplied alpha
+    let pixels = pixmap.pixels();
+    Ok(DynamicImage::ImageRgba8(RgbaImage::from_fn(
+        width,
+        height,
+        |x, y| {
+            let color = pixels[y as usize * width as usize + x as usize].demultiply();
+            Rgba([color.red(), color.green(), color.blue(), color.alpha()])
+        },
+    )))
+}
+
+/// Load and rasterize an SVG or SVGZ file, see `rasterize_svg`
+#[cfg(feature = "svg")]
+pub fn load_svg(filename: &str, size: SvgSize) -> Result<DynamicImage, String> {
+    let data = std::fs::read(filename)
+        .map_err(|err| format!("Error loading SVG {}: {}", filename, err))?;
+    rasterize_svg(&data, size).map_err(|err| format!("Error loading SVG {}: {}", filename, err))
+}
+
+/// Input decoder rasterizing SVG and SVGZ documents as a single page
+#[cfg(feature = "svg")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SvgDecoder {
+    pub size: SvgSize,
+}
+
+#[cfg(feature = "svg")]
+impl InputDecoder for SvgDecoder {
+    fn name(&self) -> &str {
+        "SVG"
+    }
+
+    fn can_decode(&self, data: &[u8], filename: &str) -> bool {
+        is_svg(data, filename)
+    }
+
+    fn decode_pages(&self, data: &[u8]) -> Result<Vec<DynamicImage>, String> {
+        rasterize_svg(data, self.size).map(|image| vec![image])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "svg")]
+    const SQUARE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="4">
+        <rect x="0" y="0" width="4" height="4" fill="#ff0000"/>
+    </svg>"##;
+
+    #[test]
+    fn test_is_svg() {
+        assert!(is_svg(b"", "icons/check.svg"));
+        assert!(is_svg(b"<?xml version=\"1.0\"?>\n<svg width=\"1\"/>", "-"));
+        assert!(!is_svg(b"\x89PNG\r\n\x1a\n", "-"));
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn test_rasterize_svg() {
+        use image::GenericImageView;
+
+        let image = rasterize_svg(SQUARE.as_bytes(), SvgSize::Scale(2.0)).unwrap();
+        let stretched = rasterize_svg(SQUARE.as_bytes(), SvgSize::Pixels(4, 4)).unwrap();
+
+        assert_eq!(image.dimensions(), (16, 8));
+        assert_eq!(image.get_pixel(3, 3), Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(12, 3), Rgba([0, 0, 0, 0]));
+        assert_eq!(stretched.get_pixel(1, 1), Rgba([255, 0, 0, 255]));
+        assert!(rasterize_svg(b"<svg", SvgSize::Scale(1.0)).is_err());
+    }
+}
@@ -1,32 +1,63 @@
+use diff_img::{encode_image, save_image, EncodeOptions};
 use image::DynamicImage;
 
-pub fn safe_save_image(image: DynamicImage, filename: &str) -> Result<String, String> {
-    // Check if path exists
-    let path = std::path::Path::new(filename);
+// "-" stands for stdin as an input and stdout as an output
+pub fn is_stdio(filename: &str) -> bool {
+    filename == "-"
+}
 
-    if !path.parent().unwrap().exists() {
-        return Err(format!(
-            "Path {} does not exist",
-            path.parent().unwrap().display()
-        ));
+// Save an image with the encoder options, "-" writes it to stdout, which
+// needs an explicit format
+pub fn safe_save_image(
+    image: DynamicImage,
+    filename: &str,
+    options: &EncodeOptions,
+) -> Result<String, String> {
+    if is_stdio(filename) {
+        let Some(format) = options.format else {
+            return Err("Writing an image to stdout needs an output format".to_string());
+        };
+        let mut stdout = std::io::stdout().lock();
+        encode_image(&image, format, options, &mut stdout)
+            .and_then(|_| std::io::Write::flush(&mut stdout).map_err(|err| err.to_string()))
+            .map_err(|err| format!("Error writing image to stdout: {}", err))?;
+        return Ok(filename.to_string());
     }
 
-    if let Err(msg) = image.save(filename) {
-        return Err(msg.to_string());
+    // Check if path exists, a bare file name is saved to the working directory
+    let path = std::path::Path::new(filename);
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        if !parent.exists() {
+            return Err(format!("Path {} does not exist", parent.display()));
+        }
     }
 
+    save_image(&image, filename, options)?;
+
     Ok(filename.to_string())
 }
 
+// Filename for one frame of a multi-frame output: "diff.png" becomes "diff_0003.png"
+pub fn frame_filename(filename: &str, index: usize) -> String {
+    let path = std::path::Path::new(filename);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}_{:04}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}_{:04}", stem, index),
+    };
+    path.with_file_name(name).to_string_lossy().to_string()
+}
 
 pub fn abs_diff(x: u8, y: u8) -> u8 {
     if x > y {
         return x - y;
     }
-    return y - x;
+    y - x
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,4 +68,18 @@ mod tests {
         assert_eq!(abs_diff(11, 11), 0);
         assert_eq!(abs_diff(0, 255), 255);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_frame_filename() {
+        assert_eq!(frame_filename("out/diff.png", 3), "out/diff_0003.png");
+        assert_eq!(frame_filename("diff", 12), "diff_0012");
+    }
+
+    #[test]
+    fn test_safe_save_image_errors() {
+        let image = DynamicImage::new_rgba8(4, 2);
+
+        assert!(safe_save_image(image.clone(), "-", &EncodeOptions::default()).is_err());
+        assert!(safe_save_image(image, "missing/dir/diff.png", &EncodeOptions::default()).is_err());
+    }
+}